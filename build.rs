@@ -0,0 +1,58 @@
+//! Builds the parsing tables of `assets/glc/lang.glc` once, they are embedded in the binary by
+//! `parser::embedded`
+
+#![allow(dead_code)]
+
+use std::{env, fs, path::Path};
+
+#[path = "src/grammar/mod.rs"]
+mod grammar;
+
+#[allow(non_snake_case)]
+#[path = "src/parser/SLR.rs"]
+mod SLR;
+#[path = "src/parser/action.rs"]
+mod action;
+#[path = "src/parser/closure.rs"]
+mod closure;
+#[path = "src/parser/conflicts.rs"]
+mod conflicts;
+#[path = "src/parser/lr1.rs"]
+mod lr1;
+#[path = "src/parser/parsing_table.rs"]
+mod parsing_table;
+#[path = "src/parser/tables.rs"]
+mod tables;
+
+// The parser modules find it in their parent, like in `src/parser/mod.rs`
+use action::Action;
+
+const GRAMMAR_FILE: &str = "assets/glc/lang.glc";
+
+fn main() {
+  println!("cargo:rerun-if-changed={GRAMMAR_FILE}");
+  println!("cargo:rerun-if-changed=src/grammar");
+  println!("cargo:rerun-if-changed=src/parser");
+
+  let glc_contents = fs::read_to_string(GRAMMAR_FILE)
+    .unwrap_or_else(|e| panic!("Could not read {GRAMMAR_FILE}: {e}"));
+  let mut glc =
+    grammar::parser::parse(&glc_contents).unwrap_or_else(|e| panic!("{GRAMMAR_FILE}: {e}"));
+
+  let kind = SLR::TableKind::declared(&glc).unwrap_or_else(|e| panic!("{GRAMMAR_FILE}: {e}"));
+
+  glc.compute_follow_set().expand();
+
+  let slr = SLR::SLR::with_kind(glc, kind);
+  if !slr.conflicts.is_empty() {
+    println!(
+      "cargo:warning={GRAMMAR_FILE} has {} parsing conflicts, `grammar check` shows them",
+      slr.conflicts.len()
+    );
+  }
+
+  let tables = tables::ParsingTables::from(slr);
+  let out_dir = env::var("OUT_DIR").expect("cargo sets OUT_DIR for build scripts");
+  fs::write(Path::new(&out_dir).join("tables.bin"), tables.encode())
+    .expect("Could not write the parsing tables");
+}
@@ -0,0 +1,26 @@
+use clap::Args;
+use miette::Result as MietteResult;
+
+use crate::codegen::vm::{assembly, VMCodeGenerator};
+
+use super::PileCompiler;
+
+#[derive(Args)]
+pub struct Asm {
+  #[arg(required = true, short, long)]
+  pub filename: String,
+
+  #[arg(short, long, default_value = "output")]
+  pub output: String,
+}
+
+impl PileCompiler {
+  pub fn asm(Asm { filename, output }: &Asm) -> MietteResult<(), Box<dyn std::error::Error>> {
+    let source = std::fs::read_to_string(filename)?;
+    let container = assembly::assemble(&source).map_err(miette::Report::new)?;
+
+    VMCodeGenerator::encode_byte_code(&container, output.clone())?;
+
+    Ok(())
+  }
+}
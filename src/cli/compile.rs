@@ -2,10 +2,8 @@ use clap::{Args, ValueEnum};
 use miette::Result as MietteResult;
 
 use crate::{
-  codegen::{self, CodeGeneratorTarget},
-  grammar, lexer,
-  parser::SLR::SLR,
-  semantic::SemanticAnalyzer,
+  codegen::{self, CodeGeneratorOptions, CodeGeneratorTarget, EmitKind},
+  frontend::Program,
 };
 
 use super::PileCompiler;
@@ -15,6 +13,33 @@ use super::PileCompiler;
 pub enum Codegen {
   VM,
   LLVM,
+  Wasm,
+}
+
+#[derive(ValueEnum, Clone)]
+pub enum Emit {
+  /// Textual LLVM IR (.ll)
+  LlvmIr,
+  /// LLVM bitcode (.bc)
+  Bitcode,
+  /// Native assembly (.s)
+  Asm,
+  /// Object file (.o)
+  Obj,
+  /// Executable
+  Exe,
+}
+
+impl From<&Emit> for EmitKind {
+  fn from(emit: &Emit) -> Self {
+    match emit {
+      Emit::LlvmIr => EmitKind::LlvmIr,
+      Emit::Bitcode => EmitKind::Bitcode,
+      Emit::Asm => EmitKind::Asm,
+      Emit::Obj => EmitKind::Obj,
+      Emit::Exe => EmitKind::Exe,
+    }
+  }
 }
 
 #[derive(Args)]
@@ -27,6 +52,36 @@ pub struct Compile {
 
   #[arg(short, long, default_value = "output")]
   pub output: String,
+
+  /// Also write the textual form of the output (only used by the wasm target)
+  #[arg(long)]
+  pub emit_text: bool,
+
+  /// Optimization level (used by the vm and llvm targets)
+  #[arg(short = 'O', default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=3))]
+  pub optimization_level: u8,
+
+  /// What to write (only used by the llvm target)
+  #[arg(long, default_value = "exe")]
+  pub emit: Emit,
+
+  /// The target triple of the native output, the host by default (only used by the llvm target,
+  /// the executables are only linked for the host)
+  #[arg(long)]
+  pub target: Option<String>,
+
+  /// Stop the program when an integer operation overflows (only used by the llvm target, the vm
+  /// has `run --overflow trapping`)
+  #[arg(long)]
+  pub checked_arith: bool,
+
+  /// How many values the stack holds, overrides the `pragma stack-size` of the source
+  #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+  pub stack_size: Option<usize>,
+
+  /// Parse with the grammar in this file instead of the one built in the compiler
+  #[arg(long)]
+  pub grammar: Option<String>,
 }
 
 impl PileCompiler {
@@ -35,29 +90,37 @@ impl PileCompiler {
       filename,
       codegen,
       output,
+      emit_text,
+      optimization_level,
+      emit,
+      target,
+      checked_arith,
+      stack_size,
+      grammar,
     }: &Compile,
   ) -> MietteResult<(), Box<dyn std::error::Error>> {
-    // Lexer
-    let lang_contents = std::fs::read_to_string(filename)?;
-    let tokens = lexer::generate::compute_tokens(&lang_contents)?;
-
-    // Parser
-    let glc_contents = std::fs::read_to_string("assets/glc/lang.glc")?;
-    let mut glc = grammar::parser::parse(&glc_contents)?;
-
-    glc.compute_follow_set().expand();
-
-    let abstract_syntax_tree = SLR::new(glc)
-      .parse(tokens, &lang_contents)?
-      .ok_or("Failed to parse")?;
+    let Program {
+      source,
+      pragmas,
+      ast,
+    } = Program::load(filename, grammar.as_deref())?;
 
-    SemanticAnalyzer::new(lang_contents).analyze(&abstract_syntax_tree)?;
+    let options = CodeGeneratorOptions {
+      emit_text: *emit_text,
+      source_code: Some(source),
+      optimization_level: *optimization_level,
+      emit: emit.into(),
+      target_triple: target.clone(),
+      checked_arithmetic: *checked_arith,
+      stack_size: stack_size.or(pragmas.stack_size),
+    };
 
     match codegen {
-      Codegen::VM => codegen::code_generator(CodeGeneratorTarget::VirtualMachine),
-      Codegen::LLVM => codegen::code_generator(CodeGeneratorTarget::LLVM),
+      Codegen::VM => codegen::code_generator(CodeGeneratorTarget::VirtualMachine, options),
+      Codegen::LLVM => codegen::code_generator(CodeGeneratorTarget::LLVM, options),
+      Codegen::Wasm => codegen::code_generator(CodeGeneratorTarget::Wasm, options),
     }
-    .generate(abstract_syntax_tree, output.clone())?;
+    .generate(ast, output.clone())?;
 
     Ok(())
   }
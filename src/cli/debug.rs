@@ -0,0 +1,89 @@
+use std::io::{BufRead, Write};
+
+use clap::Args;
+use miette::Result as MietteResult;
+
+use crate::{
+  codegen::vm::VMCodeGenerator,
+  frontend::Program,
+  interpreter::{
+    debugger::{Debugger, DebuggerAction},
+    vm::{VMInterpreter, DEFAULT_STACK_SIZE, VM},
+  },
+};
+
+use super::{run::Overflow, PileCompiler};
+
+#[derive(Args)]
+pub struct Debug {
+  /// A compiled program or a .pile source, which is compiled first
+  #[arg(required = true, short, long)]
+  pub filename: String,
+
+  #[arg(long, default_value = "wrapping")]
+  pub overflow: Overflow,
+
+  /// Parse with the grammar in this file instead of the one built in the compiler (only used with
+  /// a .pile source)
+  #[arg(long)]
+  pub grammar: Option<String>,
+}
+
+impl PileCompiler {
+  pub fn debug(
+    Debug {
+      filename,
+      overflow,
+      grammar,
+    }: &Debug,
+  ) -> MietteResult<(), Box<dyn std::error::Error>> {
+    let (bytecode, debug_info, stack_size) = if filename.ends_with(".pile") {
+      let Program {
+        source,
+        pragmas,
+        ast,
+      } = Program::load(filename, grammar.as_deref())?;
+
+      let mut generator = VMCodeGenerator::new();
+      generator.generate_byte_code(&ast)?;
+
+      (
+        generator.bytecode().to_vec(),
+        Some(generator.debug_info(&source)),
+        pragmas.stack_size,
+      )
+    } else {
+      let container = VMInterpreter::open(filename)?;
+      (
+        container.bytecode,
+        container.debug_info,
+        container.stack_size,
+      )
+    };
+
+    let vm = VM::new()
+      .with_overflow(overflow.into())
+      .with_debug_info(debug_info.clone())
+      .with_stack_size(stack_size.unwrap_or(DEFAULT_STACK_SIZE));
+    let mut debugger = Debugger::new(bytecode, debug_info, vm);
+
+    let mut lines = std::io::stdin().lock().lines();
+
+    loop {
+      print!("{}", debugger.prompt());
+      std::io::stdout().flush()?;
+
+      // End of input (Ctrl-D)
+      let Some(line) = lines.next() else {
+        println!();
+        break;
+      };
+
+      if let DebuggerAction::Quit = debugger.handle_command(&line?) {
+        break;
+      }
+    }
+
+    Ok(())
+  }
+}
@@ -0,0 +1,32 @@
+use clap::Args;
+use miette::Result as MietteResult;
+
+use crate::{codegen::vm::assembly, interpreter::vm::VMInterpreter};
+
+use super::PileCompiler;
+
+#[derive(Args)]
+pub struct Disasm {
+  #[arg(required = true, short, long)]
+  pub filename: String,
+
+  /// Write the listing to a file instead of the standard output
+  #[arg(short, long)]
+  pub output: Option<String>,
+}
+
+impl PileCompiler {
+  pub fn disasm(
+    Disasm { filename, output }: &Disasm,
+  ) -> MietteResult<(), Box<dyn std::error::Error>> {
+    let container = VMInterpreter::open(filename)?;
+    let listing = assembly::disassemble(&container);
+
+    match output {
+      Some(output) => std::fs::write(output, listing)?,
+      None => print!("{listing}"),
+    }
+
+    Ok(())
+  }
+}
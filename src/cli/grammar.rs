@@ -0,0 +1,120 @@
+use clap::{Args, Subcommand, ValueEnum};
+use miette::Result as MietteResult;
+
+use crate::parser::{embedded, SLR::TableKind};
+
+use super::PileCompiler;
+
+#[derive(ValueEnum, Clone)]
+pub enum Tables {
+  /// LR(0) states, reductions on the FOLLOW sets
+  Slr,
+  /// LR(0) states, reductions on the LR(1) lookaheads
+  Lalr,
+  /// Canonical LR(1) states
+  Lr1,
+}
+
+impl From<&Tables> for TableKind {
+  fn from(tables: &Tables) -> Self {
+    match tables {
+      Tables::Slr => TableKind::Slr,
+      Tables::Lalr => TableKind::Lalr,
+      Tables::Lr1 => TableKind::Lr1,
+    }
+  }
+}
+
+#[derive(Args)]
+pub struct Grammar {
+  #[command(subcommand)]
+  pub command: GrammarCommand,
+}
+
+#[derive(Subcommand)]
+pub enum GrammarCommand {
+  /// Build the parsing tables of a grammar and report their conflicts
+  Check {
+    /// A grammar written like assets/glc/lang.glc, the one built in the compiler by default
+    file: Option<String>,
+
+    /// How to build the tables, the `%tables` of the grammar by default
+    #[arg(short, long)]
+    tables: Option<Tables>,
+  },
+  /// Count the states and the conflicts of the SLR, LALR(1) and LR(1) tables of a grammar
+  Compare {
+    /// A grammar written like assets/glc/lang.glc, the one built in the compiler by default
+    file: Option<String>,
+  },
+}
+
+impl PileCompiler {
+  pub fn grammar(Grammar { command }: &Grammar) -> MietteResult<(), Box<dyn std::error::Error>> {
+    match command {
+      GrammarCommand::Check { file, tables } => {
+        Self::grammar_check(file.as_deref(), tables.as_ref().map(TableKind::from))
+      }
+      GrammarCommand::Compare { file } => Self::grammar_compare(file.as_deref()),
+    }
+  }
+
+  fn read_grammar(file: Option<&str>) -> Result<String, std::io::Error> {
+    match file {
+      Some(file) => std::fs::read_to_string(file),
+      None => Ok(embedded::GRAMMAR.to_string()),
+    }
+  }
+
+  fn grammar_check(
+    file: Option<&str>,
+    kind: Option<TableKind>,
+  ) -> MietteResult<(), Box<dyn std::error::Error>> {
+    let glc_contents = Self::read_grammar(file)?;
+
+    let slr = embedded::from_grammar(&glc_contents, kind)?;
+    let kind = slr.kind;
+
+    for conflict in &slr.conflicts {
+      println!("{conflict}");
+    }
+
+    match slr.conflicts.len() {
+      0 => {
+        println!("No conflicts");
+        Ok(())
+      }
+      count => Err(format!("The grammar has {count} {kind} conflicts").into()),
+    }
+  }
+
+  fn grammar_compare(file: Option<&str>) -> MietteResult<(), Box<dyn std::error::Error>> {
+    let glc_contents = Self::read_grammar(file)?;
+
+    println!(
+      "{:<10}{:>8}{:>15}{:>16}",
+      "tables", "states", "shift/reduce", "reduce/reduce"
+    );
+
+    for kind in [TableKind::Slr, TableKind::Lalr, TableKind::Lr1] {
+      let slr = embedded::from_grammar(&glc_contents, Some(kind))?;
+      let count = |conflict_kind| {
+        slr
+          .conflicts
+          .iter()
+          .filter(|conflict| conflict.kind() == conflict_kind)
+          .count()
+      };
+
+      println!(
+        "{:<10}{:>8}{:>15}{:>16}",
+        kind.to_string(),
+        slr.states.len(),
+        count("shift/reduce"),
+        count("reduce/reduce")
+      );
+    }
+
+    Ok(())
+  }
+}
@@ -1,6 +1,11 @@
 use clap::{Parser, Subcommand};
 
+pub mod asm;
 pub mod compile;
+pub mod debug;
+pub mod disasm;
+pub mod grammar;
+pub mod repl;
 pub mod run;
 
 #[derive(Parser)]
@@ -15,6 +20,16 @@ pub struct Cli {
 pub enum Commands {
   Compile(compile::Compile),
   Run(run::Run),
+  /// Start an interactive session
+  Repl(repl::Repl),
+  /// Step through a program with breakpoints and watches
+  Debug(debug::Debug),
+  /// Print the instructions of a compiled program
+  Disasm(disasm::Disasm),
+  /// Compile a listing written like the disassembler output
+  Asm(asm::Asm),
+  /// Inspect a grammar of the language
+  Grammar(grammar::Grammar),
 }
 
 pub struct PileCompiler;
@@ -0,0 +1,47 @@
+use std::io::{BufRead, Write};
+
+use clap::Args;
+use miette::Result as MietteResult;
+
+use crate::{
+  interpreter::repl::{self, ReplAction},
+  parser::embedded,
+};
+
+use super::{run::Overflow, PileCompiler};
+
+#[derive(Args)]
+pub struct Repl {
+  #[arg(long, default_value = "wrapping")]
+  pub overflow: Overflow,
+
+  /// Parse with the grammar in this file instead of the one built in the compiler
+  #[arg(long)]
+  pub grammar: Option<String>,
+}
+
+impl PileCompiler {
+  pub fn repl(Repl { overflow, grammar }: &Repl) -> MietteResult<(), Box<dyn std::error::Error>> {
+    let parser = embedded::parser(grammar.as_deref())?;
+    let mut session = repl::Repl::new(parser, overflow.into());
+
+    let mut lines = std::io::stdin().lock().lines();
+
+    loop {
+      print!("{}", session.prompt());
+      std::io::stdout().flush()?;
+
+      // End of input (Ctrl-D)
+      let Some(line) = lines.next() else {
+        println!();
+        break;
+      };
+
+      if let ReplAction::Quit = session.handle_line(&line?) {
+        break;
+      }
+    }
+
+    Ok(())
+  }
+}
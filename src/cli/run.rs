@@ -1,19 +1,125 @@
-use clap::Args;
+use clap::{Args, ValueEnum};
 
-use crate::interpreter::vm;
+use crate::{
+  codegen::{self, CodeGeneratorOptions},
+  frontend::Program,
+  interpreter::vm::{self, arithmetic::overflow::OverflowMode, errors::into_report},
+};
 use miette::Result as MietteResult;
 
 use super::PileCompiler;
 
+/// What happens when an integer operation overflows
+#[derive(ValueEnum, Clone)]
+pub enum Overflow {
+  /// Wrap around (two's complement)
+  Wrapping,
+  /// Clamp to the minimum or the maximum of the type
+  Saturating,
+  /// Stop the program with an error
+  Trapping,
+}
+
+impl From<&Overflow> for OverflowMode {
+  fn from(overflow: &Overflow) -> Self {
+    match overflow {
+      Overflow::Wrapping => OverflowMode::Wrapping,
+      Overflow::Saturating => OverflowMode::Saturating,
+      Overflow::Trapping => OverflowMode::Trapping,
+    }
+  }
+}
+
 #[derive(Args)]
 pub struct Run {
+  /// A compiled program, or a .pile source with --jit
   #[arg(required = true, short, long)]
   pub filename: String,
+
+  #[arg(long, default_value = "wrapping")]
+  pub overflow: Overflow,
+
+  /// Print every instruction and the stack before it is executed
+  #[arg(long)]
+  pub trace: bool,
+
+  /// Compile the source to native code in memory and run it (with the llvm backend)
+  #[arg(long)]
+  pub jit: bool,
+
+  /// Optimization level (only used with --jit)
+  #[arg(short = 'O', default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=3))]
+  pub optimization_level: u8,
+
+  /// Stop the program when an integer operation overflows (only used with --jit, like
+  /// --overflow trapping)
+  #[arg(long)]
+  pub checked_arith: bool,
+
+  /// How many values the stack holds, overrides the size the program was compiled with
+  #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+  pub stack_size: Option<usize>,
+
+  /// Parse with the grammar in this file instead of the one built in the compiler (only used with
+  /// --jit)
+  #[arg(long)]
+  pub grammar: Option<String>,
 }
 
 impl PileCompiler {
-  pub fn run(Run { filename }: &Run) -> MietteResult<(), Box<dyn std::error::Error>> {
-    vm::VMInterpreter::run(filename)?;
+  pub fn run(
+    Run {
+      filename,
+      overflow,
+      trace,
+      jit,
+      optimization_level,
+      checked_arith,
+      stack_size,
+      grammar,
+    }: &Run,
+  ) -> MietteResult<(), Box<dyn std::error::Error>> {
+    if *jit {
+      return Self::run_jit(
+        filename,
+        *optimization_level,
+        *checked_arith,
+        *stack_size,
+        grammar.as_deref(),
+      );
+    }
+
+    vm::VMInterpreter::run(filename, overflow.into(), *trace, *stack_size).map_err(into_report)?;
+
+    Ok(())
+  }
+
+  fn run_jit(
+    filename: &str,
+    optimization_level: u8,
+    checked_arithmetic: bool,
+    stack_size: Option<usize>,
+    grammar: Option<&str>,
+  ) -> MietteResult<(), Box<dyn std::error::Error>> {
+    let Program {
+      source,
+      pragmas,
+      ast,
+    } = Program::load(filename, grammar)?;
+
+    let options = CodeGeneratorOptions {
+      source_code: Some(source),
+      optimization_level,
+      checked_arithmetic,
+      stack_size: stack_size.or(pragmas.stack_size),
+      ..Default::default()
+    };
+
+    // Like the native executables, the program exits with the value left on top of the stack
+    let code = codegen::llvm::jit::run(&ast, &options)?;
+    if code != 0 {
+      std::process::exit(code);
+    }
 
     Ok(())
   }
@@ -1,20 +1,25 @@
-use inkwell::values::{FunctionValue, IntValue, PointerValue};
+use inkwell::values::{BasicValueEnum, FunctionValue, IntValue, PointerValue};
 
-use crate::codegen::llvm::{compiler::Compiler, globals::stack::Stack};
-
-use super::abort::AbortBuiltin;
+use crate::{
+  codegen::llvm::{
+    compiler::Compiler,
+    externs::{exit::ExitExtern, printf::PrintfExtern},
+    globals::stack::Stack,
+  },
+  semantic::stack_frame::StackType,
+};
 
 pub const POP_BUILTIN_FUNCTION_NAME: &str = "pop";
 
 pub struct PopBuiltin;
 
 impl PopBuiltin {
-  pub fn declare<'ctx>(compiler: &Compiler<'ctx>, stack: &'ctx Stack<'ctx>) {
+  pub fn declare<'ctx>(compiler: &Compiler<'ctx>, stack: &Stack<'ctx>) {
     let builder = compiler.builder();
     let module = compiler.module();
 
-    let i32_type = compiler.i32_type();
-    let remove_type = i32_type.fn_type(&[], false);
+    let slot_type = compiler.slot_type();
+    let remove_type = slot_type.fn_type(&[compiler.string_type().into()], false); // Location
     let remove_func = module.add_function("pop", remove_type, None);
     let entry = compiler.append_basic_block(remove_func, "entry");
 
@@ -31,13 +36,16 @@ impl PopBuiltin {
 
     // 2. If the stack is empty
     builder.position_at_end(stack_empty_block);
-    AbortBuiltin::call_from_values(
+    let message_ptr = builder.build_global_string_ptr(
+      "[ABORT @ pop]: stack underflow at %s\n",
+      "error_message_stack_empty",
+    );
+    let location = remove_func.get_first_param().unwrap();
+    PrintfExtern::call(
       compiler,
-      "[ABORT @ pop]: stack is already empty\n",
-      1,
-      Some("error_message_stack_empty".to_string()),
-      Some("error_stack_empty".to_string()),
+      &[message_ptr.as_pointer_value().into(), location.into()],
     );
+    ExitExtern::call_from_int(compiler, 1);
     builder.build_unreachable();
 
     // 3. If not then remove the value
@@ -45,23 +53,27 @@ impl PopBuiltin {
     let top_ptr = stack.load_top_ptr(compiler);
     let prev_ptr = unsafe {
       builder.build_in_bounds_gep(
-        i32_type,
+        slot_type,
         top_ptr,
-        &[i32_type.const_int(u64::MAX, true)],
+        &[slot_type.const_int(u64::MAX, true)],
         "prevPtr",
       )
     };
     builder.build_store::<PointerValue>(stack.stack_top_ptr(), prev_ptr);
 
     // Load and return the top element
-    let top_element = builder.build_load(i32_type, prev_ptr, "topElement");
+    let top_element = builder.build_load(slot_type, prev_ptr, "topElement");
     builder.build_return(Some(&top_element));
   }
 
   pub fn call<'ctx>(compiler: &Compiler<'ctx>) -> IntValue<'ctx> {
     let builder = compiler.builder();
 
-    let top_element = builder.build_call(Self::get(compiler), &[], "topElement");
+    let top_element = builder.build_call(
+      Self::get(compiler),
+      &[compiler.location().into()],
+      "topElement",
+    );
 
     top_element
       .try_as_basic_value()
@@ -70,6 +82,56 @@ impl PopBuiltin {
       .into_int_value()
   }
 
+  /// Pop the value on top of the stack, with the type it was pushed with
+  pub fn pop_value<'ctx>(
+    compiler: &Compiler<'ctx>,
+  ) -> anyhow::Result<(BasicValueEnum<'ctx>, StackType)> {
+    let type_ = compiler
+      .pop_type()
+      .ok_or(anyhow::anyhow!("Pop from an empty stack"))?;
+    let slot = Self::call(compiler);
+
+    Ok((Self::from_slot(compiler, slot, &type_), type_))
+  }
+
+  /// Pop an i32, like the bounds of a range
+  pub fn pop_int<'ctx>(compiler: &Compiler<'ctx>) -> anyhow::Result<IntValue<'ctx>> {
+    match Self::pop_value(compiler)? {
+      (value, StackType::I32) => Ok(value.into_int_value()),
+      (_, type_) => Err(anyhow::anyhow!(
+        "Expected an i32 on the stack, found {}",
+        type_
+      )),
+    }
+  }
+
+  /// The value stored in a stack slot, see `PushBuiltin::push_value`
+  fn from_slot<'ctx>(
+    compiler: &Compiler<'ctx>,
+    slot: IntValue<'ctx>,
+    type_: &StackType,
+  ) -> BasicValueEnum<'ctx> {
+    let builder = compiler.builder();
+
+    match type_ {
+      StackType::I32 => builder
+        .build_int_truncate(slot, compiler.i32_type(), "slot_to_int")
+        .into(),
+      StackType::I64 => slot.into(),
+      StackType::Bool => builder
+        .build_int_truncate(slot, compiler.bool_type(), "slot_to_bool")
+        .into(),
+      StackType::F32 => {
+        let bits = builder.build_int_truncate(slot, compiler.i32_type(), "float_bits");
+        builder.build_bitcast(bits, compiler.f32_type(), "slot_to_float")
+      }
+      StackType::F64 => builder.build_bitcast(slot, compiler.f64_type(), "slot_to_double"),
+      StackType::String => builder
+        .build_int_to_ptr(slot, compiler.string_type(), "slot_to_string")
+        .into(),
+    }
+  }
+
   pub fn get<'ctx>(compiler: &Compiler<'ctx>) -> FunctionValue<'ctx> {
     let module = compiler.module();
     module
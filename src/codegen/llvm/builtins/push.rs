@@ -1,17 +1,29 @@
-use crate::codegen::llvm::{compiler::Compiler, globals::stack::Stack};
+use inkwell::values::{BasicValueEnum, IntValue};
 
-use super::abort::AbortBuiltin;
+use crate::{
+  codegen::llvm::{
+    compiler::Compiler,
+    externs::{exit::ExitExtern, printf::PrintfExtern},
+    globals::stack::Stack,
+  },
+  semantic::stack_frame::StackType,
+};
 
 pub struct PushBuiltin;
 
 // TODO: Copy the arch-llvm push, it works fine
 impl PushBuiltin {
-  pub fn declare<'ctx>(compiler: &Compiler<'ctx>, stack: &'ctx Stack<'ctx>) {
+  pub fn declare<'ctx>(compiler: &Compiler<'ctx>, stack: &Stack<'ctx>) {
     let builder = compiler.builder();
     let module = compiler.module();
 
-    let i32_type = compiler.i32_type();
-    let insert_type = compiler.void_type().fn_type(&[i32_type.into()], false);
+    let insert_type = compiler.void_type().fn_type(
+      &[
+        compiler.slot_type().into(),   // Value
+        compiler.string_type().into(), // Location of the push in the source
+      ],
+      false,
+    );
     let insert_func = module.add_function("push", insert_type, None);
     let entry = compiler.append_basic_block(insert_func, "entry");
 
@@ -31,13 +43,19 @@ impl PushBuiltin {
       {
         builder.position_at_end(stack_full_block);
 
-        AbortBuiltin::call_from_values(
+        let message_ptr = builder.build_global_string_ptr(
+          &format!(
+            "[ABORT @ push]: stack overflow at %s (the stack holds {} values)\n",
+            stack.size
+          ),
+          "error_message_stack_full",
+        );
+        let location = insert_func.get_nth_param(1).unwrap();
+        PrintfExtern::call(
           compiler,
-          "[ABORT @ push]: stack is already full\n",
-          1,
-          Some("error_message_stack_full".to_string()),
-          Some("error_stack_full".to_string()),
+          &[message_ptr.as_pointer_value().into(), location.into()],
         );
+        ExitExtern::call_from_int(compiler, 1);
 
         builder.build_unreachable();
       }
@@ -56,14 +74,55 @@ impl PushBuiltin {
     }
   }
 
-  pub fn call_from_int(compiler: &Compiler<'_>, value: i32) {
-    let builder = compiler.builder();
+  /// Push a value of any type, its type is remembered so it can be popped back
+  pub fn push_value<'ctx>(
+    compiler: &Compiler<'ctx>,
+    value: BasicValueEnum<'ctx>,
+    type_: StackType,
+  ) -> anyhow::Result<()> {
+    let slot = Self::to_slot(compiler, value)?;
+    Self::call(compiler, &[slot.into(), compiler.location().into()]);
 
-    builder.build_call(
-      Self::get(compiler),
-      &[compiler.i32_type().const_int(value as u64, false).into()],
-      "push_int_call",
-    );
+    compiler.push_type(type_);
+
+    Ok(())
+  }
+
+  /// The bits of a value in a stack slot: the integers are sign extended (a bool is zero
+  /// extended), the floats keep their bits and the strings are stored as their address
+  fn to_slot<'ctx>(
+    compiler: &Compiler<'ctx>,
+    value: BasicValueEnum<'ctx>,
+  ) -> anyhow::Result<IntValue<'ctx>> {
+    let builder = compiler.builder();
+    let slot_type = compiler.slot_type();
+
+    Ok(match value {
+      BasicValueEnum::IntValue(value) => match value.get_type().get_bit_width() {
+        1 => builder.build_int_z_extend(value, slot_type, "bool_to_slot"),
+        64 => value,
+        _ => builder.build_int_s_extend(value, slot_type, "int_to_slot"),
+      },
+      BasicValueEnum::FloatValue(value) if value.get_type() == compiler.f32_type() => {
+        let bits = builder
+          .build_bitcast(value, compiler.i32_type(), "float_bits")
+          .into_int_value();
+        builder.build_int_z_extend(bits, slot_type, "float_to_slot")
+      }
+      BasicValueEnum::FloatValue(value) => builder
+        .build_bitcast(value, slot_type, "double_to_slot")
+        .into_int_value(),
+      BasicValueEnum::PointerValue(value) => {
+        builder.build_ptr_to_int(value, slot_type, "string_to_slot")
+      }
+      // Arrays, structs and vectors are never pushed
+      other => {
+        return Err(anyhow::anyhow!(
+          "Unsupported value on the stack: {:?}",
+          other
+        ));
+      }
+    })
   }
 
   pub fn call(compiler: &Compiler<'_>, args: &[inkwell::values::BasicMetadataValueEnum]) {
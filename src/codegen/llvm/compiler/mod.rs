@@ -1,13 +1,72 @@
-use std::path::Path;
+use std::{
+  cell::{Cell, RefCell},
+  collections::HashMap,
+};
 
-use inkwell::{builder::Builder, context::Context, module::Module};
+use inkwell::{
+  basic_block::BasicBlock, builder::Builder, context::Context, module::Module,
+  types::BasicTypeEnum, values::PointerValue,
+};
+
+use crate::{lexer::tokens::Type, semantic::stack_frame::StackType};
 
 // use super::wrapper::context::Context;
 
+/// The types a procedure takes from the stack and the ones it leaves on it
+pub type Signature = (Vec<Type>, Vec<Type>);
+
+/// The blocks that are still waiting for their `end`
+#[derive(Debug, Clone)]
+pub enum Block<'ctx> {
+  /// Holds the basic block the builder was positioned at before the procedure definition and the
+  /// types that were on the stack outside of it
+  Proc(BasicBlock<'ctx>, Vec<StackType>),
+  /// The blocks of the `else` branch and of the code after the condition, and the types on the
+  /// stack when both branches start
+  If {
+    otherwise: BasicBlock<'ctx>,
+    exit: BasicBlock<'ctx>,
+    types: Vec<StackType>,
+  },
+  /// The block of the code after the condition
+  Else(BasicBlock<'ctx>),
+  /// The blocks of the condition, the body and the exit of the loop
+  While {
+    condition: BasicBlock<'ctx>,
+    body: BasicBlock<'ctx>,
+    exit: BasicBlock<'ctx>,
+  },
+  /// The counter slot and the blocks of a counted loop
+  Range {
+    name: Option<String>,
+    counter: PointerValue<'ctx>,
+    condition: BasicBlock<'ctx>,
+    exit: BasicBlock<'ctx>,
+  },
+}
+
 pub struct Compiler<'ctx> {
   context: &'ctx Context,
   module: Module<'ctx>,
   builder: Builder<'ctx>,
+  blocks: RefCell<Vec<Block<'ctx>>>,
+  /// The variables in scope and their types, the innermost declarations are the last ones
+  variables: RefCell<Vec<(String, PointerValue<'ctx>, Type)>>,
+  /// How many variables were in scope when each procedure or range body started
+  scopes: RefCell<Vec<usize>>,
+  /// The types of the values on the stack at the current point of the program. The semantic
+  /// analysis makes sure they are the same whatever path leads there.
+  types: RefCell<Vec<StackType>>,
+  /// The inputs and the outputs of the procedures
+  procedures: RefCell<HashMap<String, Signature>>,
+  /// Whether the integer operations check for overflows (`--checked-arith`)
+  checked_arithmetic: bool,
+  /// The source of the program, to tell where the runtime errors happen
+  source: Option<String>,
+  /// The span of the node being generated
+  span: Cell<(usize, usize)>,
+  /// The strings of the locations already used by the runtime errors
+  locations: RefCell<HashMap<String, PointerValue<'ctx>>>,
 }
 
 impl<'ctx> Compiler<'ctx> {
@@ -19,9 +78,62 @@ impl<'ctx> Compiler<'ctx> {
       context,
       module,
       builder,
+      blocks: RefCell::new(vec![]),
+      variables: RefCell::new(vec![]),
+      scopes: RefCell::new(vec![]),
+      types: RefCell::new(vec![]),
+      procedures: RefCell::new(HashMap::new()),
+      checked_arithmetic: false,
+      source: None,
+      span: Cell::new((0, 0)),
+      locations: RefCell::new(HashMap::new()),
     }
   }
 
+  pub fn with_checked_arithmetic(mut self, checked_arithmetic: bool) -> Self {
+    self.checked_arithmetic = checked_arithmetic;
+    self
+  }
+
+  pub fn checked_arithmetic(&self) -> bool {
+    self.checked_arithmetic
+  }
+
+  pub fn with_source(mut self, source: Option<String>) -> Self {
+    self.source = source;
+    self
+  }
+
+  /// Set the span of the node being generated, returns the previous one
+  pub fn replace_span(&self, span: (usize, usize)) -> (usize, usize) {
+    self.span.replace(span)
+  }
+
+  /// Where the node being generated is in the source, as a string the runtime errors can print
+  pub fn location(&self) -> PointerValue<'ctx> {
+    let (offset, _) = self.span.get();
+    let location = match &self.source {
+      Some(source) => {
+        let before = &source[..offset.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |newline| newline + 1) + 1;
+        format!("line {line}, column {column}")
+      }
+      None => format!("offset {offset}"),
+    };
+
+    *self
+      .locations
+      .borrow_mut()
+      .entry(location)
+      .or_insert_with_key(|location| {
+        self
+          .builder
+          .build_global_string_ptr(location, "location")
+          .as_pointer_value()
+      })
+  }
+
   pub fn module(&self) -> &Module<'ctx> {
     &self.module
   }
@@ -38,16 +150,175 @@ impl<'ctx> Compiler<'ctx> {
     self.context.append_basic_block(function, name)
   }
 
+  pub fn push_block(&self, block: Block<'ctx>) {
+    self.blocks.borrow_mut().push(block);
+  }
+
+  pub fn pop_block(&self) -> Option<Block<'ctx>> {
+    self.blocks.borrow_mut().pop()
+  }
+
+  pub fn last_block(&self) -> Option<Block<'ctx>> {
+    self.blocks.borrow().last().cloned()
+  }
+
+  /// The counter slot of the innermost range loop named `name`, procedures can't see the
+  /// counters of the loops they are defined in
+  pub fn range_counter(&self, name: &str) -> Option<PointerValue<'ctx>> {
+    self
+      .blocks
+      .borrow()
+      .iter()
+      .rev()
+      .take_while(|block| !matches!(block, Block::Proc(..)))
+      .find_map(|block| match block {
+        Block::Range {
+          name: Some(counter_name),
+          counter,
+          ..
+        } if counter_name == name => Some(*counter),
+        _ => None,
+      })
+  }
+
+  /// The function the builder is currently generating code for
+  pub fn current_function(&self) -> Option<inkwell::values::FunctionValue<'ctx>> {
+    self.builder.get_insert_block()?.get_parent()
+  }
+
+  pub fn declare_variable(&self, name: &str, slot: PointerValue<'ctx>, type_: Type) {
+    self
+      .variables
+      .borrow_mut()
+      .push((name.to_string(), slot, type_));
+  }
+
+  /// How many variables were declared so far in the scopes that are still open
+  pub fn variables_in_scope(&self) -> usize {
+    self.variables.borrow().len()
+  }
+
+  /// The slot and the type of the innermost variable named `name`
+  pub fn variable(&self, name: &str) -> Option<(PointerValue<'ctx>, Type)> {
+    self
+      .variables
+      .borrow()
+      .iter()
+      .rev()
+      .find(|(variable, ..)| variable == name)
+      .map(|(_, slot, type_)| (*slot, type_.clone()))
+  }
+
+  pub fn enter_scope(&self) {
+    let len = self.variables_in_scope();
+    self.scopes.borrow_mut().push(len);
+  }
+
+  pub fn exit_scope(&self) {
+    if let Some(len) = self.scopes.borrow_mut().pop() {
+      self.variables.borrow_mut().truncate(len);
+    }
+  }
+
+  pub fn push_type(&self, type_: StackType) {
+    self.types.borrow_mut().push(type_);
+  }
+
+  pub fn pop_type(&self) -> Option<StackType> {
+    self.types.borrow_mut().pop()
+  }
+
+  pub fn types(&self) -> Vec<StackType> {
+    self.types.borrow().clone()
+  }
+
+  /// Set the types on the stack, returns the previous ones
+  pub fn replace_types(&self, types: Vec<StackType>) -> Vec<StackType> {
+    self.types.replace(types)
+  }
+
+  pub fn define_procedure(&self, name: &str, signature: Signature) {
+    self
+      .procedures
+      .borrow_mut()
+      .insert(name.to_string(), signature);
+  }
+
+  pub fn procedure_signature(&self, name: &str) -> Option<Signature> {
+    self.procedures.borrow().get(name).cloned()
+  }
+
+  /// Allocate an i32 slot at the start of the current function, so it is only allocated once
+  /// even if the alloca is requested from inside a loop
+  pub fn build_entry_alloca(&self, name: &str) -> anyhow::Result<PointerValue<'ctx>> {
+    let entry = self
+      .current_function()
+      .and_then(|function| function.get_first_basic_block())
+      .ok_or(anyhow::anyhow!("Allocation outside of a function"))?;
+
+    let builder = self.context.create_builder();
+    match entry.get_first_instruction() {
+      Some(instruction) => builder.position_before(&instruction),
+      None => builder.position_at_end(entry),
+    }
+
+    Ok(builder.build_alloca(self.i32_type(), name))
+  }
+
   // ====================== Types ======================
 
+  /// The native stack is an array of 64 bits slots, every type fits in one
   pub fn array_type(&self, size: u32) -> inkwell::types::ArrayType<'ctx> {
-    self.context.i32_type().array_type(size)
+    self.slot_type().array_type(size)
+  }
+
+  pub fn slot_type(&self) -> inkwell::types::IntType<'ctx> {
+    self.context.i64_type()
+  }
+
+  pub fn ptr_slot_type(&self) -> inkwell::types::PointerType<'ctx> {
+    self.slot_type().ptr_type(inkwell::AddressSpace::default())
+  }
+
+  /// The type of the pointers to the strings, which end with a zero like in C
+  pub fn string_type(&self) -> inkwell::types::PointerType<'ctx> {
+    self
+      .context
+      .i8_type()
+      .ptr_type(inkwell::AddressSpace::default())
+  }
+
+  /// The LLVM type of the values of a language type, a bool is an `i1`
+  pub fn basic_type(&self, type_: &Type) -> BasicTypeEnum<'ctx> {
+    match type_ {
+      Type::I32 => self.i32_type().into(),
+      Type::I64 => self.i64_type().into(),
+      Type::F32 => self.f32_type().into(),
+      Type::F64 => self.f64_type().into(),
+      Type::Bool => self.bool_type().into(),
+    }
   }
 
   pub fn i32_type(&self) -> inkwell::types::IntType<'ctx> {
     self.context.i32_type()
   }
 
+  pub fn i64_type(&self) -> inkwell::types::IntType<'ctx> {
+    self.context.i64_type()
+  }
+
+  pub fn bool_type(&self) -> inkwell::types::IntType<'ctx> {
+    self.context.bool_type()
+  }
+
+  pub fn f32_type(&self) -> inkwell::types::FloatType<'ctx> {
+    self.context.f32_type()
+  }
+
+  pub fn f64_type(&self) -> inkwell::types::FloatType<'ctx> {
+    self.context.f64_type()
+  }
+
   pub fn ptr_i32_type(&self) -> inkwell::types::PointerType<'ctx> {
     self
       .context
@@ -55,6 +326,13 @@ impl<'ctx> Compiler<'ctx> {
       .ptr_type(inkwell::AddressSpace::default())
   }
 
+  pub fn struct_type(
+    &self,
+    field_types: &[BasicTypeEnum<'ctx>],
+  ) -> inkwell::types::StructType<'ctx> {
+    self.context.struct_type(field_types, false)
+  }
+
   pub fn void_type(&self) -> inkwell::types::VoidType<'ctx> {
     self.context.void_type()
   }
@@ -75,24 +353,3 @@ impl<'ctx> Compiler<'ctx> {
     self.context.void_type().fn_type(param_types, is_var_args)
   }
 }
-
-impl<'ctx> Drop for Compiler<'ctx> {
-  fn drop(&mut self) {
-    let module = &self.module;
-
-    // let binding = module.print_to_string();
-    // let output = binding.to_str().unwrap();
-    // println!("{}", output);
-
-    module.print_to_file(Path::new("output.ll")).unwrap();
-
-    // Invoke clang -o output output.ll
-    std::process::Command::new("clang")
-      .arg("-o")
-      .arg("output")
-      .arg("output.ll")
-      .arg("-lc")
-      .output()
-      .expect("failed to execute process");
-  }
-}
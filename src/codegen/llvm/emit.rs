@@ -0,0 +1,295 @@
+use std::{path::Path, process::Command};
+
+use inkwell::{
+  module::Module,
+  passes::PassBuilderOptions,
+  targets::{
+    CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine, TargetTriple,
+  },
+  OptimizationLevel,
+};
+
+use crate::codegen::{CodeGeneratorOptions, EmitKind};
+
+use super::compiler::Compiler;
+
+/// The file written for each kind of output
+pub fn output_path(filename: &str, emit: &EmitKind) -> String {
+  match emit {
+    EmitKind::LlvmIr => format!("{filename}.ll"),
+    EmitKind::Bitcode => format!("{filename}.bc"),
+    EmitKind::Asm => format!("{filename}.s"),
+    EmitKind::Obj => format!("{filename}.o"),
+    EmitKind::Exe => filename.to_string(),
+  }
+}
+
+/// Optimize the module for the target and write it in the requested form. Only the executables
+/// need an external program, the system linker (`$CC`, or `cc`).
+pub fn emit(
+  compiler: &Compiler<'_>,
+  options: &CodeGeneratorOptions,
+  filename: &str,
+) -> anyhow::Result<()> {
+  check_linkable(options)?;
+
+  let module = compiler.module();
+  let machine = prepare(module, options)?;
+
+  let output = output_path(filename, &options.emit);
+
+  match options.emit {
+    EmitKind::LlvmIr => module
+      .print_to_file(Path::new(&output))
+      .map_err(|e| anyhow::anyhow!("Error writing {}: {}", output, e.to_string_lossy())),
+    EmitKind::Bitcode => match module.write_bitcode_to_path(Path::new(&output)) {
+      true => Ok(()),
+      false => Err(anyhow::anyhow!("Error writing {}", output)),
+    },
+    EmitKind::Asm => write(&machine, module, FileType::Assembly, &output),
+    EmitKind::Obj => write(&machine, module, FileType::Object, &output),
+    EmitKind::Exe => {
+      let object = output_path(filename, &EmitKind::Obj);
+      write(&machine, module, FileType::Object, &object)?;
+
+      let result = link(&object, &output);
+      std::fs::remove_file(&object).ok();
+
+      result
+    }
+  }
+}
+
+/// The system linker only links for the host, the executables of another `--target` have to be
+/// linked from an object file with the linker of that target
+fn check_linkable(options: &CodeGeneratorOptions) -> anyhow::Result<()> {
+  let (EmitKind::Exe, Some(triple)) = (&options.emit, &options.target_triple) else {
+    return Ok(());
+  };
+
+  let host = TargetMachine::normalize_triple(&TargetMachine::get_default_triple());
+  if TargetMachine::normalize_triple(&TargetTriple::create(triple)) != host {
+    return Err(anyhow::anyhow!(
+      "Can't link an executable for {} on {}, emit an object file with --emit obj and link it \
+       with a linker for {}",
+      triple,
+      host.as_str().to_string_lossy(),
+      triple
+    ));
+  }
+
+  Ok(())
+}
+
+/// Check the module and optimize it for the machine of the options, which is returned
+pub fn prepare(
+  module: &Module<'_>,
+  options: &CodeGeneratorOptions,
+) -> anyhow::Result<TargetMachine> {
+  let machine = target_machine(options)?;
+
+  module.set_triple(&machine.get_triple());
+  module.set_data_layout(&machine.get_target_data().get_data_layout());
+
+  module
+    .verify()
+    .map_err(|e| anyhow::anyhow!("Invalid LLVM module: {}", e.to_string_lossy()))?;
+
+  optimize(module, &machine, options.optimization_level)?;
+
+  Ok(machine)
+}
+
+/// The machine of `--target`, or of the host with its cpu features
+fn target_machine(options: &CodeGeneratorOptions) -> anyhow::Result<TargetMachine> {
+  let (triple, cpu, features) = match &options.target_triple {
+    Some(triple) => {
+      Target::initialize_all(&InitializationConfig::default());
+      (TargetTriple::create(triple), String::new(), String::new())
+    }
+    None => {
+      Target::initialize_native(&InitializationConfig::default())
+        .map_err(|e| anyhow::anyhow!("Could not initialize the native target: {}", e))?;
+      (
+        TargetMachine::get_default_triple(),
+        TargetMachine::get_host_cpu_name()
+          .to_string_lossy()
+          .into_owned(),
+        TargetMachine::get_host_cpu_features()
+          .to_string_lossy()
+          .into_owned(),
+      )
+    }
+  };
+
+  let target = Target::from_triple(&triple).map_err(|e| {
+    anyhow::anyhow!(
+      "Unknown target {}: {}",
+      triple.as_str().to_string_lossy(),
+      e.to_string_lossy()
+    )
+  })?;
+
+  // Position independent, the linkers make executables PIE by default
+  target
+    .create_target_machine(
+      &triple,
+      &cpu,
+      &features,
+      optimization_level(options.optimization_level),
+      RelocMode::PIC,
+      CodeModel::Default,
+    )
+    .ok_or_else(|| {
+      anyhow::anyhow!(
+        "Could not create a target machine for {}",
+        triple.as_str().to_string_lossy()
+      )
+    })
+}
+
+pub fn optimization_level(level: u8) -> OptimizationLevel {
+  match level {
+    0 => OptimizationLevel::None,
+    1 => OptimizationLevel::Less,
+    2 => OptimizationLevel::Default,
+    _ => OptimizationLevel::Aggressive,
+  }
+}
+
+/// Run the same pipeline as `opt -O<level>`
+fn optimize(module: &Module<'_>, machine: &TargetMachine, level: u8) -> anyhow::Result<()> {
+  module
+    .run_passes(
+      &format!("default<O{}>", level.min(3)),
+      machine,
+      PassBuilderOptions::create(),
+    )
+    .map_err(|e| anyhow::anyhow!("Error optimizing the module: {}", e.to_string_lossy()))
+}
+
+fn write(
+  machine: &TargetMachine,
+  module: &Module<'_>,
+  file_type: FileType,
+  output: &str,
+) -> anyhow::Result<()> {
+  machine
+    .write_to_file(module, file_type, Path::new(output))
+    .map_err(|e| anyhow::anyhow!("Error writing {}: {}", output, e.to_string_lossy()))
+}
+
+fn link(object: &str, output: &str) -> anyhow::Result<()> {
+  let linker = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+
+  let result = Command::new(&linker)
+    .arg(object)
+    .arg("-o")
+    .arg(output)
+    .arg("-lc")
+    .output()
+    .map_err(|e| anyhow::anyhow!("Could not run the linker {}: {}", linker, e))?;
+
+  if !result.status.success() {
+    return Err(anyhow::anyhow!(
+      "{} failed ({}):\n{}",
+      linker,
+      result.status,
+      String::from_utf8_lossy(&result.stderr)
+    ));
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod emit_tests {
+  use super::*;
+  use crate::{
+    codegen::{CodeGenerator, llvm::LLVMCodeGenerator},
+    frontend::Program,
+  };
+
+  const SOURCE: &str = "1 2 + dump\n";
+
+  /// Compile `SOURCE` in a file named after the test, the name given to the code generator
+  fn generate(name: &str, emit: EmitKind, target_triple: Option<&str>) -> anyhow::Result<String> {
+    let program = Program::from_source(SOURCE.to_string(), None).unwrap();
+    let filename = std::env::temp_dir().join(format!("pile-{}-{}", name, std::process::id()));
+    let filename = filename.to_string_lossy().to_string();
+
+    LLVMCodeGenerator::new(CodeGeneratorOptions {
+      source_code: Some(SOURCE.to_string()),
+      emit,
+      target_triple: target_triple.map(str::to_string),
+      ..Default::default()
+    })
+    .generate(program.ast, filename.clone())?;
+
+    Ok(filename)
+  }
+
+  #[test]
+  fn test_output_path() {
+    assert_eq!(output_path("out", &EmitKind::LlvmIr), "out.ll");
+    assert_eq!(output_path("out", &EmitKind::Bitcode), "out.bc");
+    assert_eq!(output_path("out", &EmitKind::Asm), "out.s");
+    assert_eq!(output_path("out", &EmitKind::Obj), "out.o");
+    assert_eq!(output_path("out", &EmitKind::Exe), "out");
+  }
+
+  #[test]
+  fn test_emit_kinds() {
+    let filename = generate("emit-ir", EmitKind::LlvmIr, None).unwrap();
+    let ir = std::fs::read_to_string(output_path(&filename, &EmitKind::LlvmIr)).unwrap();
+    std::fs::remove_file(output_path(&filename, &EmitKind::LlvmIr)).unwrap();
+    assert!(ir.contains("@main()"));
+
+    for emit in [EmitKind::Bitcode, EmitKind::Asm, EmitKind::Obj] {
+      let filename = generate("emit", emit.clone(), None).unwrap();
+      std::fs::remove_file(output_path(&filename, &emit)).unwrap();
+    }
+
+    // Only the executable is left
+    let filename = generate("emit-exe", EmitKind::Exe, None).unwrap();
+    assert!(!Path::new(&output_path(&filename, &EmitKind::Obj)).exists());
+    let output = Command::new(&filename).output().unwrap();
+    std::fs::remove_file(&filename).unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+  }
+
+  #[test]
+  fn test_target_triple() {
+    let triple = "aarch64-unknown-linux-gnu";
+
+    let filename = generate("target-ir", EmitKind::LlvmIr, Some(triple)).unwrap();
+    let ir = std::fs::read_to_string(output_path(&filename, &EmitKind::LlvmIr)).unwrap();
+    std::fs::remove_file(output_path(&filename, &EmitKind::LlvmIr)).unwrap();
+    assert!(ir.contains(&format!("target triple = \"{triple}\"")));
+
+    // An ELF object for the machine 183, AArch64
+    let filename = generate("target-obj", EmitKind::Obj, Some(triple)).unwrap();
+    let object = std::fs::read(output_path(&filename, &EmitKind::Obj)).unwrap();
+    std::fs::remove_file(output_path(&filename, &EmitKind::Obj)).unwrap();
+    assert_eq!(&object[..4], b"\x7fELF");
+    assert_eq!(u16::from_le_bytes([object[18], object[19]]), 183);
+  }
+
+  #[test]
+  fn test_foreign_executables_are_rejected() {
+    // No host is a wasm one
+    let error = generate("foreign-exe", EmitKind::Exe, Some("wasm32-unknown-unknown")).unwrap_err();
+    assert!(error.to_string().contains("--emit obj"));
+  }
+
+  #[test]
+  fn test_host_target_links() {
+    let host = TargetMachine::get_default_triple();
+    let host = host.as_str().to_string_lossy();
+
+    let filename = generate("host-exe", EmitKind::Exe, Some(&host)).unwrap();
+    let output = Command::new(&filename).output().unwrap();
+    std::fs::remove_file(&filename).unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+  }
+}
@@ -1,24 +1,31 @@
+use inkwell::values::BasicValueEnum;
+
 use crate::{
-  codegen::llvm::{
-    builtins::{pop::PopBuiltin, push::PushBuiltin},
-    compiler::Compiler,
-    generate_code::GenerateLLVMIR,
-  },
+  codegen::llvm::{builtins::push::PushBuiltin, compiler::Compiler},
   parser::parse::AstNode,
+  semantic::stack_frame::StackType,
 };
 
-pub fn generate(compiler: &Compiler<'_>, ast: &AstNode) -> anyhow::Result<()> {
-  GenerateLLVMIR::generate(compiler, &ast.children[0])?;
-  GenerateLLVMIR::generate(compiler, &ast.children[1])?;
+use super::{build_signed_division, generate_operands};
 
-  let right = PopBuiltin::call(compiler);
-  let left = PopBuiltin::call(compiler);
+pub fn generate(compiler: &Compiler<'_>, ast: &AstNode) -> anyhow::Result<()> {
+  let (left, right, type_) = generate_operands(compiler, ast)?;
+  let builder = compiler.builder();
 
-  let result = compiler
-    .builder()
-    .build_int_unsigned_div(left, right, "divtmp");
+  let result: BasicValueEnum = match (left, right) {
+    (BasicValueEnum::FloatValue(left), BasicValueEnum::FloatValue(right)) => {
+      builder.build_float_div(left, right, "divtmp").into()
+    }
+    _ => build_signed_division(
+      compiler,
+      left.into_int_value(),
+      right.into_int_value(),
+      false,
+    )?
+    .into(),
+  };
 
-  PushBuiltin::call(compiler, &[result.into()]);
+  PushBuiltin::push_value(compiler, result, StackType::from(&type_))?;
 
   Ok(())
 }
@@ -1,22 +1,35 @@
+use inkwell::values::BasicValueEnum;
+
 use crate::{
-  codegen::llvm::{
-    builtins::{pop::PopBuiltin, push::PushBuiltin},
-    compiler::Compiler,
-    generate_code::GenerateLLVMIR,
-  },
+  codegen::llvm::{builtins::push::PushBuiltin, compiler::Compiler},
   parser::parse::AstNode,
+  semantic::stack_frame::StackType,
 };
 
-pub fn generate(compiler: &Compiler<'_>, ast: &AstNode) -> anyhow::Result<()> {
-  GenerateLLVMIR::generate(compiler, &ast.children[0])?;
-  GenerateLLVMIR::generate(compiler, &ast.children[1])?;
+use super::{build_checked, generate_operands};
 
-  let right = PopBuiltin::call(compiler);
-  let left = PopBuiltin::call(compiler);
+pub fn generate(compiler: &Compiler<'_>, ast: &AstNode) -> anyhow::Result<()> {
+  let (left, right, type_) = generate_operands(compiler, ast)?;
+  let builder = compiler.builder();
 
-  let result = compiler.builder().build_int_sub(left, right, "subtmp");
+  let result: BasicValueEnum = match (left, right) {
+    (BasicValueEnum::FloatValue(left), BasicValueEnum::FloatValue(right)) => {
+      builder.build_float_sub(left, right, "subtmp").into()
+    }
+    _ if compiler.checked_arithmetic() => build_checked(
+      compiler,
+      "ssub",
+      "-",
+      left.into_int_value(),
+      right.into_int_value(),
+    )?
+    .into(),
+    _ => builder
+      .build_int_sub(left.into_int_value(), right.into_int_value(), "subtmp")
+      .into(),
+  };
 
-  PushBuiltin::call(compiler, &[result.into()]);
+  PushBuiltin::push_value(compiler, result, StackType::from(&type_))?;
 
   Ok(())
 }
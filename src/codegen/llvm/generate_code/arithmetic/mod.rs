@@ -1,5 +1,183 @@
+use inkwell::{
+  values::{BasicValueEnum, IntValue},
+  IntPredicate,
+};
+
+use crate::{
+  codegen::llvm::{
+    builtins::{abort::AbortBuiltin, pop::PopBuiltin},
+    compiler::Compiler,
+    generate_code::{cast::build_cast, GenerateLLVMIR},
+  },
+  lexer::tokens::Type,
+  parser::parse::AstNode,
+};
+
 pub mod plus;
 pub mod times;
 pub mod minus;
 pub mod divide;
 pub mod modulo;
+
+/// Generate both operands and pop them, converted to the type of the result like the semantic
+/// analysis promotes them
+pub fn generate_operands<'ctx>(
+  compiler: &Compiler<'ctx>,
+  ast: &AstNode,
+) -> anyhow::Result<(BasicValueEnum<'ctx>, BasicValueEnum<'ctx>, Type)> {
+  GenerateLLVMIR::generate(compiler, &ast.children[0])?;
+  GenerateLLVMIR::generate(compiler, &ast.children[1])?;
+
+  let (right, right_type) = PopBuiltin::pop_value(compiler)?;
+  let (left, left_type) = PopBuiltin::pop_value(compiler)?;
+
+  let type_ = left_type
+    .promote(&right_type)
+    .and_then(|type_| type_.as_type())
+    .ok_or(anyhow::anyhow!(
+      "Currently unsupported operands for the llvm target: {} and {}",
+      left_type,
+      right_type
+    ))?;
+
+  Ok((
+    build_cast(compiler, left, &type_)?,
+    build_cast(compiler, right, &type_)?,
+    type_,
+  ))
+}
+
+/// `+`, `-` or `*` through the `llvm.<operation>.with.overflow` intrinsics, an overflow aborts the
+/// program (`--checked-arith`)
+pub fn build_checked<'ctx>(
+  compiler: &Compiler<'ctx>,
+  operation: &str,
+  operator: &str,
+  left: IntValue<'ctx>,
+  right: IntValue<'ctx>,
+) -> anyhow::Result<IntValue<'ctx>> {
+  let builder = compiler.builder();
+  let module = compiler.module();
+
+  let int_type = left.get_type();
+  let name = format!(
+    "llvm.{}.with.overflow.i{}",
+    operation,
+    int_type.get_bit_width()
+  );
+  let intrinsic = module.get_function(&name).unwrap_or_else(|| {
+    let result_type = compiler.struct_type(&[int_type.into(), compiler.bool_type().into()]);
+    module.add_function(
+      &name,
+      result_type.fn_type(&[int_type.into(), int_type.into()], false),
+      None,
+    )
+  });
+
+  let result = builder
+    .build_call(intrinsic, &[left.into(), right.into()], "checked")
+    .try_as_basic_value()
+    .left()
+    .ok_or(anyhow::anyhow!("{} returned nothing", name))?
+    .into_struct_value();
+  let value = builder
+    .build_extract_value(result, 0, "checked_value")
+    .ok_or(anyhow::anyhow!("{} returned no value", name))?;
+  let overflow = builder
+    .build_extract_value(result, 1, "checked_overflow")
+    .ok_or(anyhow::anyhow!("{} returned no overflow flag", name))?;
+
+  abort_if(
+    compiler,
+    overflow.into_int_value(),
+    &format!("[ABORT @ {operator}]: integer overflow\n"),
+  )?;
+
+  Ok(value.into_int_value())
+}
+
+/// Signed `/` or `%` of integers. Dividing by zero aborts the program, and the minimum of the type
+/// divided by -1 wraps around like in the VM (or aborts with `--checked-arith`) instead of being
+/// undefined.
+pub fn build_signed_division<'ctx>(
+  compiler: &Compiler<'ctx>,
+  left: IntValue<'ctx>,
+  right: IntValue<'ctx>,
+  is_remainder: bool,
+) -> anyhow::Result<IntValue<'ctx>> {
+  let builder = compiler.builder();
+  let int_type = left.get_type();
+  let operator = if is_remainder { "%" } else { "/" };
+
+  let is_zero =
+    builder.build_int_compare(IntPredicate::EQ, right, int_type.const_zero(), "is_zero");
+  abort_if(
+    compiler,
+    is_zero,
+    &format!("[ABORT @ {operator}]: division by zero\n"),
+  )?;
+
+  let minus_one = int_type.const_all_ones();
+  let is_minus_one = builder.build_int_compare(IntPredicate::EQ, right, minus_one, "is_minus_one");
+
+  if compiler.checked_arithmetic() && !is_remainder {
+    let min = int_type.const_int(1 << (int_type.get_bit_width() - 1), false);
+    let is_min = builder.build_int_compare(IntPredicate::EQ, left, min, "is_min");
+    let overflows = builder.build_and(is_min, is_minus_one, "overflows");
+
+    abort_if(
+      compiler,
+      overflows,
+      &format!("[ABORT @ {operator}]: integer overflow\n"),
+    )?;
+  }
+
+  // Dividing by -1 is a negation, and the remainder is always 0
+  let divisor = builder
+    .build_select(is_minus_one, int_type.const_int(1, false), right, "divisor")
+    .into_int_value();
+
+  if is_remainder {
+    return Ok(builder.build_int_signed_rem(left, divisor, "modtmp"));
+  }
+
+  let quotient = builder.build_int_signed_div(left, divisor, "divtmp");
+  let negated = builder.build_int_neg(left, "negtmp");
+
+  Ok(
+    builder
+      .build_select(is_minus_one, negated, quotient, "divtmp")
+      .into_int_value(),
+  )
+}
+
+/// Abort the program with `message` when `condition` holds
+fn abort_if<'ctx>(
+  compiler: &Compiler<'ctx>,
+  condition: IntValue<'ctx>,
+  message: &str,
+) -> anyhow::Result<()> {
+  let function = compiler
+    .current_function()
+    .ok_or(anyhow::anyhow!("Arithmetic outside of a function"))?;
+  let builder = compiler.builder();
+
+  let abort = compiler.append_basic_block(function, "arith_abort");
+  let next = compiler.append_basic_block(function, "arith_ok");
+
+  builder.build_conditional_branch(condition, abort, next);
+
+  builder.position_at_end(abort);
+  AbortBuiltin::call_from_values(
+    compiler,
+    message,
+    1,
+    Some("arith_error_message".to_string()),
+    Some("arith_abort_call".to_string()),
+  );
+  builder.build_unreachable();
+
+  builder.position_at_end(next);
+
+  Ok(())
+}
@@ -0,0 +1,194 @@
+use inkwell::{
+  types::IntType,
+  values::{BasicValueEnum, FloatValue, IntValue},
+  FloatPredicate, IntPredicate,
+};
+
+use crate::{
+  codegen::llvm::{
+    builtins::{pop::PopBuiltin, push::PushBuiltin},
+    compiler::Compiler,
+  },
+  lexer::tokens::Type,
+  parser::parse::AstNode,
+  semantic::stack_frame::StackType,
+};
+
+pub fn generate(compiler: &Compiler<'_>, ast: &AstNode) -> anyhow::Result<()> {
+  let type_ = ast
+    .cast_type()
+    .ok_or(anyhow::anyhow!("Cast without a type"))?;
+
+  let (value, _) = PopBuiltin::pop_value(compiler)?;
+  let casted = build_cast(compiler, value, type_)?;
+
+  PushBuiltin::push_value(compiler, casted, StackType::from(type_))?;
+
+  Ok(())
+}
+
+/// Convert a value to the LLVM type of `type_`. Integers are signed, except the `i1` of a bool
+/// that is zero extended. Converting to a bool compares against zero. Floats saturate when
+/// converted to integers and NaN becomes 0, like Rust's `as` in the VM.
+pub fn build_cast<'ctx>(
+  compiler: &Compiler<'ctx>,
+  value: BasicValueEnum<'ctx>,
+  type_: &Type,
+) -> anyhow::Result<BasicValueEnum<'ctx>> {
+  Ok(match value {
+    BasicValueEnum::IntValue(value) => cast_int(compiler, value, type_),
+    BasicValueEnum::FloatValue(value) => cast_float(compiler, value, type_)?,
+    // Only numbers and bools reach a cast, the semantic analysis rejects anything else
+    other => other,
+  })
+}
+
+fn cast_int<'ctx>(
+  compiler: &Compiler<'ctx>,
+  value: IntValue<'ctx>,
+  type_: &Type,
+) -> BasicValueEnum<'ctx> {
+  let builder = compiler.builder();
+  let is_bool = value.get_type().get_bit_width() == 1;
+
+  match type_ {
+    Type::I32 | Type::I64 => {
+      let target = match type_ {
+        Type::I32 => compiler.i32_type(),
+        _ => compiler.i64_type(),
+      };
+      let (from, to) = (value.get_type().get_bit_width(), target.get_bit_width());
+
+      if from == to {
+        value.into()
+      } else if from > to {
+        builder.build_int_truncate(value, target, "cast_trunc").into()
+      } else if is_bool {
+        builder.build_int_z_extend(value, target, "cast_zext").into()
+      } else {
+        builder.build_int_s_extend(value, target, "cast_sext").into()
+      }
+    }
+    Type::F32 | Type::F64 => {
+      let target = match type_ {
+        Type::F32 => compiler.f32_type(),
+        _ => compiler.f64_type(),
+      };
+
+      if is_bool {
+        builder
+          .build_unsigned_int_to_float(value, target, "cast_uitofp")
+          .into()
+      } else {
+        builder
+          .build_signed_int_to_float(value, target, "cast_sitofp")
+          .into()
+      }
+    }
+    Type::Bool if is_bool => value.into(),
+    Type::Bool => builder
+      .build_int_compare(
+        IntPredicate::NE,
+        value,
+        value.get_type().const_zero(),
+        "cast_to_bool",
+      )
+      .into(),
+  }
+}
+
+fn cast_float<'ctx>(
+  compiler: &Compiler<'ctx>,
+  value: FloatValue<'ctx>,
+  type_: &Type,
+) -> anyhow::Result<BasicValueEnum<'ctx>> {
+  let builder = compiler.builder();
+  let is_double = value.get_type() == compiler.f64_type();
+
+  Ok(match type_ {
+    Type::I32 => build_saturating_cast(compiler, value, compiler.i32_type())?.into(),
+    Type::I64 => build_saturating_cast(compiler, value, compiler.i64_type())?.into(),
+    Type::F32 if is_double => builder
+      .build_float_trunc(value, compiler.f32_type(), "cast_fptrunc")
+      .into(),
+    Type::F64 if !is_double => builder
+      .build_float_ext(value, compiler.f64_type(), "cast_fpext")
+      .into(),
+    Type::F32 | Type::F64 => value.into(),
+    // NaN is not zero, so it is true like in the VM
+    Type::Bool => builder
+      .build_float_compare(
+        FloatPredicate::UNE,
+        value,
+        value.get_type().const_zero(),
+        "cast_to_bool",
+      )
+      .into(),
+  })
+}
+
+/// `fptosi` through the `llvm.fptosi.sat.<int>.<float>` intrinsics, a plain `fptosi` is poison
+/// for NaN and the floats out of the range of the integer
+fn build_saturating_cast<'ctx>(
+  compiler: &Compiler<'ctx>,
+  value: FloatValue<'ctx>,
+  target: IntType<'ctx>,
+) -> anyhow::Result<IntValue<'ctx>> {
+  let module = compiler.module();
+
+  let float_type = value.get_type();
+  let float_name = match float_type == compiler.f64_type() {
+    true => "f64",
+    false => "f32",
+  };
+  let name = format!("llvm.fptosi.sat.i{}.{}", target.get_bit_width(), float_name);
+  let intrinsic = module.get_function(&name).unwrap_or_else(|| {
+    module.add_function(&name, target.fn_type(&[float_type.into()], false), None)
+  });
+
+  Ok(
+    compiler
+      .builder()
+      .build_call(intrinsic, &[value.into()], "cast_fptosi")
+      .try_as_basic_value()
+      .left()
+      .ok_or(anyhow::anyhow!("{} returned nothing", name))?
+      .into_int_value(),
+  )
+}
+
+#[cfg(test)]
+mod cast_tests {
+  use inkwell::context::Context;
+
+  use super::*;
+
+  #[test]
+  fn test_float_casts_match_the_vm() {
+    let context = Context::create();
+    let compiler = Compiler::new(&context, "main");
+
+    // The floats are parameters, so the builder can't fold the casts away
+    let function_type = compiler.i32_type().fn_type(
+      &[compiler.f32_type().into(), compiler.f64_type().into()],
+      false,
+    );
+    let function = compiler.module().add_function("casts", function_type, None);
+    let entry = compiler.append_basic_block(function, "entry");
+    compiler.builder().position_at_end(entry);
+
+    let float = function.get_nth_param(0).unwrap();
+    let double = function.get_nth_param(1).unwrap();
+    build_cast(&compiler, float, &Type::I64).unwrap();
+    build_cast(&compiler, float, &Type::Bool).unwrap();
+    let result = build_cast(&compiler, double, &Type::I32).unwrap();
+    compiler.builder().build_return(Some(&result));
+    compiler.module().verify().unwrap();
+
+    let ir = compiler.module().print_to_string().to_string();
+    assert!(ir.contains("@llvm.fptosi.sat.i64.f32("));
+    assert!(ir.contains("@llvm.fptosi.sat.i32.f64("));
+    assert!(ir.contains("fcmp une float"));
+    assert!(!ir.contains("fptosi "));
+  }
+}
@@ -0,0 +1,95 @@
+use inkwell::{values::BasicValueEnum, FloatPredicate, IntPredicate};
+
+use crate::{
+  codegen::llvm::{
+    builtins::{pop::PopBuiltin, push::PushBuiltin},
+    compiler::Compiler,
+    generate_code::{cast::build_cast, GenerateLLVMIR},
+  },
+  lexer::tokens::ComparisonOperators,
+  parser::parse::AstNode,
+  semantic::stack_frame::StackType,
+};
+
+/// Like in the VM numbers of different types are promoted before being compared, the integers
+/// are compared as signed numbers and `false` is less than `true`. The result is a bool.
+pub fn generate(
+  compiler: &Compiler<'_>,
+  ast: &AstNode,
+  operator: ComparisonOperators,
+) -> anyhow::Result<()> {
+  GenerateLLVMIR::generate(compiler, &ast.children[0])?;
+  GenerateLLVMIR::generate(compiler, &ast.children[1])?;
+
+  let (right, right_type) = PopBuiltin::pop_value(compiler)?;
+  let (left, left_type) = PopBuiltin::pop_value(compiler)?;
+
+  let type_ = match left_type.promote(&right_type) {
+    Some(promoted) => promoted,
+    None if left_type == right_type => left_type.clone(),
+    None => {
+      return Err(anyhow::anyhow!(
+        "Can't compare {} with {}",
+        left_type,
+        right_type
+      ))
+    }
+  };
+
+  let (left, right) = match type_.as_type() {
+    Some(target) => (
+      build_cast(compiler, left, &target)?,
+      build_cast(compiler, right, &target)?,
+    ),
+    None => {
+      return Err(anyhow::anyhow!(
+        "Currently unsupported comparison for the llvm target: {} with {}",
+        left_type,
+        right_type
+      ))
+    }
+  };
+
+  let builder = compiler.builder();
+
+  let holds = match (left, right) {
+    (BasicValueEnum::FloatValue(left), BasicValueEnum::FloatValue(right)) => {
+      let predicate = match operator {
+        ComparisonOperators::EqualTo => FloatPredicate::OEQ,
+        ComparisonOperators::NotEqualTo => FloatPredicate::UNE,
+        ComparisonOperators::LessThan => FloatPredicate::OLT,
+        ComparisonOperators::LessThanOrEqualTo => FloatPredicate::OLE,
+        ComparisonOperators::GreaterThan => FloatPredicate::OGT,
+        ComparisonOperators::GreaterThanOrEqualTo => FloatPredicate::OGE,
+      };
+
+      builder.build_float_compare(predicate, left, right, "cmptmp")
+    }
+    _ => {
+      let is_bool = type_ == StackType::Bool;
+      let predicate = match operator {
+        ComparisonOperators::EqualTo => IntPredicate::EQ,
+        ComparisonOperators::NotEqualTo => IntPredicate::NE,
+        ComparisonOperators::LessThan if is_bool => IntPredicate::ULT,
+        ComparisonOperators::LessThan => IntPredicate::SLT,
+        ComparisonOperators::LessThanOrEqualTo if is_bool => IntPredicate::ULE,
+        ComparisonOperators::LessThanOrEqualTo => IntPredicate::SLE,
+        ComparisonOperators::GreaterThan if is_bool => IntPredicate::UGT,
+        ComparisonOperators::GreaterThan => IntPredicate::SGT,
+        ComparisonOperators::GreaterThanOrEqualTo if is_bool => IntPredicate::UGE,
+        ComparisonOperators::GreaterThanOrEqualTo => IntPredicate::SGE,
+      };
+
+      builder.build_int_compare(
+        predicate,
+        left.into_int_value(),
+        right.into_int_value(),
+        "cmptmp",
+      )
+    }
+  };
+
+  PushBuiltin::push_value(compiler, holds.into(), StackType::Bool)?;
+
+  Ok(())
+}
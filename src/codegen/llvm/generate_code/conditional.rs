@@ -0,0 +1,88 @@
+use inkwell::{basic_block::BasicBlock, values::IntValue};
+
+use crate::{
+  codegen::llvm::{
+    builtins::pop::PopBuiltin,
+    compiler::{Block, Compiler},
+    generate_code::{cast::build_cast, GenerateLLVMIR},
+  },
+  lexer::tokens::Type,
+  parser::parse::AstNode,
+  semantic::stack_frame::StackType,
+};
+
+/// `<condition> if <then> else <otherwise> end` becomes
+/// ```text
+/// <condition>, pop, br top if_then if_else
+/// if_then: <then>, br if_exit
+/// if_else: <otherwise>, br if_exit
+/// if_exit:
+/// ```
+/// Without `else` the `if_else` block only goes to `if_exit`.
+pub fn generate_if(compiler: &Compiler<'_>, ast: &AstNode) -> anyhow::Result<()> {
+  let function = compiler
+    .current_function()
+    .ok_or(anyhow::anyhow!("Condition outside of a function"))?;
+
+  GenerateLLVMIR::generate(compiler, &ast.children[0])?;
+
+  let then = compiler.append_basic_block(function, "if_then");
+  let otherwise = compiler.append_basic_block(function, "if_else");
+  let exit = compiler.append_basic_block(function, "if_exit");
+
+  let builder = compiler.builder();
+
+  let is_true = pop_condition(compiler)?;
+
+  builder.build_conditional_branch(is_true, then, otherwise);
+  builder.position_at_end(then);
+
+  compiler.push_block(Block::If {
+    otherwise,
+    exit,
+    types: compiler.types(),
+  });
+
+  Ok(())
+}
+
+/// The `else` branch starts with the stack the `if` branch started with
+pub fn generate_else<'ctx>(
+  compiler: &Compiler<'ctx>,
+  otherwise: BasicBlock<'ctx>,
+  exit: BasicBlock<'ctx>,
+  types: Vec<StackType>,
+) {
+  compiler.builder().build_unconditional_branch(exit);
+  compiler.builder().position_at_end(otherwise);
+  compiler.replace_types(types);
+
+  compiler.push_block(Block::Else(exit));
+}
+
+/// The end of an `if` without `else`, the empty `if_else` block is kept so both cases end the
+/// same way
+pub fn generate_if_end<'ctx>(
+  compiler: &Compiler<'ctx>,
+  otherwise: BasicBlock<'ctx>,
+  exit: BasicBlock<'ctx>,
+) {
+  let builder = compiler.builder();
+
+  builder.build_unconditional_branch(exit);
+  builder.position_at_end(otherwise);
+  builder.build_unconditional_branch(exit);
+  builder.position_at_end(exit);
+}
+
+/// Pop the condition of an `if` or of a `while` as an `i1`
+pub fn pop_condition<'ctx>(compiler: &Compiler<'ctx>) -> anyhow::Result<IntValue<'ctx>> {
+  let (condition, _) = PopBuiltin::pop_value(compiler)?;
+
+  Ok(build_cast(compiler, condition, &Type::Bool)?.into_int_value())
+}
+
+pub fn generate_else_end<'ctx>(compiler: &Compiler<'ctx>, exit: BasicBlock<'ctx>) {
+  compiler.builder().build_unconditional_branch(exit);
+  compiler.builder().position_at_end(exit);
+}
@@ -0,0 +1,131 @@
+use inkwell::{basic_block::BasicBlock, values::PointerValue, IntPredicate};
+
+use crate::{
+  codegen::llvm::{
+    builtins::pop::PopBuiltin,
+    compiler::{Block, Compiler},
+    generate_code::conditional::pop_condition,
+  },
+  parser::parse::AstNode,
+};
+
+/// `while <condition> do <body> end` becomes
+/// ```text
+/// br while_condition
+/// while_condition: <condition>, pop, br (top != 0) while_body while_exit
+/// while_body: <body>, br while_condition
+/// while_exit:
+/// ```
+pub fn generate_while(compiler: &Compiler<'_>) -> anyhow::Result<()> {
+  let function = compiler
+    .current_function()
+    .ok_or(anyhow::anyhow!("Loop outside of a function"))?;
+
+  let condition = compiler.append_basic_block(function, "while_condition");
+  let body = compiler.append_basic_block(function, "while_body");
+  let exit = compiler.append_basic_block(function, "while_exit");
+
+  compiler.builder().build_unconditional_branch(condition);
+  compiler.builder().position_at_end(condition);
+
+  compiler.push_block(Block::While {
+    condition,
+    body,
+    exit,
+  });
+
+  Ok(())
+}
+
+pub fn generate_while_do<'ctx>(
+  compiler: &Compiler<'ctx>,
+  body: BasicBlock<'ctx>,
+  exit: BasicBlock<'ctx>,
+) -> anyhow::Result<()> {
+  let builder = compiler.builder();
+
+  let is_true = pop_condition(compiler)?;
+
+  builder.build_conditional_branch(is_true, body, exit);
+  builder.position_at_end(body);
+
+  Ok(())
+}
+
+pub fn generate_while_end<'ctx>(
+  compiler: &Compiler<'ctx>,
+  condition: BasicBlock<'ctx>,
+  exit: BasicBlock<'ctx>,
+) {
+  compiler.builder().build_unconditional_branch(condition);
+  compiler.builder().position_at_end(exit);
+}
+
+/// `<bound> range [from] [name] do <body> end` becomes
+/// ```text
+/// counter = <start or 0>, br range_condition
+/// range_condition: br (counter < bound) range_body range_exit
+/// range_body: <body>, counter += 1, br range_condition
+/// range_exit:
+/// ```
+pub fn generate_range(compiler: &Compiler<'_>, ast: &AstNode) -> anyhow::Result<()> {
+  let function = compiler
+    .current_function()
+    .ok_or(anyhow::anyhow!("Loop outside of a function"))?;
+  let builder = compiler.builder();
+
+  let bound = PopBuiltin::pop_int(compiler)?;
+  let start = if ast.range_has_start() {
+    PopBuiltin::pop_int(compiler)?
+  } else {
+    compiler.const_i32(0)
+  };
+
+  compiler.enter_scope();
+
+  let counter = compiler.build_entry_alloca("range_counter")?;
+  builder.build_store(counter, start);
+
+  let condition = compiler.append_basic_block(function, "range_condition");
+  let body = compiler.append_basic_block(function, "range_body");
+  let exit = compiler.append_basic_block(function, "range_exit");
+
+  builder.build_unconditional_branch(condition);
+  builder.position_at_end(condition);
+
+  let current = builder
+    .build_load(compiler.i32_type(), counter, "range_current")
+    .into_int_value();
+  let in_range = builder.build_int_compare(IntPredicate::SLT, current, bound, "range_in_range");
+  builder.build_conditional_branch(in_range, body, exit);
+  builder.position_at_end(body);
+
+  compiler.push_block(Block::Range {
+    name: ast.range_counter().map(|name| name.to_string()),
+    counter,
+    condition,
+    exit,
+  });
+
+  Ok(())
+}
+
+pub fn generate_range_end<'ctx>(
+  compiler: &Compiler<'ctx>,
+  counter: PointerValue<'ctx>,
+  condition: BasicBlock<'ctx>,
+  exit: BasicBlock<'ctx>,
+) {
+  let builder = compiler.builder();
+
+  let current = builder
+    .build_load(compiler.i32_type(), counter, "range_current")
+    .into_int_value();
+  let next = builder.build_int_add(current, compiler.const_i32(1), "range_next");
+  builder.build_store(counter, next);
+
+  builder.build_unconditional_branch(condition);
+  builder.position_at_end(exit);
+
+  compiler.exit_scope();
+}
@@ -1,19 +1,38 @@
 // Iter over the AST and call the needed functions to generate LLVM IR
 
 use crate::{
-  lexer::tokens::{ArithmeticOperators, StackOperators, Token},
+  lexer::tokens::{ArithmeticOperators, StackOperators, Token, Type},
   parser::parse::AstNode,
+  semantic::stack_frame::StackType,
 };
 
-use super::{builtins::pop::PopBuiltin, compiler::Compiler};
+use super::{
+  builtins::pop::PopBuiltin,
+  compiler::{Block, Compiler},
+};
 
 pub mod arithmetic;
+pub mod cast;
+pub mod comparison;
+pub mod conditional;
+pub mod loops;
+pub mod procedure;
 pub mod stack;
+pub mod variables;
 
 pub struct GenerateLLVMIR;
 
 impl GenerateLLVMIR {
   pub fn generate(compiler: &Compiler<'_>, ast: &AstNode) -> anyhow::Result<()> {
+    // The runtime errors of this node point at it, the parent's span is restored after
+    let parent_span = compiler.replace_span(ast.span);
+    let result = Self::generate_node(compiler, ast);
+    compiler.replace_span(parent_span);
+
+    result
+  }
+
+  fn generate_node(compiler: &Compiler<'_>, ast: &AstNode) -> anyhow::Result<()> {
     match ast.token {
       Token::Program => {
         let module = compiler.module();
@@ -28,8 +47,15 @@ impl GenerateLLVMIR {
           GenerateLLVMIR::generate(compiler, child)?;
         }
 
-        let top_element = PopBuiltin::call(compiler);
-        builder.build_return(Some(&top_element));
+        // The number left on top of the stack is the exit code
+        let exit_code = match compiler.types().last() {
+          Some(StackType::String) | None => compiler.const_i32(0),
+          Some(_) => {
+            let (value, _) = PopBuiltin::pop_value(compiler)?;
+            cast::build_cast(compiler, value, &Type::I32)?.into_int_value()
+          }
+        };
+        builder.build_return(Some(&exit_code));
       }
       Token::ArithmeticOp(operator) => match operator {
         ArithmeticOperators::Plus => arithmetic::plus::generate(compiler, ast)?,
@@ -38,12 +64,69 @@ impl GenerateLLVMIR {
         ArithmeticOperators::Divide => arithmetic::divide::generate(compiler, ast)?,
         ArithmeticOperators::Modulo => arithmetic::modulo::generate(compiler, ast)?,
       },
-      Token::Integer(..) => stack::push::generate(compiler, ast)?,
+      Token::ComparisonOp(operator) => comparison::generate(compiler, ast, operator)?,
+      Token::Integer(..) | Token::Float(..) | Token::Boolean(..) | Token::String(..) => {
+        stack::push::generate(compiler, ast)?
+      }
       Token::StackOps(operator) => match operator {
         StackOperators::Dump => stack::dump::generate(compiler, ast)?,
-        _ => todo!(),
+        StackOperators::Dup => stack::dup::generate(compiler, ast)?,
+        StackOperators::Drop => stack::drop::generate(compiler, ast)?,
+      },
+      Token::If => conditional::generate_if(compiler, ast)?,
+      Token::Else => match compiler.pop_block() {
+        Some(Block::If {
+          otherwise,
+          exit,
+          types,
+        }) => conditional::generate_else(compiler, otherwise, exit, types),
+        _ => return Err(anyhow::anyhow!("Mismatched 'else'")),
       },
-      _ => todo!(),
+      Token::Proc => procedure::generate_definition(compiler, ast)?,
+      Token::While => loops::generate_while(compiler)?,
+      Token::Range => loops::generate_range(compiler, ast)?,
+      Token::Do => {
+        if let Some(Block::While { body, exit, .. }) = compiler.last_block() {
+          loops::generate_while_do(compiler, body, exit)?;
+        }
+      }
+      Token::End => match compiler.pop_block() {
+        Some(Block::If {
+          otherwise, exit, ..
+        }) => conditional::generate_if_end(compiler, otherwise, exit),
+        Some(Block::Else(exit)) => conditional::generate_else_end(compiler, exit),
+        Some(Block::Proc(previous_block, outer_types)) => {
+          procedure::generate_end(compiler, previous_block, outer_types)
+        }
+        Some(Block::While {
+          condition, exit, ..
+        }) => loops::generate_while_end(compiler, condition, exit),
+        Some(Block::Range {
+          counter,
+          condition,
+          exit,
+          ..
+        }) => loops::generate_range_end(compiler, counter, condition, exit),
+        None => return Err(anyhow::anyhow!("Mismatched 'end'")),
+      },
+      Token::CastOp => cast::generate(compiler, ast)?,
+      Token::DefType(_) => variables::generate_declaration(compiler, ast)?,
+      Token::AtSign => variables::generate_assignment(compiler, ast)?,
+      Token::Identifier(ref name) => {
+        if let Some(counter) = compiler.range_counter(name) {
+          variables::generate_read(compiler, counter, &Type::I32)?;
+        } else if let Some((slot, type_)) = compiler.variable(name) {
+          variables::generate_read(compiler, slot, &type_)?;
+        } else {
+          procedure::generate_call(compiler, name)?;
+        }
+      }
+      _ => {
+        return Err(anyhow::anyhow!(
+          "Currently unsupported token for the llvm target: {:?}",
+          ast.token
+        ));
+      }
     }
 
     Ok(())
@@ -0,0 +1,74 @@
+use inkwell::basic_block::BasicBlock;
+
+use crate::{
+  codegen::llvm::compiler::{Block, Compiler},
+  parser::parse::AstNode,
+  semantic::stack_frame::StackType,
+};
+
+/// Procedures are prefixed so they can't clash with the builtins or the C library
+pub const PROC_PREFIX: &str = "proc.";
+
+/// Every procedure becomes a `void ()` function, the arguments and the results are passed
+/// through the global stack. The body starts with only the inputs on the stack.
+pub fn generate_definition(compiler: &Compiler<'_>, ast: &AstNode) -> anyhow::Result<()> {
+  let name = ast
+    .proc_name()
+    .ok_or(anyhow::anyhow!("Procedure without a name"))?;
+
+  let (inputs, outputs) = ast.proc_signature();
+  compiler.define_procedure(name, (inputs.clone(), outputs));
+
+  let function = compiler.module().add_function(
+    &format!("{PROC_PREFIX}{name}"),
+    compiler.void_type().fn_type(&[], false),
+    None,
+  );
+
+  let previous_block = compiler
+    .builder()
+    .get_insert_block()
+    .ok_or(anyhow::anyhow!("Procedure defined outside of a function"))?;
+  let outer_types = compiler.replace_types(inputs.iter().map(StackType::from).collect());
+  compiler.push_block(Block::Proc(previous_block, outer_types));
+
+  let entry = compiler.append_basic_block(function, "entry");
+  compiler.builder().position_at_end(entry);
+  compiler.enter_scope();
+
+  Ok(())
+}
+
+pub fn generate_end<'ctx>(
+  compiler: &Compiler<'ctx>,
+  previous_block: BasicBlock<'ctx>,
+  outer_types: Vec<StackType>,
+) {
+  compiler.builder().build_return(None);
+  compiler.builder().position_at_end(previous_block);
+  compiler.replace_types(outer_types);
+  compiler.exit_scope();
+}
+
+pub fn generate_call(compiler: &Compiler<'_>, name: &str) -> anyhow::Result<()> {
+  let function = compiler
+    .module()
+    .get_function(&format!("{PROC_PREFIX}{name}"))
+    .ok_or(anyhow::anyhow!("Unknown procedure: {}", name))?;
+
+  let (inputs, outputs) = compiler
+    .procedure_signature(name)
+    .ok_or(anyhow::anyhow!("Unknown procedure: {}", name))?;
+
+  compiler.builder().build_call(function, &[], "");
+
+  // The procedure takes its inputs and leaves its outputs
+  for _ in inputs.iter() {
+    compiler.pop_type();
+  }
+  for output in outputs.iter() {
+    compiler.push_type(StackType::from(output));
+  }
+
+  Ok(())
+}
@@ -0,0 +1,10 @@
+use crate::{
+  codegen::llvm::{builtins::pop::PopBuiltin, compiler::Compiler},
+  parser::parse::AstNode,
+};
+
+pub fn generate(compiler: &Compiler<'_>, _ast: &AstNode) -> anyhow::Result<()> {
+  PopBuiltin::pop_value(compiler)?;
+
+  Ok(())
+}
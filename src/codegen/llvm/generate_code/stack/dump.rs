@@ -1,16 +1,53 @@
-use crate::codegen::llvm::{builtins::pop::PopBuiltin, externs::printf::PrintfExtern};
-
-pub fn generate(
-  compiler: &crate::codegen::llvm::compiler::Compiler<'_>,
-  _ast: &crate::parser::parse::AstNode,
-) -> anyhow::Result<()> {
-  let value = PopBuiltin::call(compiler);
-  let message_ptr = compiler
-    .builder()
-    .build_global_string_ptr("%d\n", "dump_message")
+use inkwell::values::BasicMetadataValueEnum;
+
+use crate::{
+  codegen::llvm::{builtins::pop::PopBuiltin, compiler::Compiler, externs::printf::PrintfExtern},
+  parser::parse::AstNode,
+  semantic::stack_frame::StackType,
+};
+
+/// Print the top of the stack like the VM does: the floats with `%g`, the bools as `true` or
+/// `false`
+pub fn generate(compiler: &Compiler<'_>, _ast: &AstNode) -> anyhow::Result<()> {
+  let builder = compiler.builder();
+
+  let (value, type_) = PopBuiltin::pop_value(compiler)?;
+
+  let (format, value): (&str, BasicMetadataValueEnum) = match type_ {
+    StackType::I32 => ("%d\n", value.into()),
+    StackType::I64 => ("%lld\n", value.into()),
+    // The variadic arguments of printf are never floats
+    StackType::F32 => (
+      "%g\n",
+      builder
+        .build_float_ext(value.into_float_value(), compiler.f64_type(), "dump_double")
+        .into(),
+    ),
+    StackType::F64 => ("%g\n", value.into()),
+    StackType::Bool => {
+      let true_ptr = builder.build_global_string_ptr("true", "dump_true");
+      let false_ptr = builder.build_global_string_ptr("false", "dump_false");
+
+      (
+        "%s\n",
+        builder
+          .build_select(
+            value.into_int_value(),
+            true_ptr.as_pointer_value(),
+            false_ptr.as_pointer_value(),
+            "dump_bool",
+          )
+          .into(),
+      )
+    }
+    StackType::String => ("%s\n", value.into()),
+  };
+
+  let message_ptr = builder
+    .build_global_string_ptr(format, "dump_message")
     .as_pointer_value();
 
-  PrintfExtern::call(compiler, &[message_ptr.into(), value.into()]);
+  PrintfExtern::call(compiler, &[message_ptr.into(), value]);
 
   Ok(())
 }
@@ -0,0 +1,16 @@
+use crate::{
+  codegen::llvm::{
+    builtins::{pop::PopBuiltin, push::PushBuiltin},
+    compiler::Compiler,
+  },
+  parser::parse::AstNode,
+};
+
+pub fn generate(compiler: &Compiler<'_>, _ast: &AstNode) -> anyhow::Result<()> {
+  let (value, type_) = PopBuiltin::pop_value(compiler)?;
+
+  PushBuiltin::push_value(compiler, value, type_.clone())?;
+  PushBuiltin::push_value(compiler, value, type_)?;
+
+  Ok(())
+}
@@ -1,2 +1,4 @@
+pub mod drop;
 pub mod dump;
+pub mod dup;
 pub mod push;
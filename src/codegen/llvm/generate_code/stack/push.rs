@@ -1,19 +1,36 @@
+use inkwell::values::BasicValueEnum;
+
 use crate::{
   codegen::llvm::{builtins::push::PushBuiltin, compiler::Compiler},
   lexer::tokens::Token,
   parser::parse::AstNode,
+  semantic::stack_frame::StackType,
 };
 
 pub fn generate(compiler: &Compiler<'_>, ast: &AstNode) -> anyhow::Result<()> {
-  match ast.token {
-    Token::Integer(value) => {
-      PushBuiltin::call_from_int(compiler, value);
-      Ok(())
-    }
-    _ => Err(anyhow::anyhow!("Invalid token")),
-  }
-}
+  let (value, type_): (BasicValueEnum, StackType) = match &ast.token {
+    Token::Integer(value) => (compiler.const_i32(*value).into(), StackType::I32),
+    Token::Float(value) => (
+      compiler.f32_type().const_float(*value as f64).into(),
+      StackType::F32,
+    ),
+    Token::Boolean(value) => (
+      compiler.bool_type().const_int(*value as u64, false).into(),
+      StackType::Bool,
+    ),
+    // The strings are constants, they live for the whole program
+    Token::String(value) => (
+      compiler
+        .builder()
+        .build_global_string_ptr(value, "string")
+        .as_pointer_value()
+        .into(),
+      StackType::String,
+    ),
+    _ => return Err(anyhow::anyhow!("Invalid token")),
+  };
+
+  PushBuiltin::push_value(compiler, value, type_)?;
 
-pub fn generate_from_int(compiler: &Compiler<'_>, value: i32) {
-  PushBuiltin::call_from_int(compiler, value);
+  Ok(())
 }
@@ -0,0 +1,65 @@
+use inkwell::values::PointerValue;
+
+use crate::{
+  codegen::llvm::{
+    builtins::{pop::PopBuiltin, push::PushBuiltin},
+    compiler::Compiler,
+  },
+  lexer::tokens::{Token, Type},
+  parser::parse::AstNode,
+  semantic::stack_frame::StackType,
+};
+
+/// Variables are prefixed so they can't clash with the procedures or the builtins
+pub const VARIABLE_PREFIX: &str = "var.";
+
+/// Every declaration becomes its own zero initialized global, so procedures can reach the
+/// variables declared outside of them
+pub fn generate_declaration(compiler: &Compiler<'_>, ast: &AstNode) -> anyhow::Result<()> {
+  let name = ast
+    .variable_name()
+    .ok_or(anyhow::anyhow!("Declaration without a name"))?;
+
+  let Token::DefType(type_) = &ast.token else {
+    return Err(anyhow::anyhow!("Declaration without a type"));
+  };
+  let basic_type = compiler.basic_type(type_);
+
+  // LLVM renames the global if another variable with the same name was already declared
+  let global = compiler
+    .module()
+    .add_global(basic_type, None, &format!("{VARIABLE_PREFIX}{name}"));
+  global.set_linkage(inkwell::module::Linkage::Internal);
+  global.set_initializer(&basic_type.const_zero());
+
+  compiler.declare_variable(name, global.as_pointer_value(), type_.clone());
+
+  Ok(())
+}
+
+pub fn generate_assignment(compiler: &Compiler<'_>, ast: &AstNode) -> anyhow::Result<()> {
+  let name = ast
+    .variable_name()
+    .ok_or(anyhow::anyhow!("Assignment without a name"))?;
+  let (slot, _) = compiler
+    .variable(name)
+    .ok_or(anyhow::anyhow!("Unknown variable: {}", name))?;
+
+  let (value, _) = PopBuiltin::pop_value(compiler)?;
+  compiler.builder().build_store(slot, value);
+
+  Ok(())
+}
+
+/// Push the value of a variable or of a range counter
+pub fn generate_read<'ctx>(
+  compiler: &Compiler<'ctx>,
+  slot: PointerValue<'ctx>,
+  type_: &Type,
+) -> anyhow::Result<()> {
+  let value = compiler
+    .builder()
+    .build_load(compiler.basic_type(type_), slot, "variable_value");
+
+  PushBuiltin::push_value(compiler, value, StackType::from(type_))
+}
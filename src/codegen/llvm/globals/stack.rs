@@ -12,6 +12,9 @@ pub struct Stack<'ctx> {
 pub const STACK_NAME: &str = "gStack";
 pub const STACK_TOP_PTR_NAME: &str = "gTopPtr";
 
+/// How many values the stack holds when neither the program nor the command line chose
+pub const DEFAULT_STACK_SIZE: u32 = 64 * 1024;
+
 impl<'ctx> Stack<'ctx> {
   pub fn new(size: u32, compiler: &Compiler<'ctx>) -> Self {
     let module = compiler.module();
@@ -22,7 +25,7 @@ impl<'ctx> Stack<'ctx> {
     g_array.set_linkage(inkwell::module::Linkage::Internal);
     g_array.set_initializer(&array_type.const_zero());
 
-    let g_top = module.add_global(compiler.ptr_i32_type(), None, STACK_TOP_PTR_NAME);
+    let g_top = module.add_global(compiler.ptr_slot_type(), None, STACK_TOP_PTR_NAME);
     g_top.set_linkage(inkwell::module::Linkage::Internal);
     g_top.set_initializer(&g_array.as_pointer_value());
 
@@ -33,22 +36,22 @@ impl<'ctx> Stack<'ctx> {
     }
   }
 
-  pub fn stack_top_ptr(&self) -> PointerValue {
+  pub fn stack_top_ptr(&self) -> PointerValue<'ctx> {
     self.top.as_pointer_value()
   }
 
-  pub fn stack_ptr(&self) -> PointerValue {
+  pub fn stack_ptr(&self) -> PointerValue<'ctx> {
     self.stack.as_pointer_value()
   }
 
-  pub fn is_full(&'ctx self, compiler: &Compiler<'ctx>) -> IntValue<'ctx> {
+  pub fn is_full(&self, compiler: &Compiler<'ctx>) -> IntValue<'ctx> {
     let builder = compiler.builder();
 
     let top = self.load_top_ptr(compiler);
 
     let end_of_stack_ptr = unsafe {
       builder.build_in_bounds_gep(
-        compiler.i32_type(),
+        compiler.slot_type(),
         self.stack_ptr(),
         &[compiler.const_u32(self.size)],
         "nextTopPtr",
@@ -63,7 +66,7 @@ impl<'ctx> Stack<'ctx> {
     )
   }
 
-  pub fn is_empty(&'ctx self, compiler: &Compiler<'ctx>) -> IntValue<'ctx> {
+  pub fn is_empty(&self, compiler: &Compiler<'ctx>) -> IntValue<'ctx> {
     let builder = compiler.builder();
 
     let top = self.load_top_ptr(compiler);
@@ -78,7 +81,7 @@ impl<'ctx> Stack<'ctx> {
   }
 
   // Store in the stack
-  pub fn store(&'ctx self, compiler: &Compiler<'ctx>, value: IntValue) {
+  pub fn store(&self, compiler: &Compiler<'ctx>, value: IntValue<'ctx>) {
     let builder = compiler.builder();
 
     // Load gTop into the gTop variable
@@ -90,7 +93,7 @@ impl<'ctx> Stack<'ctx> {
     // Increment the top
     let next_ptr = unsafe {
       builder.build_in_bounds_gep(
-        compiler.i32_type(),
+        compiler.slot_type(),
         ptr,
         &[compiler.i32_type().const_int(1, false)],
         "nextTopPtr",
@@ -100,27 +103,27 @@ impl<'ctx> Stack<'ctx> {
     builder.build_store(self.stack_top_ptr(), next_ptr);
   }
 
-  pub fn load_top(&'ctx self, compiler: &Compiler<'ctx>) -> BasicValueEnum<'ctx> {
+  pub fn load_top(&self, compiler: &Compiler<'ctx>) -> BasicValueEnum<'ctx> {
     compiler.builder().build_load(
-      compiler.ptr_i32_type(),
+      compiler.ptr_slot_type(),
       self.stack_top_ptr(),
       STACK_TOP_PTR_NAME,
     )
   }
 
-  pub fn load_top_ptr(&'ctx self, compiler: &Compiler<'ctx>) -> PointerValue<'ctx> {
+  pub fn load_top_ptr(&self, compiler: &Compiler<'ctx>) -> PointerValue<'ctx> {
     self.load_top(compiler).into_pointer_value()
   }
 
-  pub fn load_size(&'ctx self, compiler: &Compiler<'ctx>) -> BasicValueEnum<'ctx> {
+  pub fn load_size(&self, compiler: &Compiler<'ctx>) -> BasicValueEnum<'ctx> {
     compiler.builder().build_load(
-      compiler.ptr_i32_type(),
+      compiler.ptr_slot_type(),
       self.stack_top_ptr(),
       STACK_TOP_PTR_NAME,
     )
   }
 
-  pub fn load_size_ptr(&'ctx self, compiler: &Compiler<'ctx>) -> PointerValue<'ctx> {
+  pub fn load_size_ptr(&self, compiler: &Compiler<'ctx>) -> PointerValue<'ctx> {
     self.load_size(compiler).into_pointer_value()
   }
 }
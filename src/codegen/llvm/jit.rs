@@ -0,0 +1,111 @@
+use std::ffi::{c_char, c_int};
+
+use inkwell::context::Context;
+
+use crate::{codegen::CodeGeneratorOptions, parser::parse::AstNode};
+
+use super::{
+  compiler::Compiler,
+  emit,
+  externs::{exit::ExitExtern, printf::PrintfExtern},
+};
+
+extern "C" {
+  fn printf(format: *const c_char, ...) -> c_int;
+  fn exit(code: c_int) -> !;
+}
+
+type MainFunction = unsafe extern "C" fn() -> i32;
+
+/// Compile the program in memory and run it in this process. Returns what `main` returns, the
+/// exit code of the native executables.
+pub fn run(ast: &AstNode, options: &CodeGeneratorOptions) -> anyhow::Result<i32> {
+  let context = Context::create();
+  let compiler = Compiler::new(&context, "main")
+    .with_checked_arithmetic(options.checked_arithmetic)
+    .with_source(options.source_code.clone());
+
+  super::build(&compiler, ast, options.stack_size)?;
+  emit::prepare(compiler.module(), options)?;
+
+  let engine = compiler
+    .module()
+    .create_jit_execution_engine(emit::optimization_level(options.optimization_level))
+    .map_err(|e| anyhow::anyhow!("Could not create the JIT: {}", e.to_string_lossy()))?;
+
+  // The externs go to the C library of this process, without looking their names up
+  engine.add_global_mapping(&PrintfExtern::get(&compiler), printf as *const () as usize);
+  engine.add_global_mapping(&ExitExtern::get(&compiler), exit as *const () as usize);
+
+  let main = unsafe { engine.get_function::<MainFunction>("main") }
+    .map_err(|e| anyhow::anyhow!("Could not find main: {:?}", e))?;
+
+  Ok(unsafe { main.call() })
+}
+
+#[cfg(test)]
+mod jit_tests {
+  use super::*;
+  use crate::frontend::Program;
+
+  fn run_with(source: &str, options: CodeGeneratorOptions) -> i32 {
+    let program = Program::from_source(source.to_string(), None).unwrap();
+    run(&program.ast, &options).unwrap()
+  }
+
+  #[test]
+  fn test_main_returns_the_top_of_the_stack() {
+    assert_eq!(run_with("1 2 +\n", Default::default()), 3);
+    assert_eq!(run_with("42 dump\n", Default::default()), 0);
+    assert_eq!(run_with("\"a\" dump 1 2\n", Default::default()), 2);
+  }
+
+  #[test]
+  fn test_optimization_levels_agree() {
+    let source = "proc square i32 -- i32 do dup * end\n\
+      def(i32) total\n\
+      0 @total\n\
+      5 range i do i square total + @total end\n\
+      total\n";
+
+    for optimization_level in 0..=3 {
+      let options = CodeGeneratorOptions {
+        optimization_level,
+        ..Default::default()
+      };
+      assert_eq!(run_with(source, options), 30, "-O{optimization_level}");
+    }
+  }
+
+  #[test]
+  fn test_float_casts_saturate() {
+    // Like Rust's `as` in the VM: out of range floats saturate, NaN becomes 0 and is true
+    assert_eq!(run_with("1e20 :: i32\n", Default::default()), i32::MAX);
+    assert_eq!(
+      run_with("0.0 1e20 - :: i32\n", Default::default()),
+      i32::MIN
+    );
+    assert_eq!(
+      run_with("1e20 :: f64 :: i32\n", Default::default()),
+      i32::MAX
+    );
+    assert_eq!(run_with("0.0 0.0 / :: i32\n", Default::default()), 0);
+    assert_eq!(run_with("0.0 0.0 / :: i64\n", Default::default()), 0);
+    assert_eq!(run_with("0.0 0.0 / :: bool\n", Default::default()), 1);
+  }
+
+  #[test]
+  fn test_samples() {
+    for sample in ["procedures", "while", "range", "variables"] {
+      let source = std::fs::read_to_string(format!("assets/lang/{sample}.pile")).unwrap();
+      let options = CodeGeneratorOptions {
+        source_code: Some(source.clone()),
+        checked_arithmetic: true,
+        stack_size: Some(16),
+        ..Default::default()
+      };
+
+      assert_eq!(run_with(&source, options), 0, "{sample}");
+    }
+  }
+}
@@ -6,38 +6,203 @@ use self::{
   builtins::{abort::AbortBuiltin, pop::PopBuiltin, push::PushBuiltin},
   compiler::Compiler,
   externs::{exit::ExitExtern, printf::PrintfExtern},
-  globals::stack::Stack,
+  globals::stack::{Stack, DEFAULT_STACK_SIZE},
 };
 
-use super::CodeGenerator;
+use super::{CodeGenerator, CodeGeneratorOptions};
 
 pub mod builtins;
 pub mod compiler;
+pub mod emit;
 pub mod externs;
 pub mod generate_code;
 pub mod globals;
+pub mod jit;
 
 #[derive(Default)]
-pub struct LLVMCodeGenerator;
+pub struct LLVMCodeGenerator {
+  options: CodeGeneratorOptions,
+}
+
+impl LLVMCodeGenerator {
+  pub fn new(options: CodeGeneratorOptions) -> Self {
+    Self { options }
+  }
+}
 
 impl CodeGenerator for LLVMCodeGenerator {
-  fn generate(&mut self, ast: AstNode, _filename: String) -> anyhow::Result<()> {
-    // This trick is to ensure that stack is dropped before context
-    let stack;
-    {
-      let context = Context::create();
-      let compiler = Compiler::new(&context, "main");
-      stack = Stack::new(64 * 1024, &compiler);
-
-      PrintfExtern::declare(&compiler);
-      ExitExtern::declare(&compiler);
-      AbortBuiltin::declare(&compiler);
-      PushBuiltin::declare(&compiler, &stack);
-      PopBuiltin::declare(&compiler, &stack);
-
-      generate_code::GenerateLLVMIR::generate(&compiler, &ast)?;
+  fn generate(&mut self, ast: AstNode, filename: String) -> anyhow::Result<()> {
+    let context = Context::create();
+    let compiler = Compiler::new(&context, "main")
+      .with_checked_arithmetic(self.options.checked_arithmetic)
+      .with_source(self.options.source_code.clone());
+
+    build(&compiler, &ast, self.options.stack_size)?;
+    emit::emit(&compiler, &self.options, &filename)
+  }
+}
+
+/// Declare the stack, the externs and the builtins, then generate the program in the module
+pub fn build(
+  compiler: &Compiler<'_>,
+  ast: &AstNode,
+  stack_size: Option<usize>,
+) -> anyhow::Result<()> {
+  let stack_size = match stack_size {
+    Some(size) => u32::try_from(size)
+      .map_err(|_| anyhow::anyhow!("The stack size {size} is too large for the llvm backend"))?,
+    None => DEFAULT_STACK_SIZE,
+  };
+  let stack = Stack::new(stack_size, compiler);
+
+  PrintfExtern::declare(compiler);
+  ExitExtern::declare(compiler);
+  AbortBuiltin::declare(compiler);
+  PushBuiltin::declare(compiler, &stack);
+  PopBuiltin::declare(compiler, &stack);
+
+  generate_code::GenerateLLVMIR::generate(compiler, ast)
+}
+
+#[cfg(test)]
+mod llvm_tests {
+  use std::process::Command;
+
+  use super::*;
+  use crate::{
+    codegen::{vm::VMCodeGenerator, EmitKind},
+    frontend::Program,
+    interpreter::vm::VM,
+  };
+
+  fn options(source: &str) -> CodeGeneratorOptions {
+    CodeGeneratorOptions {
+      source_code: Some(source.to_string()),
+      ..Default::default()
     }
+  }
+
+  /// Run a program with the JIT, what its `main` returns
+  fn run_jit(source: &str) -> i32 {
+    let program = Program::from_source(source.to_string(), None).unwrap();
+    jit::run(&program.ast, &options(source)).unwrap()
+  }
+
+  /// Link a program into an executable named after the test and run it, what it printed and
+  /// its exit code. The programs that abort can't run in the process of the tests.
+  fn execute(name: &str, source: &str, options: CodeGeneratorOptions) -> (String, i32) {
+    let program = Program::from_source(source.to_string(), None).unwrap();
+    let output = std::env::temp_dir().join(format!("pile-{}-{}", name, std::process::id()));
+    let output = output.to_string_lossy().to_string();
+
+    LLVMCodeGenerator::new(CodeGeneratorOptions {
+      emit: EmitKind::Exe,
+      ..options
+    })
+    .generate(program.ast, output.clone())
+    .unwrap();
+
+    let result = Command::new(&output).output().unwrap();
+    std::fs::remove_file(&output).ok();
+
+    (
+      String::from_utf8_lossy(&result.stdout).to_string(),
+      result.status.code().unwrap(),
+    )
+  }
+
+  #[test]
+  fn test_if_else() {
+    assert_eq!(run_jit("1 2 < if 10 else 20 end\n"), 10);
+    assert_eq!(run_jit("2 1 < if 10 else 20 end\n"), 20);
+    assert_eq!(run_jit("5 1 1 = if 1 + end\n"), 6);
+    assert_eq!(run_jit("5 1 2 = if 1 + end\n"), 5);
+
+    let sample = std::fs::read_to_string("assets/lang/conditional_and_branching.pile").unwrap();
+    let (output, code) = execute("if-else", &sample, options(&sample));
+    assert_eq!((output.as_str(), code), ("2\n3\n", 0));
+  }
+
+  #[test]
+  fn test_signed_division() {
+    assert_eq!(run_jit("0 7 - 2 /\n"), -3);
+    assert_eq!(run_jit("0 7 - 2 %\n"), -1);
+    assert_eq!(run_jit("7 0 2 - /\n"), -3);
+    assert_eq!(run_jit("7 0 2 - %\n"), 1);
+
+    // The most negative number divided by -1 wraps around, like in the VM
+    assert_eq!(run_jit("0 2147483647 - 1 - 0 1 - /\n"), i32::MIN);
+    assert_eq!(run_jit("0 2147483647 - 1 - 0 1 - %\n"), 0);
+  }
+
+  #[test]
+  fn test_division_aborts() {
+    let source = "1 0 /\n";
+    assert_eq!(
+      execute("division-by-zero", source, options(source)),
+      ("[ABORT @ /]: division by zero\n".to_string(), 1)
+    );
+
+    let source = "1 0 %\n";
+    assert_eq!(
+      execute("remainder-by-zero", source, options(source)),
+      ("[ABORT @ %]: division by zero\n".to_string(), 1)
+    );
+
+    let source = "0 2147483647 - 1 - 0 1 - /\n";
+    let checked = CodeGeneratorOptions {
+      checked_arithmetic: true,
+      ..options(source)
+    };
+    assert_eq!(
+      execute("division-overflow", source, checked),
+      ("[ABORT @ /]: integer overflow\n".to_string(), 1)
+    );
+  }
+
+  #[test]
+  fn test_floats_are_dumped_like_in_the_vm() {
+    // The sign of a NaN depends on how it was computed, so they are left out
+    let numbers = [
+      "1234567.5",
+      "2013386.25",
+      "0.1",
+      "3.0",
+      "100.0",
+      "1.0 3.0 /",
+      "0.0 0.05 -",
+      "16777216.0",
+      "3000000000.0",
+      "0.0000001",
+      "0.0 1.0 - 0.0 *",
+      "1.0 0.0 /",
+      "0.0 1.0 0.0 / -",
+      "0.1 :: f64",
+      "1.0 :: f64 3.0 :: f64 /",
+    ];
+
+    let expected: String = numbers
+      .iter()
+      .map(|number| {
+        let program = Program::from_source(format!("{number}\n"), None).unwrap();
+        let mut generator = VMCodeGenerator::new();
+        generator.generate_byte_code(&program.ast).unwrap();
+
+        let mut vm = VM::new();
+        vm.execute(generator.bytecode()).unwrap();
+
+        format!("{}\n", vm.stack()[0])
+      })
+      .collect();
+
+    let source: String = numbers
+      .iter()
+      .map(|number| format!("{number} dump\n"))
+      .collect();
 
-    Ok(())
+    assert_eq!(
+      execute("float-dump", &source, options(&source)),
+      (expected, 0)
+    );
   }
 }
@@ -10,15 +10,48 @@ pub enum CodeGeneratorTarget {
   VirtualMachine,
 }
 
+/// What the native backends write
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum EmitKind {
+  LlvmIr,
+  Bitcode,
+  Asm,
+  Obj,
+  #[default]
+  Exe,
+}
+
+/// Options coming from the command line that the code generators may use
+#[derive(Debug, Clone, Default)]
+pub struct CodeGeneratorOptions {
+  /// Also write a human readable version of the output (e.g. `.wat` for Wasm)
+  pub emit_text: bool,
+  /// The source of the program, kept in the debug section of the outputs that have one
+  pub source_code: Option<String>,
+  /// How hard the code generators try to make the output smaller and faster (`-O`)
+  pub optimization_level: u8,
+  pub emit: EmitKind,
+  /// The target of the native output, the host when absent
+  pub target_triple: Option<String>,
+  /// Stop the native programs when `+`, `-`, `*` or `/` overflows an integer, like the VM does
+  /// with `--overflow trapping`
+  pub checked_arithmetic: bool,
+  /// How many values the stack holds, the backend's default when absent
+  pub stack_size: Option<usize>,
+}
+
 pub mod llvm;
 pub mod vm;
 pub mod wasm;
 
 // Choose the code generator based on the target
-pub fn code_generator(target: CodeGeneratorTarget) -> Box<dyn CodeGenerator> {
+pub fn code_generator(
+  target: CodeGeneratorTarget,
+  options: CodeGeneratorOptions,
+) -> Box<dyn CodeGenerator> {
   match target {
-    CodeGeneratorTarget::LLVM => Box::<llvm::LLVMCodeGenerator>::default(),
-    CodeGeneratorTarget::Wasm => Box::<wasm::WasmCodeGenerator>::default(),
-    CodeGeneratorTarget::VirtualMachine => Box::<vm::VMCodeGenerator>::default(),
+    CodeGeneratorTarget::LLVM => Box::new(llvm::LLVMCodeGenerator::new(options)),
+    CodeGeneratorTarget::Wasm => Box::new(wasm::WasmCodeGenerator::new(options)),
+    CodeGeneratorTarget::VirtualMachine => Box::new(vm::VMCodeGenerator::with_options(options)),
   }
 }
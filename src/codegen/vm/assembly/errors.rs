@@ -0,0 +1,57 @@
+use miette::Diagnostic;
+use thiserror::Error;
+
+#[derive(Error, Diagnostic, Debug)]
+pub enum AssemblyError {
+  #[error("Unknown instruction")]
+  #[diagnostic(code(assembly_error::unknown_instruction))]
+  UnknownInstruction {
+    #[source_code]
+    input: String,
+
+    #[help]
+    advice: String,
+
+    #[label = "Here"]
+    extension_src: (usize, usize),
+  },
+
+  #[error("Invalid operand")]
+  #[diagnostic(code(assembly_error::invalid_operand))]
+  InvalidOperand {
+    #[source_code]
+    input: String,
+
+    #[help]
+    advice: String,
+
+    #[label = "Here"]
+    extension_src: (usize, usize),
+  },
+
+  #[error("Unknown label")]
+  #[diagnostic(code(assembly_error::unknown_label))]
+  UnknownLabel {
+    #[source_code]
+    input: String,
+
+    #[help]
+    advice: String,
+
+    #[label = "Used here"]
+    extension_src: (usize, usize),
+  },
+
+  #[error("Duplicate label")]
+  #[diagnostic(code(assembly_error::duplicate_label))]
+  DuplicateLabel {
+    #[source_code]
+    input: String,
+
+    #[help]
+    advice: String,
+
+    #[label = "Defined again here"]
+    extension_src: (usize, usize),
+  },
+}
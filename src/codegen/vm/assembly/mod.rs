@@ -0,0 +1,483 @@
+//! A textual form of the bytecode, one instruction per line:
+//!
+//! ```text
+//! ; comments start with a semicolon
+//! .stack_size 64
+//!     push_int 3
+//!     range_init
+//! L0:
+//!     range_check L1
+//!     push_str "twice"
+//!     range_next L0
+//! L1:
+//! ```
+//!
+//! The jump targets are labels (or instruction numbers) and the other operands are written the
+//! way they are in a program. The `.stack_size` directive is the size of the stack the program
+//! asks for.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::lexer::tokens::Type;
+
+use self::errors::AssemblyError;
+
+use super::{container::Container, ByteCode};
+
+pub mod errors;
+
+/// The column of the comments that show the source of each instruction
+const COMMENT_COLUMN: usize = 32;
+
+pub fn mnemonic(instruction: &ByteCode) -> &'static str {
+  match instruction {
+    ByteCode::PushInt(_) => "push_int",
+    ByteCode::PushFloat(_) => "push_float",
+    ByteCode::PushStr(_) => "push_str",
+    ByteCode::PushBool(_) => "push_bool",
+    ByteCode::Pop => "pop",
+    ByteCode::Dump => "dump",
+    ByteCode::Dup => "dup",
+    ByteCode::Add => "add",
+    ByteCode::Sub => "sub",
+    ByteCode::Mul => "mul",
+    ByteCode::Div => "div",
+    ByteCode::Mod => "mod",
+    ByteCode::Cast(_) => "cast",
+    ByteCode::Eq => "eq",
+    ByteCode::Neq => "neq",
+    ByteCode::Lt => "lt",
+    ByteCode::Gt => "gt",
+    ByteCode::Leq => "leq",
+    ByteCode::Geq => "geq",
+    ByteCode::JumpIfNotTrue(_) => "jump_if_not_true",
+    ByteCode::Jump(_) => "jump",
+    ByteCode::Call(_) => "call",
+    ByteCode::Ret => "ret",
+    ByteCode::RangeInit(_) => "range_init",
+    ByteCode::RangeCheck(_) => "range_check",
+    ByteCode::RangeNext(_) => "range_next",
+    ByteCode::RangeCounter(_) => "range_counter",
+    ByteCode::Store(_) => "store",
+    ByteCode::Load(_) => "load",
+    ByteCode::Ignore => "ignore",
+  }
+}
+
+/// The instruction an instruction may continue at, other than the next one
+pub fn target(instruction: &ByteCode) -> Option<usize> {
+  match instruction {
+    ByteCode::JumpIfNotTrue(target)
+    | ByteCode::Jump(target)
+    | ByteCode::Call(target)
+    | ByteCode::RangeCheck(target)
+    | ByteCode::RangeNext(target) => Some(*target),
+    _ => None,
+  }
+}
+
+pub fn disassemble(container: &Container) -> String {
+  let bytecode = &container.bytecode;
+
+  // The labels are numbered in the order of their instructions
+  let labels = bytecode
+    .iter()
+    .filter_map(target)
+    .collect::<BTreeSet<_>>()
+    .into_iter()
+    .enumerate()
+    .map(|(index, target)| (target, format!("L{index}")))
+    .collect::<HashMap<_, _>>();
+
+  let mut listing = format!(
+    "; compiled by pile {}, {} instructions\n",
+    container.compiler_version,
+    bytecode.len()
+  );
+  if let Some(stack_size) = container.stack_size {
+    listing.push_str(&format!(".stack_size {stack_size}\n"));
+  }
+
+  for (location, instruction) in bytecode.iter().enumerate() {
+    if let Some(label) = labels.get(&location) {
+      listing.push_str(&format!("{label}:\n"));
+    }
+
+    let operand = match instruction {
+      ByteCode::PushInt(value) => value.to_string(),
+      ByteCode::PushFloat(value) => format!("{value:?}"),
+      ByteCode::PushStr(value) => format!("{value:?}"),
+      ByteCode::PushBool(value) => value.to_string(),
+      ByteCode::Cast(type_) => type_.to_string(),
+      ByteCode::RangeInit(true) => "from".to_string(),
+      ByteCode::RangeCounter(index) | ByteCode::Store(index) | ByteCode::Load(index) => {
+        index.to_string()
+      }
+      instruction => match target(instruction) {
+        Some(target) => labels[&target].clone(),
+        None => String::new(),
+      },
+    };
+
+    let line = format!("    {} {}", mnemonic(instruction), operand);
+    let line = line.trim_end();
+
+    let source_line = container.debug_info.as_ref().and_then(|debug_info| {
+      let line = debug_info.line(location)?;
+      Some((line, debug_info.line_text(line)?.trim()))
+    });
+
+    match source_line {
+      Some((number, text)) => {
+        listing.push_str(&format!("{line: <COMMENT_COLUMN$} ; {number}: {text}\n"))
+      }
+      None => listing.push_str(&format!("{line}\n")),
+    }
+  }
+
+  // A jump to the end of the program
+  if let Some(label) = labels.get(&bytecode.len()) {
+    listing.push_str(&format!("{label}:\n"));
+  }
+
+  listing
+}
+
+/// The container of the listing, without debug info
+pub fn assemble(source: &str) -> Result<Container, AssemblyError> {
+  let mut assembler = Assembler {
+    source,
+    labels: HashMap::new(),
+    fixups: vec![],
+    bytecode: vec![],
+    stack_size: None,
+  };
+
+  let mut offset = 0;
+  for line in source.split_inclusive('\n') {
+    assembler.line(strip_comment(line), offset)?;
+    offset += line.len();
+  }
+
+  let stack_size = assembler.stack_size;
+  Ok(Container::new(assembler.resolve_labels()?, None).with_stack_size(stack_size))
+}
+
+/// A piece of the source and its span (offset, length)
+type Word<'a> = (&'a str, (usize, usize));
+
+struct Assembler<'a> {
+  source: &'a str,
+  labels: HashMap<&'a str, usize>,
+  /// The instructions whose target is a label, it is only known once every line was read
+  fixups: Vec<(usize, Word<'a>)>,
+  bytecode: Vec<ByteCode>,
+  stack_size: Option<usize>,
+}
+
+impl<'a> Assembler<'a> {
+  fn line(&mut self, line: &'a str, offset: usize) -> Result<(), AssemblyError> {
+    let trimmed = line.trim_start();
+    let offset = offset + line.len() - trimmed.len();
+    let trimmed = trimmed.trim_end();
+
+    if trimmed.is_empty() {
+      return Ok(());
+    }
+
+    if let Some(label) = trimmed.strip_suffix(':') {
+      if !is_identifier(label) {
+        return Err(AssemblyError::InvalidOperand {
+          input: self.source.to_string(),
+          advice: "Labels are made of letters, digits and underscores".to_string(),
+          extension_src: (offset, trimmed.len()),
+        });
+      }
+
+      if self.labels.insert(label, self.bytecode.len()).is_some() {
+        return Err(AssemblyError::DuplicateLabel {
+          input: self.source.to_string(),
+          advice: format!("The label {label} is already defined"),
+          extension_src: (offset, label.len()),
+        });
+      }
+
+      return Ok(());
+    }
+
+    let (name, operand) = trimmed
+      .split_once(char::is_whitespace)
+      .unwrap_or((trimmed, ""));
+    let mnemonic = (name, (offset, name.len()));
+    let operand_text = operand.trim();
+    let operand = (
+      operand_text,
+      (
+        offset + trimmed.len() - operand_text.len(),
+        operand_text.len(),
+      ),
+    );
+
+    if name.starts_with('.') {
+      return self.directive(mnemonic, operand);
+    }
+
+    let instruction = self.instruction(mnemonic, operand)?;
+    self.bytecode.push(instruction);
+
+    Ok(())
+  }
+
+  fn directive(&mut self, directive: Word<'a>, operand: Word<'a>) -> Result<(), AssemblyError> {
+    match directive.0 {
+      ".stack_size" => {
+        let stack_size = self.parse(operand, "a stack size of at least 1", |text| {
+          text.parse().ok().filter(|&size| size > 0)
+        })?;
+        self.stack_size = Some(stack_size);
+
+        Ok(())
+      }
+      name => Err(AssemblyError::UnknownInstruction {
+        input: self.source.to_string(),
+        advice: format!("There is no directive named {name}"),
+        extension_src: directive.1,
+      }),
+    }
+  }
+
+  fn instruction(
+    &mut self,
+    mnemonic: Word<'a>,
+    operand: Word<'a>,
+  ) -> Result<ByteCode, AssemblyError> {
+    let simple = match mnemonic.0 {
+      "pop" => Some(ByteCode::Pop),
+      "dump" => Some(ByteCode::Dump),
+      "dup" => Some(ByteCode::Dup),
+      "add" => Some(ByteCode::Add),
+      "sub" => Some(ByteCode::Sub),
+      "mul" => Some(ByteCode::Mul),
+      "div" => Some(ByteCode::Div),
+      "mod" => Some(ByteCode::Mod),
+      "eq" => Some(ByteCode::Eq),
+      "neq" => Some(ByteCode::Neq),
+      "lt" => Some(ByteCode::Lt),
+      "gt" => Some(ByteCode::Gt),
+      "leq" => Some(ByteCode::Leq),
+      "geq" => Some(ByteCode::Geq),
+      "ret" => Some(ByteCode::Ret),
+      "ignore" => Some(ByteCode::Ignore),
+      _ => None,
+    };
+
+    if let Some(instruction) = simple {
+      return match operand.0 {
+        "" => Ok(instruction),
+        _ => Err(self.invalid_operand(operand, format!("{} takes no operand", mnemonic.0))),
+      };
+    }
+
+    Ok(match mnemonic.0 {
+      "push_int" => ByteCode::PushInt(self.parse(operand, "an integer", |text| text.parse().ok())?),
+      "push_float" => {
+        ByteCode::PushFloat(self.parse(operand, "a float", |text| text.parse().ok())?)
+      }
+      "push_str" => ByteCode::PushStr(self.parse(operand, "a quoted string", parse_string)?),
+      "push_bool" => {
+        ByteCode::PushBool(self.parse(operand, "true or false", |text| text.parse().ok())?)
+      }
+      "cast" => ByteCode::Cast(self.parse(operand, "a type", Type::from_name)?),
+      "jump_if_not_true" => ByteCode::JumpIfNotTrue(self.target(operand)?),
+      "jump" => ByteCode::Jump(self.target(operand)?),
+      "call" => ByteCode::Call(self.target(operand)?),
+      "range_init" => {
+        ByteCode::RangeInit(self.parse(operand, "nothing or from", |text| match text {
+          "" => Some(false),
+          "from" => Some(true),
+          _ => None,
+        })?)
+      }
+      "range_check" => ByteCode::RangeCheck(self.target(operand)?),
+      "range_next" => ByteCode::RangeNext(self.target(operand)?),
+      "range_counter" => ByteCode::RangeCounter(self.index(operand)?),
+      "store" => ByteCode::Store(self.index(operand)?),
+      "load" => ByteCode::Load(self.index(operand)?),
+      name => {
+        return Err(AssemblyError::UnknownInstruction {
+          input: self.source.to_string(),
+          advice: format!("There is no instruction named {name}"),
+          extension_src: mnemonic.1,
+        });
+      }
+    })
+  }
+
+  fn parse<T>(
+    &self,
+    operand: Word<'a>,
+    expected: &str,
+    parse: impl Fn(&str) -> Option<T>,
+  ) -> Result<T, AssemblyError> {
+    parse(operand.0).ok_or_else(|| self.invalid_operand(operand, format!("Expected {expected}")))
+  }
+
+  fn index(&self, operand: Word<'a>) -> Result<usize, AssemblyError> {
+    self.parse(operand, "a number", |text| text.parse().ok())
+  }
+
+  /// An instruction number, or a label that is resolved once every line was read
+  fn target(&mut self, operand: Word<'a>) -> Result<usize, AssemblyError> {
+    if let Ok(target) = operand.0.parse() {
+      return Ok(target);
+    }
+
+    if !is_identifier(operand.0) {
+      return Err(self.invalid_operand(operand, "Expected a label".to_string()));
+    }
+
+    self.fixups.push((self.bytecode.len(), operand));
+    Ok(usize::MAX) // Placeholder position for now
+  }
+
+  fn resolve_labels(mut self) -> Result<Vec<ByteCode>, AssemblyError> {
+    for (location, (label, span)) in self.fixups.iter() {
+      let target = *self
+        .labels
+        .get(label)
+        .ok_or_else(|| AssemblyError::UnknownLabel {
+          input: self.source.to_string(),
+          advice: format!("There is no label named {label}"),
+          extension_src: *span,
+        })?;
+
+      self.bytecode[*location] = match self.bytecode[*location] {
+        ByteCode::JumpIfNotTrue(_) => ByteCode::JumpIfNotTrue(target),
+        ByteCode::Jump(_) => ByteCode::Jump(target),
+        ByteCode::Call(_) => ByteCode::Call(target),
+        ByteCode::RangeCheck(_) => ByteCode::RangeCheck(target),
+        ByteCode::RangeNext(_) => ByteCode::RangeNext(target),
+        ref instruction => instruction.clone(),
+      };
+    }
+
+    Ok(self.bytecode)
+  }
+
+  fn invalid_operand(&self, operand: Word<'a>, advice: String) -> AssemblyError {
+    AssemblyError::InvalidOperand {
+      input: self.source.to_string(),
+      advice,
+      extension_src: operand.1,
+    }
+  }
+}
+
+fn is_identifier(text: &str) -> bool {
+  let mut chars = text.chars();
+
+  matches!(chars.next(), Some(first) if first.is_ascii_alphabetic() || first == '_')
+    && chars.all(|char| char.is_ascii_alphanumeric() || char == '_')
+}
+
+/// Everything before the first `;` that is not in a string
+fn strip_comment(line: &str) -> &str {
+  let mut in_string = false;
+  let mut escaped = false;
+
+  for (index, char) in line.char_indices() {
+    match char {
+      _ if escaped => escaped = false,
+      '\\' if in_string => escaped = true,
+      '"' => in_string = !in_string,
+      ';' if !in_string => return &line[..index],
+      _ => {}
+    }
+  }
+
+  line
+}
+
+/// A string in quotes, with the escapes the disassembler writes
+fn parse_string(text: &str) -> Option<String> {
+  let mut chars = text.strip_prefix('"')?.strip_suffix('"')?.chars();
+  let mut string = String::new();
+
+  while let Some(char) = chars.next() {
+    if char != '\\' {
+      string.push(char);
+      continue;
+    }
+
+    string.push(match chars.next()? {
+      'n' => '\n',
+      'r' => '\r',
+      't' => '\t',
+      '0' => '\0',
+      'u' => {
+        let code = chars
+          .by_ref()
+          .skip_while(|&char| char == '{')
+          .take_while(|&char| char != '}')
+          .collect::<String>();
+
+        char::from_u32(u32::from_str_radix(&code, 16).ok()?)?
+      }
+      escaped => escaped,
+    });
+  }
+
+  Some(string)
+}
+
+#[cfg(test)]
+mod assembly_tests {
+  use super::*;
+
+  #[test]
+  fn test_round_trip() {
+    let bytecode = vec![
+      ByteCode::PushStr("say \"hi\"; bye\n".to_string()),
+      ByteCode::PushInt(3),
+      ByteCode::RangeInit(false),
+      ByteCode::RangeCheck(6),
+      ByteCode::Cast(Type::F64),
+      ByteCode::RangeNext(3),
+      ByteCode::PushFloat(1.5),
+      ByteCode::Jump(8),
+    ];
+
+    let listing = disassemble(&Container::new(bytecode.clone(), None));
+    let assembled = assemble(&listing).unwrap();
+
+    assert_eq!(
+      format!("{:?}", assembled.bytecode),
+      format!("{:?}", bytecode)
+    );
+    assert_eq!(assembled.stack_size, None);
+
+    let container = Container::new(bytecode, None).with_stack_size(Some(64));
+    let assembled = assemble(&disassemble(&container)).unwrap();
+    assert_eq!(assembled.stack_size, Some(64));
+  }
+
+  #[test]
+  fn test_directives() {
+    assert!(matches!(
+      assemble(".stack_size 0\n"),
+      Err(AssemblyError::InvalidOperand { .. })
+    ));
+    assert!(matches!(
+      assemble(".heap_size 64\n"),
+      Err(AssemblyError::UnknownInstruction { .. })
+    ));
+  }
+
+  #[test]
+  fn test_unknown_label() {
+    assert!(matches!(
+      assemble("    jump nowhere\n"),
+      Err(AssemblyError::UnknownLabel { .. })
+    ));
+  }
+}
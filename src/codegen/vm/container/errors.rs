@@ -0,0 +1,34 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ContainerError {
+  #[error("Not a pile bytecode file (missing magic number), it may come from an older compiler: compile it again")]
+  MissingMagic,
+
+  #[error("Unsupported bytecode format version {found}, this interpreter reads versions up to {supported}: compile it again")]
+  UnsupportedVersion { found: u16, supported: u16 },
+
+  #[error("Corrupted bytecode file: the stored checksum {stored:#010x} does not match the contents ({computed:#010x})")]
+  ChecksumMismatch { stored: u32, computed: u32 },
+
+  #[error("Corrupted bytecode file: unexpected end of the {section} section")]
+  Truncated { section: &'static str },
+
+  #[error("Corrupted bytecode file: unknown opcode {opcode:#04x} at instruction {instruction}")]
+  UnknownOpcode { opcode: u8, instruction: usize },
+
+  #[error("Corrupted bytecode file: unknown type tag {tag:#04x} at instruction {instruction}")]
+  UnknownType { tag: u8, instruction: usize },
+
+  #[error("Corrupted bytecode file: constant {index} does not exist (instruction {instruction})")]
+  UnknownConstant { index: usize, instruction: usize },
+
+  #[error("Corrupted bytecode file: {0} unexpected bytes after the debug section")]
+  TrailingBytes(usize),
+
+  #[error("Corrupted bytecode file: invalid text in the {section} section")]
+  InvalidText { section: &'static str },
+
+  #[error("The program is too large for the bytecode format ({0} does not fit in 32 bits)")]
+  TooLarge(usize),
+}
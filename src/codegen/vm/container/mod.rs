@@ -0,0 +1,436 @@
+//! The `.bin` file format. Every number is little endian:
+//!
+//! ```text
+//! magic             b"PILE"
+//! format version    u16
+//! compiler version  u32 length + utf-8
+//! stack size        u32, how many values the stack holds (0 for the default), since version 2
+//! constant pool     u32 count, each one a u32 length + utf-8
+//! code              u32 count, each one an opcode (u8) followed by its operands
+//! debug section     u8 (0 when absent), then the source (u32 length + utf-8) and, for every
+//!                   instruction, the span (u32 offset, u32 length) of the code that emitted it
+//! checksum          u32, CRC-32 of everything before it
+//! ```
+
+use std::{collections::HashMap, fs::File, io::Read};
+
+use super::{debug_info::DebugInfo, ByteCode};
+
+use self::{errors::ContainerError, opcode::*};
+
+pub mod errors;
+pub mod opcode;
+
+pub const MAGIC: &[u8; 4] = b"PILE";
+
+/// Bump it whenever a change to the format keeps the older interpreters from reading the files
+pub const FORMAT_VERSION: u16 = 2;
+
+pub const COMPILER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const CHECKSUM_SIZE: usize = 4;
+
+/// A compiled program as it is stored on disk
+#[derive(Debug, Clone)]
+pub struct Container {
+  /// The version of the compiler that wrote the file
+  pub compiler_version: String,
+  pub bytecode: Vec<ByteCode>,
+  pub debug_info: Option<DebugInfo>,
+  /// The size of the stack the program asked for, the interpreter's default when absent
+  pub stack_size: Option<usize>,
+}
+
+impl Container {
+  pub fn new(bytecode: Vec<ByteCode>, debug_info: Option<DebugInfo>) -> Self {
+    Self {
+      compiler_version: COMPILER_VERSION.to_string(),
+      bytecode,
+      debug_info,
+      stack_size: None,
+    }
+  }
+
+  pub fn with_stack_size(mut self, stack_size: Option<usize>) -> Self {
+    self.stack_size = stack_size;
+    self
+  }
+
+  pub fn encode(&self) -> Result<Vec<u8>, ContainerError> {
+    let mut writer = Writer::default();
+
+    writer.bytes.extend_from_slice(MAGIC);
+    writer.u16(FORMAT_VERSION);
+    writer.text(&self.compiler_version)?;
+    writer.length(self.stack_size.unwrap_or(0))?;
+
+    // Every string is stored once, the instructions refer to it by its index
+    let mut constants: Vec<&str> = vec![];
+    let mut constant_indexes: HashMap<&str, usize> = HashMap::new();
+    for instruction in self.bytecode.iter() {
+      if let ByteCode::PushStr(value) = instruction {
+        constant_indexes.entry(value).or_insert_with(|| {
+          constants.push(value);
+          constants.len() - 1
+        });
+      }
+    }
+
+    writer.length(constants.len())?;
+    for constant in constants.iter() {
+      writer.text(constant)?;
+    }
+
+    writer.length(self.bytecode.len())?;
+    for instruction in self.bytecode.iter() {
+      writer.u8(opcode(instruction));
+
+      match instruction {
+        ByteCode::PushInt(value) => writer.bytes.extend_from_slice(&value.to_le_bytes()),
+        ByteCode::PushFloat(value) => writer.bytes.extend_from_slice(&value.to_le_bytes()),
+        ByteCode::PushStr(value) => writer.length(constant_indexes[value.as_str()])?,
+        ByteCode::PushBool(value) | ByteCode::RangeInit(value) => writer.u8(*value as u8),
+        ByteCode::Cast(type_) => writer.u8(type_tag(type_)),
+        ByteCode::JumpIfNotTrue(operand)
+        | ByteCode::Jump(operand)
+        | ByteCode::Call(operand)
+        | ByteCode::RangeCheck(operand)
+        | ByteCode::RangeNext(operand)
+        | ByteCode::RangeCounter(operand)
+        | ByteCode::Store(operand)
+        | ByteCode::Load(operand) => writer.length(*operand)?,
+        _ => {}
+      }
+    }
+
+    match &self.debug_info {
+      Some(debug_info) => {
+        writer.u8(1);
+        writer.text(&debug_info.source)?;
+        writer.length(debug_info.spans.len())?;
+        for (offset, length) in debug_info.spans.iter() {
+          writer.length(*offset)?;
+          writer.length(*length)?;
+        }
+      }
+      None => writer.u8(0),
+    }
+
+    let checksum = crc32fast::hash(&writer.bytes);
+    writer.bytes.extend_from_slice(&checksum.to_le_bytes());
+
+    Ok(writer.bytes)
+  }
+
+  pub fn decode(bytes: &[u8]) -> Result<Container, ContainerError> {
+    if !bytes.starts_with(MAGIC) {
+      return Err(ContainerError::MissingMagic);
+    }
+
+    let mut reader = Reader::new(&bytes[MAGIC.len()..]);
+
+    // The version comes before the checksum, a newer format may not even have one, nor anything
+    // else of this header
+    let version = reader.u16("header")?;
+    if version == 0 || version > FORMAT_VERSION {
+      return Err(ContainerError::UnsupportedVersion {
+        found: version,
+        supported: FORMAT_VERSION,
+      });
+    }
+    let compiler_version = reader.text("header")?;
+
+    let stack_size = match version {
+      1 => None,
+      _ => Some(reader.length("header")?).filter(|&size| size > 0),
+    };
+
+    if bytes.len() < MAGIC.len() + CHECKSUM_SIZE {
+      return Err(ContainerError::Truncated { section: "checksum" });
+    }
+    let (contents, checksum) = bytes.split_at(bytes.len() - CHECKSUM_SIZE);
+    let stored = u32::from_le_bytes(checksum.try_into().unwrap());
+    let computed = crc32fast::hash(contents);
+    if stored != computed {
+      return Err(ContainerError::ChecksumMismatch { stored, computed });
+    }
+
+    let body = contents
+      .get(MAGIC.len() + reader.position..)
+      .ok_or(ContainerError::Truncated { section: "header" })?;
+    let mut reader = Reader::new(body);
+
+    let constants = (0..reader.length("constant pool")?)
+      .map(|_| reader.text("constant pool"))
+      .collect::<Result<Vec<_>, _>>()?;
+
+    let count = reader.length("code")?;
+    let mut bytecode = Vec::with_capacity(count);
+    for instruction in 0..count {
+      bytecode.push(reader.instruction(instruction, &constants)?);
+    }
+
+    let debug_info = match reader.u8("debug")? {
+      0 => None,
+      _ => {
+        let source = reader.text("debug")?;
+        let spans = (0..reader.length("debug")?)
+          .map(|_| Ok((reader.length("debug")?, reader.length("debug")?)))
+          .collect::<Result<Vec<_>, _>>()?;
+
+        Some(DebugInfo::new(source, spans))
+      }
+    };
+
+    let trailing = body.len() - reader.position;
+    if trailing > 0 {
+      return Err(ContainerError::TrailingBytes(trailing));
+    }
+
+    Ok(Container {
+      compiler_version,
+      bytecode,
+      debug_info,
+      stack_size,
+    })
+  }
+
+  pub fn write(&self, filename: &str) -> anyhow::Result<()> {
+    use std::io::Write;
+
+    let encoded = self.encode()?;
+
+    let mut file =
+      File::create(filename).map_err(|e| anyhow::anyhow!("Error creating file: {}", e))?;
+    file
+      .write_all(&encoded)
+      .map_err(|e| anyhow::anyhow!("Error writing to file: {}", e))?;
+
+    Ok(())
+  }
+
+  pub fn read(filename: &str) -> anyhow::Result<Container> {
+    let mut file =
+      File::open(filename).map_err(|e| anyhow::anyhow!("Error opening file: {}", e))?;
+    let mut encoded = Vec::new();
+    file
+      .read_to_end(&mut encoded)
+      .map_err(|e| anyhow::anyhow!("Error reading file: {}", e))?;
+
+    Container::decode(&encoded).map_err(|e| anyhow::anyhow!("{}: {}", filename, e))
+  }
+}
+
+#[derive(Default)]
+struct Writer {
+  bytes: Vec<u8>,
+}
+
+impl Writer {
+  fn u8(&mut self, value: u8) {
+    self.bytes.push(value);
+  }
+
+  fn u16(&mut self, value: u16) {
+    self.bytes.extend_from_slice(&value.to_le_bytes());
+  }
+
+  /// Lengths, counts, offsets and instruction locations are all stored as u32
+  fn length(&mut self, value: usize) -> Result<(), ContainerError> {
+    let value = u32::try_from(value).map_err(|_| ContainerError::TooLarge(value))?;
+    self.bytes.extend_from_slice(&value.to_le_bytes());
+    Ok(())
+  }
+
+  fn text(&mut self, value: &str) -> Result<(), ContainerError> {
+    self.length(value.len())?;
+    self.bytes.extend_from_slice(value.as_bytes());
+    Ok(())
+  }
+}
+
+struct Reader<'a> {
+  bytes: &'a [u8],
+  position: usize,
+}
+
+impl<'a> Reader<'a> {
+  fn new(bytes: &'a [u8]) -> Self {
+    Self { bytes, position: 0 }
+  }
+
+  fn take<const N: usize>(&mut self, section: &'static str) -> Result<[u8; N], ContainerError> {
+    let bytes = self
+      .bytes
+      .get(self.position..self.position + N)
+      .ok_or(ContainerError::Truncated { section })?;
+    self.position += N;
+
+    Ok(bytes.try_into().unwrap())
+  }
+
+  fn u8(&mut self, section: &'static str) -> Result<u8, ContainerError> {
+    Ok(self.take::<1>(section)?[0])
+  }
+
+  fn u16(&mut self, section: &'static str) -> Result<u16, ContainerError> {
+    Ok(u16::from_le_bytes(self.take(section)?))
+  }
+
+  fn length(&mut self, section: &'static str) -> Result<usize, ContainerError> {
+    Ok(u32::from_le_bytes(self.take(section)?) as usize)
+  }
+
+  fn text(&mut self, section: &'static str) -> Result<String, ContainerError> {
+    let length = self.length(section)?;
+    let bytes = self
+      .bytes
+      .get(self.position..self.position + length)
+      .ok_or(ContainerError::Truncated { section })?;
+    self.position += length;
+
+    String::from_utf8(bytes.to_vec()).map_err(|_| ContainerError::InvalidText { section })
+  }
+
+  fn instruction(
+    &mut self,
+    instruction: usize,
+    constants: &[String],
+  ) -> Result<ByteCode, ContainerError> {
+    const SECTION: &str = "code";
+
+    let opcode = self.u8(SECTION)?;
+    if let Some(simple) = simple_instruction(opcode) {
+      return Ok(simple);
+    }
+
+    Ok(match opcode {
+      PUSH_INT => ByteCode::PushInt(i32::from_le_bytes(self.take(SECTION)?)),
+      PUSH_FLOAT => ByteCode::PushFloat(f32::from_le_bytes(self.take(SECTION)?)),
+      PUSH_STR => {
+        let index = self.length(SECTION)?;
+        let constant = constants
+          .get(index)
+          .ok_or(ContainerError::UnknownConstant { index, instruction })?;
+
+        ByteCode::PushStr(constant.clone())
+      }
+      PUSH_BOOL => ByteCode::PushBool(self.u8(SECTION)? != 0),
+      CAST => {
+        let tag = self.u8(SECTION)?;
+        let type_ = tag_type(tag).ok_or(ContainerError::UnknownType { tag, instruction })?;
+
+        ByteCode::Cast(type_)
+      }
+      JUMP_IF_NOT_TRUE => ByteCode::JumpIfNotTrue(self.length(SECTION)?),
+      JUMP => ByteCode::Jump(self.length(SECTION)?),
+      CALL => ByteCode::Call(self.length(SECTION)?),
+      RANGE_INIT => ByteCode::RangeInit(self.u8(SECTION)? != 0),
+      RANGE_CHECK => ByteCode::RangeCheck(self.length(SECTION)?),
+      RANGE_NEXT => ByteCode::RangeNext(self.length(SECTION)?),
+      RANGE_COUNTER => ByteCode::RangeCounter(self.length(SECTION)?),
+      STORE => ByteCode::Store(self.length(SECTION)?),
+      LOAD => ByteCode::Load(self.length(SECTION)?),
+      _ => return Err(ContainerError::UnknownOpcode { opcode, instruction }),
+    })
+  }
+}
+
+#[cfg(test)]
+mod container_tests {
+  use crate::lexer::tokens::Type;
+
+  use super::*;
+
+  fn sample() -> Container {
+    Container::new(
+      vec![
+        ByteCode::PushStr("hello".to_string()),
+        ByteCode::PushInt(-7),
+        ByteCode::Cast(Type::F64),
+        ByteCode::JumpIfNotTrue(5),
+        ByteCode::PushStr("hello".to_string()),
+        ByteCode::Dump,
+      ],
+      Some(DebugInfo::new(
+        "\"hello\" -7 :: f64".to_string(),
+        vec![(0, 7); 6],
+      )),
+    )
+    .with_stack_size(Some(256))
+  }
+
+  #[test]
+  fn test_round_trip() {
+    let container = sample();
+    let decoded = Container::decode(&container.encode().unwrap()).unwrap();
+
+    assert_eq!(
+      format!("{:?}", decoded.bytecode),
+      format!("{:?}", container.bytecode)
+    );
+    assert_eq!(decoded.compiler_version, COMPILER_VERSION);
+    assert_eq!(decoded.debug_info.unwrap().spans, vec![(0, 7); 6]);
+    assert_eq!(decoded.stack_size, Some(256));
+  }
+
+  #[test]
+  fn test_rejects_invalid_files() {
+    let mut encoded = sample().encode().unwrap();
+
+    assert!(matches!(
+      Container::decode(b"not a program"),
+      Err(ContainerError::MissingMagic)
+    ));
+
+    let middle = encoded.len() / 2;
+    encoded[middle] ^= 0xff;
+    assert!(matches!(
+      Container::decode(&encoded),
+      Err(ContainerError::ChecksumMismatch { .. })
+    ));
+
+    encoded[MAGIC.len()] = 0xff;
+    assert!(matches!(
+      Container::decode(&encoded),
+      Err(ContainerError::UnsupportedVersion { found: 0xff, .. })
+    ));
+  }
+
+  /// The bytes of `sample` with `edit` applied before the checksum, which still matches
+  fn edited(edit: impl FnOnce(&mut Vec<u8>)) -> Vec<u8> {
+    let mut encoded = sample().encode().unwrap();
+    encoded.truncate(encoded.len() - CHECKSUM_SIZE);
+    edit(&mut encoded);
+
+    let checksum = crc32fast::hash(&encoded);
+    encoded.extend_from_slice(&checksum.to_le_bytes());
+    encoded
+  }
+
+  #[test]
+  fn test_version_is_checked_first() {
+    // Nothing after the version is known of a newer format
+    let mut newer = MAGIC.to_vec();
+    newer.extend_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+    assert!(matches!(
+      Container::decode(&newer),
+      Err(ContainerError::UnsupportedVersion { found, .. }) if found == FORMAT_VERSION + 1
+    ));
+
+    let older = edited(|bytes| bytes[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&[0, 0]));
+    assert!(matches!(
+      Container::decode(&older),
+      Err(ContainerError::UnsupportedVersion { found: 0, .. })
+    ));
+  }
+
+  #[test]
+  fn test_rejects_trailing_bytes() {
+    let padded = edited(|bytes| bytes.extend_from_slice(&[0; 3]));
+    assert!(matches!(
+      Container::decode(&padded),
+      Err(ContainerError::TrailingBytes(3))
+    ));
+  }
+}
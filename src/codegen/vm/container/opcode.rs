@@ -0,0 +1,135 @@
+// The numbers of the opcodes and of the types in the bytecode format. They are part of the
+// format: never reuse or change a number, add new ones instead (and bump the format version when
+// an older interpreter can't run the new files)
+
+use crate::lexer::tokens::Type;
+
+use super::super::ByteCode;
+
+// Stack manipulation
+pub const PUSH_INT: u8 = 0x01;
+pub const PUSH_FLOAT: u8 = 0x02;
+pub const PUSH_STR: u8 = 0x03;
+pub const PUSH_BOOL: u8 = 0x04;
+pub const POP: u8 = 0x05;
+pub const DUMP: u8 = 0x06;
+pub const DUP: u8 = 0x07;
+
+// Arithmetic
+pub const ADD: u8 = 0x10;
+pub const SUB: u8 = 0x11;
+pub const MUL: u8 = 0x12;
+pub const DIV: u8 = 0x13;
+pub const MOD: u8 = 0x14;
+
+// Conversion
+pub const CAST: u8 = 0x18;
+
+// Comparison
+pub const EQ: u8 = 0x20;
+pub const NEQ: u8 = 0x21;
+pub const LT: u8 = 0x22;
+pub const GT: u8 = 0x23;
+pub const LEQ: u8 = 0x24;
+pub const GEQ: u8 = 0x25;
+
+// Branching
+pub const JUMP_IF_NOT_TRUE: u8 = 0x30;
+pub const JUMP: u8 = 0x31;
+
+// Procedures
+pub const CALL: u8 = 0x38;
+pub const RET: u8 = 0x39;
+
+// Counted loops
+pub const RANGE_INIT: u8 = 0x40;
+pub const RANGE_CHECK: u8 = 0x41;
+pub const RANGE_NEXT: u8 = 0x42;
+pub const RANGE_COUNTER: u8 = 0x43;
+
+// Variables
+pub const STORE: u8 = 0x48;
+pub const LOAD: u8 = 0x49;
+
+// Ignore
+pub const IGNORE: u8 = 0x7f;
+
+/// The opcode of an instruction, its operands are written after it
+pub fn opcode(instruction: &ByteCode) -> u8 {
+  match instruction {
+    ByteCode::PushInt(_) => PUSH_INT,
+    ByteCode::PushFloat(_) => PUSH_FLOAT,
+    ByteCode::PushStr(_) => PUSH_STR,
+    ByteCode::PushBool(_) => PUSH_BOOL,
+    ByteCode::Pop => POP,
+    ByteCode::Dump => DUMP,
+    ByteCode::Dup => DUP,
+    ByteCode::Add => ADD,
+    ByteCode::Sub => SUB,
+    ByteCode::Mul => MUL,
+    ByteCode::Div => DIV,
+    ByteCode::Mod => MOD,
+    ByteCode::Cast(_) => CAST,
+    ByteCode::Eq => EQ,
+    ByteCode::Neq => NEQ,
+    ByteCode::Lt => LT,
+    ByteCode::Gt => GT,
+    ByteCode::Leq => LEQ,
+    ByteCode::Geq => GEQ,
+    ByteCode::JumpIfNotTrue(_) => JUMP_IF_NOT_TRUE,
+    ByteCode::Jump(_) => JUMP,
+    ByteCode::Call(_) => CALL,
+    ByteCode::Ret => RET,
+    ByteCode::RangeInit(_) => RANGE_INIT,
+    ByteCode::RangeCheck(_) => RANGE_CHECK,
+    ByteCode::RangeNext(_) => RANGE_NEXT,
+    ByteCode::RangeCounter(_) => RANGE_COUNTER,
+    ByteCode::Store(_) => STORE,
+    ByteCode::Load(_) => LOAD,
+    ByteCode::Ignore => IGNORE,
+  }
+}
+
+/// The instructions without operands
+pub fn simple_instruction(opcode: u8) -> Option<ByteCode> {
+  match opcode {
+    POP => Some(ByteCode::Pop),
+    DUMP => Some(ByteCode::Dump),
+    DUP => Some(ByteCode::Dup),
+    ADD => Some(ByteCode::Add),
+    SUB => Some(ByteCode::Sub),
+    MUL => Some(ByteCode::Mul),
+    DIV => Some(ByteCode::Div),
+    MOD => Some(ByteCode::Mod),
+    EQ => Some(ByteCode::Eq),
+    NEQ => Some(ByteCode::Neq),
+    LT => Some(ByteCode::Lt),
+    GT => Some(ByteCode::Gt),
+    LEQ => Some(ByteCode::Leq),
+    GEQ => Some(ByteCode::Geq),
+    RET => Some(ByteCode::Ret),
+    IGNORE => Some(ByteCode::Ignore),
+    _ => None,
+  }
+}
+
+pub fn type_tag(type_: &Type) -> u8 {
+  match type_ {
+    Type::I32 => 0x01,
+    Type::I64 => 0x02,
+    Type::F32 => 0x03,
+    Type::F64 => 0x04,
+    Type::Bool => 0x05,
+  }
+}
+
+pub fn tag_type(tag: u8) -> Option<Type> {
+  match tag {
+    0x01 => Some(Type::I32),
+    0x02 => Some(Type::I64),
+    0x03 => Some(Type::F32),
+    0x04 => Some(Type::F64),
+    0x05 => Some(Type::Bool),
+    _ => None,
+  }
+}
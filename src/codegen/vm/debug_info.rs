@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+/// The source a program was compiled from and, for every instruction, the span (offset, length)
+/// of the code that emitted it
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DebugInfo {
+  pub source: String,
+  pub spans: Vec<(usize, usize)>,
+}
+
+impl DebugInfo {
+  pub fn new(source: String, spans: Vec<(usize, usize)>) -> Self {
+    Self { source, spans }
+  }
+
+  pub fn span(&self, instruction: usize) -> Option<(usize, usize)> {
+    self.spans.get(instruction).copied()
+  }
+
+  /// The line (starting at 1) of an instruction
+  pub fn line(&self, instruction: usize) -> Option<usize> {
+    let (offset, _) = self.span(instruction)?;
+    let offset = offset.min(self.source.len());
+
+    Some(self.source.as_bytes()[..offset].iter().filter(|&&byte| byte == b'\n').count() + 1)
+  }
+
+  /// The text of a line (starting at 1)
+  pub fn line_text(&self, line: usize) -> Option<&str> {
+    self.source.lines().nth(line.checked_sub(1)?)
+  }
+
+  /// The first instruction emitted for a line, if any
+  pub fn first_instruction(&self, line: usize) -> Option<usize> {
+    (0..self.spans.len()).find(|&instruction| self.line(instruction) == Some(line))
+  }
+}
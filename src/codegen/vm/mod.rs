@@ -1,12 +1,19 @@
 use serde::{Deserialize, Serialize};
 
 use crate::{
-  lexer::tokens::{ArithmeticOperators, ComparisonOperators, StackOperators, Token},
+  lexer::tokens::{ArithmeticOperators, ComparisonOperators, StackOperators, Token, Type},
   parser::parse::AstNode,
 };
-use std::{collections::HashMap, fs::File};
+use std::collections::HashMap;
 
-use super::CodeGenerator;
+use self::{container::Container, debug_info::DebugInfo, optimizer::Optimizer};
+
+use super::{CodeGenerator, CodeGeneratorOptions};
+
+pub mod assembly;
+pub mod container;
+pub mod debug_info;
+pub mod optimizer;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum ByteCode {
@@ -26,6 +33,9 @@ pub enum ByteCode {
   Div,
   Mod,
 
+  // Conversion
+  Cast(Type),
+
   // Comparison
   Eq,
   Neq,
@@ -38,60 +48,193 @@ pub enum ByteCode {
   JumpIfNotTrue(usize),
   Jump(usize),
 
+  // Procedures
+  Call(usize),
+  Ret,
+
+  // Counted loops
+  RangeInit(bool),   // Pops the bound (and the start when true) into a new loop frame
+  RangeCheck(usize), // Leaves the loop and drops its frame when the counter reached the bound
+  RangeNext(usize),  // Increments the counter and jumps back to the check
+  RangeCounter(usize), // Pushes the counter of the n-th enclosing loop (0 is the innermost)
+
+  // Variables
+  Store(usize), // Pops the top of the stack into a variable slot
+  Load(usize),  // Pushes the value of a variable slot
+
   // Ignore
   Ignore,
 }
 
+impl ByteCode {
+  /// How many values the instruction takes from the stack
+  pub fn consumed_values(&self) -> usize {
+    use ByteCode::*;
+
+    match self {
+      Add | Sub | Mul | Div | Mod | Eq | Neq | Lt | Gt | Leq | Geq | RangeInit(true) => 2,
+      Pop | Dump | Cast(_) | JumpIfNotTrue(_) | RangeInit(false) | Store(_) => 1,
+      _ => 0,
+    }
+  }
+}
+
+/// The blocks that are still waiting for their `end`, with the location of the instruction that
+/// must be patched once the end of the block is known
+#[derive(Debug, Clone)]
+enum Block {
+  If(usize),
+  Else(usize),
+  Proc(usize),
+  /// The start of the condition and, once `do` is reached, the conditional jump out of the loop
+  While(usize, Option<usize>),
+  /// The location of the check that leaves the loop and the name of the counter
+  Range(usize, Option<String>),
+}
+
+#[derive(Clone)]
 pub struct VMCodeGenerator {
-  branching_blocks: Vec<usize>,
-  instructions_count: usize,
+  blocks: Vec<Block>,
+  procedures: HashMap<String, usize>,
+  /// The variables in scope and their slots, the innermost declarations are the last ones
+  variables: Vec<(String, usize)>,
+  /// How many variables were in scope when each procedure or range body started
+  scopes: Vec<usize>,
+  /// Every declaration gets its own slot, so it is never shared with another variable
+  slots: usize,
   bytecode: Vec<ByteCode>,
+  /// The source span of the node that emitted each instruction
+  spans: Vec<(usize, usize)>,
+  /// The span of the node being generated
+  span: (usize, usize),
+  /// Written in the debug section of the output when known
+  source_code: Option<String>,
+  optimization_level: u8,
+  stack_size: Option<usize>,
 }
 
 impl VMCodeGenerator {
   pub fn new() -> Self {
     Self {
-      branching_blocks: vec![],
-      instructions_count: 0,
+      blocks: vec![],
+      procedures: HashMap::new(),
+      variables: vec![],
+      scopes: vec![],
+      slots: 0,
       bytecode: vec![],
+      spans: vec![],
+      span: (0, 0),
+      source_code: None,
+      optimization_level: 0,
+      stack_size: None,
     }
   }
 
-  pub fn generate_two_children_code(
+  pub fn with_options(options: CodeGeneratorOptions) -> Self {
+    Self {
+      source_code: options.source_code,
+      optimization_level: options.optimization_level,
+      stack_size: options.stack_size,
+      ..Self::new()
+    }
+  }
+
+  fn emit(&mut self, opcode: ByteCode) {
+    self.bytecode.push(opcode);
+    self.spans.push(self.span);
+  }
+
+  /// Location of the next instruction to be emitted
+  fn next_location(&self) -> usize {
+    self.bytecode.len()
+  }
+
+  /// The operands that are not children were pushed by an earlier input, like a previous line of
+  /// the REPL
+  pub fn generate_operator_code(
     &mut self,
-    left: &AstNode,
-    right: &AstNode,
+    operands: &[AstNode],
     opcode: ByteCode,
-  ) -> anyhow::Result<Vec<ByteCode>> {
-    let mut bytecode = self.generate_byte_code(left)?;
-    bytecode.append(&mut self.generate_byte_code(right)?);
-    bytecode.push(opcode);
-    self.instructions_count += 2;
-    Ok(bytecode)
+  ) -> anyhow::Result<()> {
+    for operand in operands {
+      self.generate_byte_code(operand)?;
+    }
+    self.emit(opcode);
+    Ok(())
+  }
+
+  /// How many range loops are between the innermost one and the one whose counter is `name`.
+  /// The counters of the loops outside of a procedure are not visible inside of it.
+  fn range_depth(&self, name: &str) -> Option<usize> {
+    self
+      .blocks
+      .iter()
+      .rev()
+      .take_while(|block| !matches!(block, Block::Proc(_)))
+      .filter_map(|block| match block {
+        Block::Range(_, counter) => Some(counter),
+        _ => None,
+      })
+      .position(|counter| counter.as_deref() == Some(name))
+  }
+
+  /// The slot of the innermost variable named `name`
+  fn variable_slot(&self, name: &str) -> Option<usize> {
+    self
+      .variables
+      .iter()
+      .rev()
+      .find(|(variable, _)| variable == name)
+      .map(|(_, slot)| *slot)
+  }
+
+  fn enter_scope(&mut self) {
+    self.scopes.push(self.variables.len());
+  }
+
+  fn exit_scope(&mut self) {
+    if let Some(len) = self.scopes.pop() {
+      self.variables.truncate(len);
+    }
+  }
+
+  pub fn bytecode(&self) -> &[ByteCode] {
+    &self.bytecode
+  }
+
+  /// Map the generated instructions back to the source they came from
+  pub fn debug_info(&self, source: &str) -> DebugInfo {
+    DebugInfo::new(source.to_string(), self.spans.clone())
+  }
+
+  pub fn generate_byte_code(&mut self, ast: &AstNode) -> anyhow::Result<()> {
+    // The instructions emitted for this node point at it, the parent's span is restored after
+    let parent_span = std::mem::replace(&mut self.span, ast.span);
+    let result = self.generate_node(ast);
+    self.span = parent_span;
+
+    result
   }
 
-  pub fn generate_byte_code(&mut self, ast: &AstNode) -> anyhow::Result<Vec<ByteCode>> {
+  fn generate_node(&mut self, ast: &AstNode) -> anyhow::Result<()> {
     match ast.token.clone() {
       Token::Program => {
         for child in ast.children.iter() {
-          self.instructions_count += 1;
-          let mut bytecode = self.generate_byte_code(child)?;
-
-          self.bytecode.append(&mut bytecode);
+          self.generate_byte_code(child)?;
         }
 
-        Ok(self.bytecode.clone())
+        if !self.blocks.is_empty() {
+          return Err(anyhow::anyhow!("Missing 'end'"));
+        }
       }
-      Token::Integer(value) => Ok(vec![ByteCode::PushInt(value)]),
-      Token::Float(value) => Ok(vec![ByteCode::PushFloat(value)]),
-      Token::String(value) => Ok(vec![ByteCode::PushStr(value)]),
+      Token::Integer(value) => self.emit(ByteCode::PushInt(value)),
+      Token::Float(value) => self.emit(ByteCode::PushFloat(value)),
+      Token::String(value) => self.emit(ByteCode::PushStr(value)),
+      Token::Boolean(value) => self.emit(ByteCode::PushBool(value)),
       Token::StackOps(operator) => match operator {
-        StackOperators::Dump => Ok(vec![ByteCode::Dump]),
-        StackOperators::Dup => Ok(vec![ByteCode::Dup]),
-        _ => Err(anyhow::anyhow!(
-          "Currently unsupported token: {:?}",
-          ast.token
-        )),
+        StackOperators::Dump => self.emit(ByteCode::Dump),
+        StackOperators::Dup => self.emit(ByteCode::Dup),
+        StackOperators::Drop => self.emit(ByteCode::Pop),
       },
       Token::ArithmeticOp(operator) => {
         use ArithmeticOperators::*;
@@ -108,12 +251,12 @@ impl VMCodeGenerator {
         .collect::<HashMap<_, _>>()
         .get(&operator)
         {
-          self.generate_two_children_code(&ast.children[0], &ast.children[1], opcode.clone())
+          self.generate_operator_code(&ast.children, opcode.clone())?
         } else {
-          Err(anyhow::anyhow!(
+          return Err(anyhow::anyhow!(
             "Currently unsupported token: {:?}",
             ast.token
-          ))
+          ));
         }
       }
       Token::ComparisonOp(operator) => {
@@ -132,78 +275,140 @@ impl VMCodeGenerator {
         .collect::<HashMap<_, _>>()
         .get(&operator)
         {
-          self.generate_two_children_code(&ast.children[0], &ast.children[1], opcode.clone())
+          self.generate_operator_code(&ast.children, opcode.clone())?
         } else {
-          Err(anyhow::anyhow!(
+          return Err(anyhow::anyhow!(
             "Currently unsupported token: {:?}",
             ast.token
-          ))
+          ));
         }
       }
-      Token::If => {
-        let mut bytecode = vec![];
-        let mut condition = self.generate_byte_code(&ast.children[0])?;
+      Token::CastOp => {
+        let type_ = ast
+          .cast_type()
+          .ok_or(anyhow::anyhow!("Cast without a type"))?;
 
-        self.branching_blocks.push(self.instructions_count);
-        bytecode.append(&mut condition);
-        bytecode.push(ByteCode::JumpIfNotTrue(usize::MAX)); // Placeholder position for now
-        self.instructions_count += 1;
+        self.emit(ByteCode::Cast(type_.clone()));
+      }
+      Token::If => {
+        if let Some(condition) = ast.children.first() {
+          self.generate_byte_code(condition)?;
+        }
 
-        Ok(bytecode)
+        self.blocks.push(Block::If(self.next_location()));
+        self.emit(ByteCode::JumpIfNotTrue(usize::MAX)); // Placeholder position for now
       }
       Token::Else => {
-        if let Some(if_intruction_location) = self.branching_blocks.pop() {
-          if let ByteCode::JumpIfNotTrue(_) = &mut self.bytecode[if_intruction_location] {
-            self.bytecode[if_intruction_location] =
-              ByteCode::JumpIfNotTrue(self.instructions_count);
-          }
+        if let Some(Block::If(if_intruction_location)) = self.blocks.pop() {
+          self.bytecode[if_intruction_location] = ByteCode::JumpIfNotTrue(self.next_location() + 1);
         } else {
           return Err(anyhow::anyhow!("Mismatched 'else'"));
         }
-        self.branching_blocks.push(self.instructions_count - 1);
 
-        Ok(vec![ByteCode::Jump(usize::MAX)])
+        self.blocks.push(Block::Else(self.next_location()));
+        self.emit(ByteCode::Jump(usize::MAX));
       }
-      Token::End => {
-        if let Some(branch_intruction_location) = self.branching_blocks.pop() {
-          match &mut self.bytecode[branch_intruction_location] {
-            ByteCode::JumpIfNotTrue(_) => {
-              self.bytecode[branch_intruction_location] =
-                ByteCode::JumpIfNotTrue(self.instructions_count);
-            }
-            ByteCode::Jump(_) => {
-              self.bytecode[branch_intruction_location] = ByteCode::Jump(self.instructions_count);
-            }
-            _ => {
-              return Err(anyhow::anyhow!("Mismatched 'end' (1)"));
-            }
-          }
-
-          Ok(vec![ByteCode::Ignore])
-        } else {
-          Err(anyhow::anyhow!("Mismatched 'end' (2)"))
-        }
+      Token::Proc => {
+        let name = ast
+          .proc_name()
+          .ok_or(anyhow::anyhow!("Procedure without a name"))?
+          .to_string();
+
+        // The body is only reachable through a call, so the normal flow jumps over it
+        self.blocks.push(Block::Proc(self.next_location()));
+        self.emit(ByteCode::Jump(usize::MAX));
+
+        self.procedures.insert(name, self.next_location());
+        self.enter_scope();
       }
-      _ => Err(anyhow::anyhow!(
-        "Currently unsupported token: {:?}",
-        ast.token
-      )),
-    }
-  }
+      Token::While => self.blocks.push(Block::While(self.next_location(), None)),
+      Token::Range => {
+        self.emit(ByteCode::RangeInit(ast.range_has_start()));
 
-  pub fn encode_byte_code(bytecode: Vec<ByteCode>, filename: String) -> anyhow::Result<()> {
-    let encoded: Vec<u8> = bincode::serialize(&bytecode).unwrap();
+        let counter = ast.range_counter().map(|name| name.to_string());
+        self.blocks.push(Block::Range(self.next_location(), counter));
+        self.emit(ByteCode::RangeCheck(usize::MAX)); // Placeholder position for now
+        self.enter_scope();
+      }
+      Token::Do => {
+        let location = self.next_location();
+
+        if let Some(Block::While(_, condition)) = self.blocks.last_mut() {
+          *condition = Some(location);
+          self.emit(ByteCode::JumpIfNotTrue(usize::MAX)); // Placeholder position for now
+        }
+      }
+      Token::DefType(_) => {
+        let name = ast
+          .variable_name()
+          .ok_or(anyhow::anyhow!("Declaration without a name"))?
+          .to_string();
 
-    use std::io::Write;
+        self.variables.push((name, self.slots));
+        self.slots += 1;
+      }
+      Token::AtSign => {
+        let name = ast
+          .variable_name()
+          .ok_or(anyhow::anyhow!("Assignment without a name"))?;
+        let slot = self
+          .variable_slot(name)
+          .ok_or(anyhow::anyhow!("Unknown variable: {}", name))?;
 
-    let mut file = File::create(format!("{filename}.bin"))
-      .map_err(|e| anyhow::anyhow!("Error creating file: {}", e))?;
-    file
-      .write_all(&encoded)
-      .map_err(|e| anyhow::anyhow!("Error writing to file: {}", e))?;
+        self.emit(ByteCode::Store(slot));
+      }
+      Token::Identifier(name) => {
+        if let Some(depth) = self.range_depth(&name) {
+          self.emit(ByteCode::RangeCounter(depth));
+        } else if let Some(slot) = self.variable_slot(&name) {
+          self.emit(ByteCode::Load(slot));
+        } else if let Some(location) = self.procedures.get(&name) {
+          self.emit(ByteCode::Call(*location));
+        } else {
+          return Err(anyhow::anyhow!("Unknown identifier: {}", name));
+        }
+      }
+      Token::End => match self.blocks.pop() {
+        Some(Block::If(branch_intruction_location)) => {
+          self.bytecode[branch_intruction_location] =
+            ByteCode::JumpIfNotTrue(self.next_location() + 1);
+          self.emit(ByteCode::Ignore);
+        }
+        Some(Block::Else(branch_intruction_location)) => {
+          self.bytecode[branch_intruction_location] = ByteCode::Jump(self.next_location() + 1);
+          self.emit(ByteCode::Ignore);
+        }
+        Some(Block::Proc(jump_location)) => {
+          self.emit(ByteCode::Ret);
+          self.bytecode[jump_location] = ByteCode::Jump(self.next_location());
+          self.exit_scope();
+        }
+        Some(Block::While(start, Some(condition))) => {
+          self.emit(ByteCode::Jump(start));
+          self.bytecode[condition] = ByteCode::JumpIfNotTrue(self.next_location());
+        }
+        Some(Block::While(_, None)) => return Err(anyhow::anyhow!("Missing 'do' in 'while'")),
+        Some(Block::Range(check, _)) => {
+          self.emit(ByteCode::RangeNext(check));
+          self.bytecode[check] = ByteCode::RangeCheck(self.next_location());
+          self.exit_scope();
+        }
+        None => return Err(anyhow::anyhow!("Mismatched 'end'")),
+      },
+      _ => {
+        return Err(anyhow::anyhow!(
+          "Currently unsupported token: {:?}",
+          ast.token
+        ))
+      }
+    }
 
     Ok(())
   }
+
+  pub fn encode_byte_code(container: &Container, filename: String) -> anyhow::Result<()> {
+    container.write(&format!("{filename}.bin"))
+  }
 }
 
 impl Default for VMCodeGenerator {
@@ -215,9 +420,17 @@ impl Default for VMCodeGenerator {
 impl CodeGenerator for VMCodeGenerator {
   fn generate(&mut self, ast: AstNode, filename: String) -> anyhow::Result<()> {
     let mut generator = VMCodeGenerator::new();
-    let bytecode = generator.generate_byte_code(&ast)?;
-    println!("{:?}", bytecode);
-    VMCodeGenerator::encode_byte_code(bytecode, filename)?;
+    generator.generate_byte_code(&ast)?;
+
+    let (bytecode, spans) = Optimizer::new(generator.bytecode, generator.spans)
+      .optimize(self.optimization_level);
+
+    let debug_info = self
+      .source_code
+      .as_deref()
+      .map(|source_code| DebugInfo::new(source_code.to_string(), spans));
+    let container = Container::new(bytecode, debug_info).with_stack_size(self.stack_size);
+    VMCodeGenerator::encode_byte_code(&container, filename)?;
 
     Ok(())
   }
@@ -0,0 +1,305 @@
+use crate::interpreter::vm::{arithmetic::overflow::OverflowMode, value::Value, VM};
+
+use super::{assembly::target, ByteCode};
+
+/// Rewrites the bytecode of a program, keeping the source span of every instruction.
+///
+/// - level 1 removes the `Ignore`s, the jumps to the next instruction and the unreachable code, and
+///   makes the jumps that land on another jump go straight to its target
+/// - level 2 and above also compute the operations whose operands are constants
+pub struct Optimizer {
+  bytecode: Vec<ByteCode>,
+  spans: Vec<(usize, usize)>,
+}
+
+impl Optimizer {
+  pub fn new(bytecode: Vec<ByteCode>, spans: Vec<(usize, usize)>) -> Self {
+    Self { bytecode, spans }
+  }
+
+  pub fn optimize(mut self, level: u8) -> (Vec<ByteCode>, Vec<(usize, usize)>) {
+    if level >= 2 {
+      while self.fold_constants() {}
+    }
+
+    if level >= 1 {
+      loop {
+        let length = self.bytecode.len();
+
+        self.thread_jumps();
+        self.remove_useless();
+
+        if self.bytecode.len() == length {
+          break;
+        }
+      }
+    }
+
+    (self.bytecode, self.spans)
+  }
+
+  /// The instructions that some jump, call or loop continues at
+  fn targets(&self) -> Vec<bool> {
+    let mut targets = vec![false; self.bytecode.len() + 1];
+    for target in self.bytecode.iter().filter_map(target) {
+      if let Some(is_target) = targets.get_mut(target) {
+        *is_target = true;
+      }
+    }
+
+    targets
+  }
+
+  /// Replace the first operation whose operands are all pushed right before it by the push of its
+  /// result. Returns whether something was folded.
+  fn fold_constants(&mut self) -> bool {
+    let targets = self.targets();
+
+    for end in 0..self.bytecode.len() {
+      let operation = &self.bytecode[end];
+      let is_pure = matches!(
+        operation,
+        ByteCode::Add
+          | ByteCode::Sub
+          | ByteCode::Mul
+          | ByteCode::Div
+          | ByteCode::Mod
+          | ByteCode::Eq
+          | ByteCode::Neq
+          | ByteCode::Lt
+          | ByteCode::Gt
+          | ByteCode::Leq
+          | ByteCode::Geq
+          | ByteCode::Cast(_)
+      );
+
+      let operands = operation.consumed_values();
+      if !is_pure || end < operands {
+        continue;
+      }
+      let start = end - operands;
+
+      // A jump into the middle would skip some of the pushes
+      let window = &self.bytecode[start..=end];
+      if targets[start + 1..=end].iter().any(|&is_target| is_target)
+        || !window[..operands].iter().all(is_push)
+      {
+        continue;
+      }
+
+      // Run it on the VM itself, an overflow or a division by zero is left for the runtime
+      let mut vm = VM::new().with_overflow(OverflowMode::Trapping);
+      if vm.execute(window).is_err() {
+        continue;
+      }
+
+      if let [value] = vm.stack() {
+        if let Some(push) = push_instruction(value) {
+          let span = self.spans[end];
+
+          self.bytecode.splice(start..=end, [push]);
+          self.spans.splice(start..=end, [span]);
+          self.remap_targets(|target| {
+            if target > end {
+              target - operands
+            } else {
+              target.min(start)
+            }
+          });
+
+          return true;
+        }
+      }
+    }
+
+    false
+  }
+
+  /// Jumps that land on an unconditional jump go to its target instead
+  fn thread_jumps(&mut self) {
+    for location in 0..self.bytecode.len() {
+      let final_target = |mut target: usize| {
+        // The chain is at most as long as the program, unless it is an endless loop
+        for _ in 0..self.bytecode.len() {
+          match self.bytecode.get(target) {
+            Some(ByteCode::Jump(next)) if *next != target => target = *next,
+            _ => break,
+          }
+        }
+
+        target
+      };
+
+      let threaded = match self.bytecode[location] {
+        ByteCode::Jump(target) => ByteCode::Jump(final_target(target)),
+        ByteCode::JumpIfNotTrue(target) => ByteCode::JumpIfNotTrue(final_target(target)),
+        ref instruction => instruction.clone(),
+      };
+      self.bytecode[location] = threaded;
+    }
+  }
+
+  /// Remove the `Ignore`s, the jumps to the next instruction and the code that can't be reached
+  fn remove_useless(&mut self) {
+    let reachable = self.reachable();
+
+    let keep = self
+      .bytecode
+      .iter()
+      .enumerate()
+      .map(|(location, instruction)| {
+        reachable[location]
+          && !matches!(instruction, ByteCode::Ignore)
+          && !matches!(instruction, ByteCode::Jump(target) if *target == location + 1)
+      })
+      .collect::<Vec<_>>();
+
+    // Where every old location ends up, a removed instruction is replaced by the next one kept
+    let mut new_locations = Vec::with_capacity(keep.len() + 1);
+    let mut kept = 0;
+    for &is_kept in keep.iter() {
+      new_locations.push(kept);
+      kept += is_kept as usize;
+    }
+    new_locations.push(kept);
+
+    let mut kept_instructions = keep.iter();
+    self.bytecode.retain(|_| *kept_instructions.next().unwrap());
+    let mut kept_spans = keep.iter();
+    self.spans.retain(|_| *kept_spans.next().unwrap());
+
+    self.remap_targets(|target| new_locations.get(target).copied().unwrap_or(kept));
+  }
+
+  /// Which instructions can run, starting from the first one
+  fn reachable(&self) -> Vec<bool> {
+    let mut reachable = vec![false; self.bytecode.len()];
+    let mut pending = vec![0];
+
+    while let Some(location) = pending.pop() {
+      if location >= self.bytecode.len() || reachable[location] {
+        continue;
+      }
+      reachable[location] = true;
+
+      let instruction = &self.bytecode[location];
+      pending.extend(target(instruction));
+
+      if !matches!(
+        instruction,
+        ByteCode::Jump(_) | ByteCode::Ret | ByteCode::RangeNext(_)
+      ) {
+        pending.push(location + 1);
+      }
+    }
+
+    reachable
+  }
+
+  fn remap_targets(&mut self, remap: impl Fn(usize) -> usize) {
+    for instruction in self.bytecode.iter_mut() {
+      match instruction {
+        ByteCode::JumpIfNotTrue(target)
+        | ByteCode::Jump(target)
+        | ByteCode::Call(target)
+        | ByteCode::RangeCheck(target)
+        | ByteCode::RangeNext(target) => *target = remap(*target),
+        _ => {}
+      }
+    }
+  }
+}
+
+fn is_push(instruction: &ByteCode) -> bool {
+  matches!(
+    instruction,
+    ByteCode::PushInt(_) | ByteCode::PushFloat(_) | ByteCode::PushStr(_) | ByteCode::PushBool(_)
+  )
+}
+
+/// Only some types of values can be pushed by an instruction
+fn push_instruction(value: &Value) -> Option<ByteCode> {
+  match value {
+    Value::Int(value) => Some(ByteCode::PushInt(*value)),
+    Value::Float32(value) => Some(ByteCode::PushFloat(*value)),
+    Value::Bool(value) => Some(ByteCode::PushBool(*value)),
+    Value::Str(value) => Some(ByteCode::PushStr(value.clone())),
+    Value::Int64(_) | Value::Float64(_) => None,
+  }
+}
+
+#[cfg(test)]
+mod optimizer_tests {
+  use super::*;
+
+  fn optimize(bytecode: Vec<ByteCode>, level: u8) -> String {
+    let spans = vec![(0, 0); bytecode.len()];
+    let (bytecode, _) = Optimizer::new(bytecode, spans).optimize(level);
+
+    format!("{:?}", bytecode)
+  }
+
+  #[test]
+  fn test_fold_constants() {
+    use ByteCode::*;
+
+    assert_eq!(
+      optimize(vec![PushInt(1), PushInt(2), Add, PushInt(3), Mul, Dump], 2),
+      format!("{:?}", vec![PushInt(9), Dump])
+    );
+
+    // Left for the runtime error
+    assert_eq!(
+      optimize(vec![PushInt(1), PushInt(0), Div, Dump], 2),
+      format!("{:?}", vec![PushInt(1), PushInt(0), Div, Dump])
+    );
+
+    // Mixed numbers are compared like at runtime, promoted to the widest type
+    assert_eq!(
+      optimize(vec![PushInt(1), PushFloat(1.0), Eq, Dump], 2),
+      format!("{:?}", vec![PushBool(true), Dump])
+    );
+    assert_eq!(
+      optimize(vec![PushInt(2), PushFloat(1.5), Gt, Dump], 2),
+      format!("{:?}", vec![PushBool(true), Dump])
+    );
+  }
+
+  #[test]
+  fn test_remove_and_remap() {
+    use ByteCode::*;
+
+    // if 1 2 < do 3 dump else 4 dump end
+    let bytecode = vec![
+      PushInt(1),
+      PushInt(2),
+      Lt,
+      JumpIfNotTrue(7),
+      PushInt(3),
+      Dump,
+      Jump(10),
+      PushInt(4),
+      Dump,
+      Ignore,
+      Ignore,
+    ];
+
+    assert_eq!(
+      optimize(bytecode, 1),
+      format!(
+        "{:?}",
+        vec![
+          PushInt(1),
+          PushInt(2),
+          Lt,
+          JumpIfNotTrue(7),
+          PushInt(3),
+          Dump,
+          Jump(9),
+          PushInt(4),
+          Dump
+        ]
+      )
+    );
+  }
+}
@@ -1,23 +1,241 @@
-use crate::parser::parse::AstNode;
+use std::fs::File;
 
-use super::CodeGenerator;
+use crate::{
+  lexer::tokens::{ArithmeticOperators, ComparisonOperators, StackOperators, Token},
+  parser::parse::AstNode,
+};
 
-pub struct WasmCodeGenerator {}
+use super::{CodeGenerator, CodeGeneratorOptions};
+
+pub mod module;
+
+/// The kind of structured block that is currently open
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WasmBlock {
+  If,
+  Else,
+}
+
+pub struct WasmCodeGenerator {
+  emit_text: bool,
+  blocks: Vec<WasmBlock>,
+  instructions: Vec<String>,
+}
 
 impl WasmCodeGenerator {
-  pub fn new() -> Self {
-    Self {}
+  pub fn new(options: CodeGeneratorOptions) -> Self {
+    Self {
+      emit_text: options.emit_text,
+      blocks: vec![],
+      instructions: vec![],
+    }
+  }
+
+  fn emit(&mut self, instruction: &str) {
+    let indentation = "  ".repeat(self.blocks.len());
+    self.instructions.push(format!("{indentation}{instruction}"));
+  }
+
+  /// Pop the two operands into the `$lhs` and `$rhs` locals and push them back into the wasm
+  /// operand stack, ready for a binary instruction
+  fn emit_binary_operands(&mut self) {
+    self.emit("call $pop");
+    self.emit("local.set $rhs");
+    self.emit("call $pop");
+    self.emit("local.set $lhs");
+    self.emit("local.get $lhs");
+    self.emit("local.get $rhs");
+  }
+
+  fn generate_two_children_code(
+    &mut self,
+    left: &AstNode,
+    right: &AstNode,
+    instruction: &str,
+  ) -> anyhow::Result<()> {
+    self.generate_instructions(left)?;
+    self.generate_instructions(right)?;
+
+    self.emit_binary_operands();
+    self.emit(instruction);
+    self.emit("call $push");
+
+    Ok(())
+  }
+
+  pub fn generate_instructions(&mut self, ast: &AstNode) -> anyhow::Result<()> {
+    match ast.token.clone() {
+      Token::Program => {
+        for child in ast.children.iter() {
+          self.generate_instructions(child)?;
+        }
+
+        if !self.blocks.is_empty() {
+          return Err(anyhow::anyhow!("Missing 'end' for an 'if' block"));
+        }
+      }
+      Token::Integer(value) => {
+        self.emit(&format!("i32.const {value}"));
+        self.emit("call $push");
+      }
+      Token::Boolean(value) => {
+        self.emit(&format!("i32.const {}", value as i32));
+        self.emit("call $push");
+      }
+      Token::StackOps(operator) => match operator {
+        StackOperators::Dump => {
+          self.emit("call $pop");
+          self.emit("call $dump");
+        }
+        StackOperators::Dup => {
+          self.emit("call $pop");
+          self.emit("local.tee $lhs");
+          self.emit("call $push");
+          self.emit("local.get $lhs");
+          self.emit("call $push");
+        }
+        StackOperators::Drop => {
+          self.emit("call $pop");
+          self.emit("drop");
+        }
+      },
+      Token::ArithmeticOp(operator) => {
+        let instruction = match operator {
+          ArithmeticOperators::Plus => "i32.add",
+          ArithmeticOperators::Minus => "i32.sub",
+          ArithmeticOperators::Times => "i32.mul",
+          ArithmeticOperators::Divide => "i32.div_s",
+          ArithmeticOperators::Modulo => "i32.rem_s",
+        };
+
+        self.generate_two_children_code(&ast.children[0], &ast.children[1], instruction)?;
+      }
+      Token::ComparisonOp(operator) => {
+        let instruction = match operator {
+          ComparisonOperators::EqualTo => "i32.eq",
+          ComparisonOperators::NotEqualTo => "i32.ne",
+          ComparisonOperators::LessThan => "i32.lt_s",
+          ComparisonOperators::LessThanOrEqualTo => "i32.le_s",
+          ComparisonOperators::GreaterThan => "i32.gt_s",
+          ComparisonOperators::GreaterThanOrEqualTo => "i32.ge_s",
+        };
+
+        self.generate_two_children_code(&ast.children[0], &ast.children[1], instruction)?;
+      }
+      Token::If => {
+        self.generate_instructions(&ast.children[0])?;
+
+        self.emit("call $pop");
+        self.emit("if");
+        self.blocks.push(WasmBlock::If);
+      }
+      Token::Else => match self.blocks.pop() {
+        Some(WasmBlock::If) => {
+          self.emit("else");
+          self.blocks.push(WasmBlock::Else);
+        }
+        _ => return Err(anyhow::anyhow!("Mismatched 'else'")),
+      },
+      Token::End => match self.blocks.pop() {
+        Some(_) => self.emit("end"),
+        None => return Err(anyhow::anyhow!("Mismatched 'end'")),
+      },
+      _ => {
+        return Err(anyhow::anyhow!(
+          "Currently unsupported token for the wasm target: {:?}",
+          ast.token
+        ))
+      }
+    }
+
+    Ok(())
+  }
+
+  pub fn write_module(&self, filename: &str) -> anyhow::Result<()> {
+    use std::io::Write;
+
+    let text = module::build_module(&self.instructions);
+    let binary =
+      wat::parse_str(&text).map_err(|e| anyhow::anyhow!("Error assembling wasm module: {}", e))?;
+
+    let mut file = File::create(format!("{filename}.wasm"))
+      .map_err(|e| anyhow::anyhow!("Error creating file: {}", e))?;
+    file
+      .write_all(&binary)
+      .map_err(|e| anyhow::anyhow!("Error writing to file: {}", e))?;
+
+    if self.emit_text {
+      let mut file = File::create(format!("{filename}.wat"))
+        .map_err(|e| anyhow::anyhow!("Error creating file: {}", e))?;
+      file
+        .write_all(text.as_bytes())
+        .map_err(|e| anyhow::anyhow!("Error writing to file: {}", e))?;
+    }
+
+    Ok(())
   }
 }
 
 impl Default for WasmCodeGenerator {
   fn default() -> Self {
-    Self::new()
+    Self::new(CodeGeneratorOptions::default())
   }
 }
 
 impl CodeGenerator for WasmCodeGenerator {
-  fn generate(&mut self, _ast: AstNode, _filename: String) -> anyhow::Result<()> {
+  fn generate(&mut self, ast: AstNode, filename: String) -> anyhow::Result<()> {
+    self.generate_instructions(&ast)?;
+    self.write_module(&filename)?;
+
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod wasm_tests {
+  use super::*;
+  use crate::frontend::Program;
+
+  /// Run a program like a host would: the dumped values and the exit code of `main`
+  fn run(source: &str) -> (Vec<i32>, i32) {
+    let program = Program::from_source(source.to_string(), None).unwrap();
+
+    let mut generator = WasmCodeGenerator::default();
+    generator.generate_instructions(&program.ast).unwrap();
+    let binary = wat::parse_str(module::build_module(&generator.instructions)).unwrap();
+
+    let engine = wasmi::Engine::default();
+    let module = wasmi::Module::new(&engine, &binary[..]).unwrap();
+    let mut store = wasmi::Store::new(&engine, Vec::new());
+
+    let mut linker = wasmi::Linker::new(&engine);
+    linker
+      .func_wrap(
+        module::HOST_MODULE_NAME,
+        module::DUMP_FUNCTION_NAME,
+        |mut caller: wasmi::Caller<'_, Vec<i32>>, value: i32| caller.data_mut().push(value),
+      )
+      .unwrap();
+
+    let instance = linker
+      .instantiate(&mut store, &module)
+      .unwrap()
+      .start(&mut store)
+      .unwrap();
+    let main = instance
+      .get_typed_func::<(), i32>(&store, module::MAIN_FUNCTION_NAME)
+      .unwrap();
+    let code = main.call(&mut store, ()).unwrap();
+
+    (store.into_data(), code)
+  }
+
+  #[test]
+  fn test_main_returns_the_top_of_the_stack() {
+    let sample = std::fs::read_to_string("assets/lang/conditional_and_branching.pile").unwrap();
+    assert_eq!(run(&sample), (vec![2, 3], 0));
+
+    assert_eq!(run("1 2 + dup dump 4 *\n"), (vec![3], 12));
+    assert_eq!(run("7 2 % dump 7 2 / dump\n"), (vec![1, 3], 0));
+  }
+}
@@ -0,0 +1,63 @@
+// The fixed part of every generated module: the host imports, the linear memory used as the
+// operand stack and the push/pop helpers that guard it
+
+pub const HOST_MODULE_NAME: &str = "env";
+pub const DUMP_FUNCTION_NAME: &str = "dump";
+pub const MAIN_FUNCTION_NAME: &str = "main";
+
+/// One page of linear memory (64KiB) is reserved for the operand stack
+pub const STACK_SIZE_IN_BYTES: u32 = 64 * 1024;
+
+/// Every slot of the operand stack is an i32
+pub const STACK_SLOT_SIZE: u32 = 4;
+
+/// Wrap the instructions of the main function with the module prelude
+pub fn build_module(main_body: &[String]) -> String {
+  let mut module = String::new();
+
+  module.push_str("(module\n");
+  module.push_str(&format!(
+    "  (import \"{HOST_MODULE_NAME}\" \"{DUMP_FUNCTION_NAME}\" (func $dump (param i32)))\n\n"
+  ));
+  module.push_str("  (memory (export \"memory\") 1)\n");
+  module.push_str("  (global $sp (mut i32) (i32.const 0))\n\n");
+
+  // push: traps if the stack is already full
+  module.push_str("  (func $push (param $value i32)\n");
+  module.push_str(&format!(
+    "    (if (i32.ge_u (global.get $sp) (i32.const {STACK_SIZE_IN_BYTES}))\n"
+  ));
+  module.push_str("      (then unreachable))\n");
+  module.push_str("    (i32.store (global.get $sp) (local.get $value))\n");
+  module.push_str(&format!(
+    "    (global.set $sp (i32.add (global.get $sp) (i32.const {STACK_SLOT_SIZE}))))\n\n"
+  ));
+
+  // pop: traps if the stack is empty
+  module.push_str("  (func $pop (result i32)\n");
+  module.push_str("    (if (i32.eqz (global.get $sp))\n");
+  module.push_str("      (then unreachable))\n");
+  module.push_str(&format!(
+    "    (global.set $sp (i32.sub (global.get $sp) (i32.const {STACK_SLOT_SIZE})))\n"
+  ));
+  module.push_str("    (i32.load (global.get $sp)))\n\n");
+
+  // main: returns the top of the stack, or 0 when the program left it empty, just like the LLVM
+  // backend
+  module.push_str(&format!(
+    "  (func ${MAIN_FUNCTION_NAME} (export \"{MAIN_FUNCTION_NAME}\") (result i32)\n"
+  ));
+  module.push_str("    (local $lhs i32)\n");
+  module.push_str("    (local $rhs i32)\n");
+
+  for instruction in main_body {
+    module.push_str(&format!("    {instruction}\n"));
+  }
+
+  module.push_str("    (if (result i32) (i32.eqz (global.get $sp))\n");
+  module.push_str("      (then (i32.const 0))\n");
+  module.push_str("      (else (call $pop))))\n");
+  module.push_str(")\n");
+
+  module
+}
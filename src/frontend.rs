@@ -0,0 +1,69 @@
+//! The stages every program goes through before a backend sees it: the lexer, the pragmas, the
+//! parser and the semantic analysis
+
+use miette::Result as MietteResult;
+
+use crate::{
+  lexer::{self, pragma::Pragmas, PileToken},
+  parser::{embedded, parse::AstNode, SLR::SLR},
+  semantic::SemanticAnalyzer,
+};
+
+/// A source that went through the front end
+pub struct Program {
+  pub source: String,
+  pub pragmas: Pragmas,
+  pub ast: AstNode,
+}
+
+impl Program {
+  /// Read and check a source file, parsed with the grammar in `grammar_file` (`--grammar`) or
+  /// with the embedded one
+  pub fn load(filename: &str, grammar_file: Option<&str>) -> MietteResult<Program> {
+    let source = std::fs::read_to_string(filename)
+      .map_err(|e| miette::miette!("Could not read {}: {}", filename, e))?;
+
+    Program::from_source(source, grammar_file)
+  }
+
+  pub fn from_source(source: String, grammar_file: Option<&str>) -> MietteResult<Program> {
+    let parser = embedded::parser(grammar_file)?;
+    let mut analyzer = SemanticAnalyzer::new(source.clone());
+
+    let ast = check(&parser, &mut analyzer, &source, 0)?;
+    let pragmas = lexer::pragma::parse(&source)?;
+
+    Ok(Program {
+      source,
+      pragmas,
+      ast,
+    })
+  }
+}
+
+/// Lex, parse and analyze the end of `source` that starts at `offset`. The spans point into the
+/// whole source and the analyzer keeps what it learned, so the REPL checks every line on top of
+/// the previous ones.
+pub fn check(
+  parser: &SLR,
+  analyzer: &mut SemanticAnalyzer,
+  source: &str,
+  offset: usize,
+) -> MietteResult<AstNode> {
+  let tokens = lexer::generate::compute_tokens(&source[offset..])?
+    .into_iter()
+    .map(|token| PileToken {
+      span: token.span.start + offset..token.span.end + offset,
+      ..token
+    })
+    .collect();
+
+  let ast = parser
+    .parse(tokens, source)?
+    .ok_or(miette::miette!("Failed to parse"))?;
+
+  analyzer.set_source(source.to_string());
+  analyzer.analyze(&ast)?;
+
+  Ok(ast)
+}
@@ -1,168 +1,133 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 
 use super::{Grammar, Symbol};
 
 impl Grammar {
+  /// Compute the FIRST set of every non terminal, then its FOLLOW set. Both grow until no
+  /// production adds anything to them.
   pub fn compute_follow_set(&mut self) -> &mut Self {
+    self.compute_first_set();
+
     // 1. Add $ to the follow set of the start symbol
     self
       .follow_set
-      .insert(self.start_symbol(), vec![Symbol::End].into_iter().collect());
+      .insert(self.start_symbol(), HashSet::from([Symbol::End]));
+
+    let mut changed = true;
+    while changed {
+      changed = false;
 
-    let mut last_follow_set = HashMap::new();
-    while last_follow_set != self.follow_set {
-      last_follow_set = self.follow_set.clone();
+      for (lhs, rhs) in self.productions.clone() {
+        for (position, symbol) in rhs.iter().enumerate() {
+          if !symbol.is_non_terminal() {
+            continue;
+          }
 
-      // Compute follow set for each production
-      for (lhs, _) in self.productions.clone() {
-        self.follow_set_for_production(lhs.clone());
+          // 2. What can start the rest of the production follows the symbol
+          let rest = self.first_of_sequence(&rhs[position + 1..]);
+          let mut follow = rest
+            .iter()
+            .filter(|s| !s.is_empty())
+            .cloned()
+            .collect::<HashSet<_>>();
+
+          // 3. When the rest can be empty, what follows the lhs follows the symbol too
+          if rest.contains(&Symbol::Empty) {
+            follow.extend(self.follow_set.get(&lhs).cloned().unwrap_or_default());
+          }
+
+          let follow_set = self.follow_set.entry(symbol.clone()).or_default();
+          let size = follow_set.len();
+          follow_set.extend(follow);
+          changed |= follow_set.len() != size;
+        }
       }
     }
 
     self
   }
 
-  pub fn follow_set_for_production(&mut self, lhs: Symbol) {
-    // Filter out the lhs
-    let binding = self.productions.clone();
-    let all_productions_but_the_current = binding.iter().filter(|(_lhs, _)| lhs != *_lhs);
+  fn compute_first_set(&mut self) {
+    let mut changed = true;
+    while changed {
+      changed = false;
 
-    for (lhs, rhs) in all_productions_but_the_current {
-      for symbol in rhs.iter() {
-        // If the symbol is not a non-terminal, skip it ✅
-        if !symbol.is_non_terminal() {
-          continue;
-        }
-
-        // Find the symbol next to the current symbol ✅
-        let next_symbol = rhs.iter().skip_while(|s| *s != symbol).nth(1);
+      for (lhs, rhs) in self.productions.clone() {
+        let first = self.first_of_sequence(&rhs);
 
-        // 1. If the symbol is the last symbol in the production, add the follow set of the lhs to the follow set of the symbol ✅
-        if next_symbol.is_none() {
-          // Add the follow set of the production to the follow set of the next_symbol
-          let binding = self.follow_set.clone();
-          let production_follow_set = binding.get(lhs);
-          if production_follow_set.is_none() {
-            continue;
-          }
-
-          self
-            .follow_set
-            .entry(symbol.clone())
-            .or_insert(HashSet::new())
-            .extend(production_follow_set.unwrap().clone());
-
-          continue;
-        }
-
-        let next_symbol = next_symbol.unwrap();
-
-        match next_symbol {
-          Symbol::Terminal(_) => {
-            // 2. If the next_symbol is a terminal, add it to the follow set of the symbol ✅
-            self
-              .follow_set
-              .entry(symbol.clone())
-              .or_insert(HashSet::new())
-              .insert(next_symbol.clone());
-          }
-          Symbol::NonTerminal(_) => {
-            // If the first set of the next symbol is not computed yet, then compute it
-            if !self.first_set.contains_key(next_symbol) {
-              self.first_set_for_symbol(next_symbol.clone());
-            }
-
-            // If the first set of the next symbol contains the empty symbol and the next symbol is the
-            // last, then add the follow set of the production to the follow set of the symbol
-            if self
-              .first_set
-              .get(next_symbol)
-              .unwrap()
-              .contains(&Symbol::Empty)
-              && rhs.iter().last().unwrap() == symbol
-            {
-              // Add the follow set of the production to the follow set of the next_symbol
-              self
-                .follow_set
-                .entry(symbol.clone())
-                .or_insert(HashSet::new())
-                .insert(next_symbol.clone());
-            }
-
-            // Add the first set of the next symbol to the follow set of the symbol, except the empty
-            // symbol
-            self
-              .first_set
-              .get(next_symbol)
-              .unwrap()
-              .iter()
-              .filter(|s| **s != Symbol::Empty)
-              .for_each(|s| {
-                self
-                  .follow_set
-                  .entry(symbol.clone())
-                  .or_insert(HashSet::new())
-                  .insert(s.clone());
-              });
-          }
-          _ => {
-            panic!("Unexpected symbol: {:?}", next_symbol);
-          }
-        }
+        let first_set = self.first_set.entry(lhs).or_default();
+        let size = first_set.len();
+        first_set.extend(first);
+        changed |= first_set.len() != size;
       }
     }
   }
 
-  fn first_set_for_symbol(&mut self, lhs: Symbol) {
-    // Filter out the lhs
-    let binding = self.productions.clone();
-    let all_productions_but_the_current = binding.iter().filter(|(_lhs, _)| lhs != *_lhs);
-
-    for (lhs, rhs) in all_productions_but_the_current {
-      for symbol in rhs.iter() {
-        match symbol {
-          Symbol::Terminal(_) | Symbol::Empty => {
-            // 2. If the next symbol is a terminal, add it to the follow set of the symbol ✅
-            self
-              .first_set
-              .entry(lhs.clone())
-              .or_insert(HashSet::new())
-              .insert(symbol.clone());
-          }
-          Symbol::NonTerminal(_) => {
-            // If the first set of the production that produces the symbol is not computed yet, then
-            // compute it
-            if !self.first_set.contains_key(symbol) {
-              self.first_set_for_symbol(lhs.clone());
-            }
-
-            let binding = self.first_set.clone();
-            let first_set = binding.get(symbol).unwrap();
-
-            // Add the first set of the symbol to the first set of the production
-            first_set.iter().for_each(|s| {
-              self
-                .first_set
-                .entry(lhs.clone())
-                .or_insert(HashSet::new())
-                .insert(s.clone());
-            });
-
-            // If the first set of the symbol contains the empty symbol, then continue to the next
-            // symbol
-            if first_set.contains(&Symbol::Empty) {
-              continue;
-            }
-
-            // If the first set of the production that produces the symbol does not contain the empty
-            // symbol, then stop
-            break;
-          }
-          _ => {
-            panic!("Unexpected symbol: {:?}", symbol);
+  /// The terminals that can start a sequence of symbols, with ε when the whole sequence can be
+  /// empty
+  pub fn first_of_sequence(&self, symbols: &[Symbol]) -> HashSet<Symbol> {
+    let mut first = HashSet::new();
+
+    for symbol in symbols {
+      match symbol {
+        Symbol::Empty => continue,
+        Symbol::NonTerminal(_) => {
+          let symbol_first = self.first_set.get(symbol).cloned().unwrap_or_default();
+          let can_be_empty = symbol_first.contains(&Symbol::Empty);
+
+          first.extend(symbol_first.into_iter().filter(|s| !s.is_empty()));
+          if !can_be_empty {
+            return first;
           }
         }
+        // The terminals (and $) start the sequence themselves
+        _ => {
+          first.insert(symbol.clone());
+          return first;
+        }
       }
     }
+
+    first.insert(Symbol::Empty);
+    first
+  }
+}
+
+#[cfg(test)]
+mod follow_tests {
+  use std::collections::HashSet;
+
+  use crate::grammar::{parser::parse, Symbol};
+
+  #[test]
+  fn test_follow_set() {
+    let mut grammar = parse("<E> -> <E> plus <T> | <T>; <T> -> id <O>; <O> -> bang | ε;").unwrap();
+    grammar.compute_follow_set();
+
+    let terminals = |names: &[&str]| {
+      names
+        .iter()
+        .map(|name| match *name {
+          "$" => Symbol::End,
+          "ε" => Symbol::Empty,
+          name => Symbol::Terminal(name.to_string()),
+        })
+        .collect::<HashSet<_>>()
+    };
+    let non_terminal = |name: &str| Symbol::NonTerminal(name.to_string());
+
+    assert_eq!(grammar.first_set[&non_terminal("E")], terminals(&["id"]));
+    assert_eq!(
+      grammar.first_set[&non_terminal("O")],
+      terminals(&["bang", "ε"])
+    );
+    assert_eq!(
+      grammar.follow_set[&non_terminal("E")],
+      terminals(&["plus", "$"])
+    );
+    assert_eq!(
+      grammar.follow_set[&non_terminal("O")],
+      terminals(&["plus", "$"])
+    );
   }
 }
@@ -3,13 +3,18 @@ use std::{
   fmt::Display,
 };
 
+use serde::{Deserialize, Serialize};
+
+use self::precedence::Precedence;
+
 pub mod expand;
 pub mod follow;
 pub mod parser;
+pub mod precedence;
 pub mod production;
 pub mod tokens;
 
-#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub enum Symbol {
   Terminal(String),
   NonTerminal(String),
@@ -62,11 +67,17 @@ impl Display for Symbol {
   }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Grammar {
   pub productions: Vec<(Symbol, Vec<Symbol>)>,
   pub follow_set: HashMap<Symbol, HashSet<Symbol>>,
   pub first_set: HashMap<Symbol, HashSet<Symbol>>,
+  /// The precedence of the terminals, used to settle shift/reduce conflicts
+  pub precedence: HashMap<Symbol, Precedence>,
+  /// The terminal whose precedence a production takes with `%prec`
+  pub prec_overrides: HashMap<(Symbol, Vec<Symbol>), Symbol>,
+  /// The kind of parsing tables declared with `%tables`
+  pub tables: Option<String>,
 }
 
 impl Display for Grammar {
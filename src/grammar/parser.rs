@@ -2,7 +2,11 @@ use std::collections::HashMap;
 
 use logos::{Lexer, Logos};
 
-use super::{tokens::GLCTokens, Grammar, Symbol};
+use super::{
+  precedence::{Associativity, Precedence},
+  tokens::GLCTokens,
+  Grammar, Symbol,
+};
 
 pub fn remove_angle_brackets(s: &str) -> String {
   s.replace(['<', '>'], "")
@@ -13,13 +17,39 @@ impl TryFrom<&mut Lexer<'_, GLCTokens>> for Grammar {
 
   fn try_from(value: &mut Lexer<GLCTokens>) -> Result<Self, Self::Error> {
     let mut productions = Vec::new();
+    let mut precedence = HashMap::new();
+    let mut prec_overrides = HashMap::new();
 
     let mut current_lhs: Option<Symbol> = None;
     let mut current_rhs: Vec<Symbol> = Vec::new();
 
+    // The `%left`, `%right` or `%nonassoc` declaration being read
+    let mut current_precedence: Option<Precedence> = None;
+    let mut levels = 0;
+
+    // The `%prec` terminal of the current production
+    let mut current_prec: Option<Symbol> = None;
+    let mut expects_prec = false;
+
+    // The kind of the `%tables` declaration being read
+    let mut tables: Option<String> = None;
+    let mut in_tables = false;
+
     while let (Some(token), slice, _span) = (value.next(), value.slice(), value.span()) {
+      if expects_prec && token != GLCTokens::Terminal {
+        return Err("%prec must be followed by a terminal symbol");
+      }
+
       match token {
         GLCTokens::NonTerminal => {
+          if in_tables {
+            return Err("%tables must be followed by slr, lalr or lr1");
+          }
+
+          if current_precedence.is_some() {
+            return Err("Only terminal symbols can be given a precedence");
+          }
+
           if current_lhs.is_some() {
             current_rhs.push(Symbol::NonTerminal(remove_angle_brackets(slice)));
             continue;
@@ -28,10 +58,30 @@ impl TryFrom<&mut Lexer<'_, GLCTokens>> for Grammar {
           current_lhs = Some(Symbol::NonTerminal(remove_angle_brackets(slice)));
         }
         GLCTokens::Terminal => {
+          if in_tables {
+            if tables.is_some() {
+              return Err("%tables takes a single kind of tables");
+            }
+
+            tables = Some(slice.to_string());
+            continue;
+          }
+
+          if let Some(declared) = current_precedence {
+            precedence.insert(Symbol::Terminal(slice.to_string()), declared);
+            continue;
+          }
+
           if current_lhs.is_none() {
             return Err("Terminal symbols can only be defined after a non terminal symbol");
           }
 
+          if expects_prec {
+            current_prec = Some(Symbol::Terminal(slice.to_string()));
+            expects_prec = false;
+            continue;
+          }
+
           current_rhs.push(Symbol::Terminal(slice.to_string()));
         }
         GLCTokens::Epsilon => {
@@ -51,32 +101,100 @@ impl TryFrom<&mut Lexer<'_, GLCTokens>> for Grammar {
             return Err("Pipe can only be defined after a non terminal symbol");
           }
 
-          productions.push((current_lhs.clone().unwrap(), current_rhs));
+          let production = (current_lhs.clone().unwrap(), current_rhs);
+          if let Some(terminal) = current_prec.take() {
+            prec_overrides.insert(production.clone(), terminal);
+          }
+
+          productions.push(production);
           current_rhs = Vec::new();
         }
         GLCTokens::EndOfProduction => {
+          if current_precedence.take().is_some() {
+            continue;
+          }
+
+          if in_tables {
+            if tables.is_none() {
+              return Err("%tables must be followed by slr, lalr or lr1");
+            }
+
+            in_tables = false;
+            continue;
+          }
+
           if current_lhs.is_none() {
             return Err("End of production can only be defined after a non terminal symbol");
           }
 
-          productions.push((current_lhs.clone().unwrap(), current_rhs));
+          let production = (current_lhs.clone().unwrap(), current_rhs);
+          if let Some(terminal) = current_prec.take() {
+            prec_overrides.insert(production.clone(), terminal);
+          }
+
+          productions.push(production);
           current_lhs = None;
           current_rhs = Vec::new();
         }
+        GLCTokens::Left | GLCTokens::Right | GLCTokens::NonAssoc => {
+          if current_lhs.is_some() || current_precedence.is_some() {
+            return Err("Precedence can only be declared between productions");
+          }
+
+          levels += 1;
+          current_precedence = Some(Precedence {
+            level: levels,
+            associativity: match token {
+              GLCTokens::Left => Associativity::Left,
+              GLCTokens::Right => Associativity::Right,
+              _ => Associativity::NonAssoc,
+            },
+          });
+        }
+        GLCTokens::Tables => {
+          if current_lhs.is_some() || current_precedence.is_some() || in_tables {
+            return Err("%tables can only be declared between productions");
+          }
+
+          if tables.is_some() {
+            return Err("%tables can only be declared once");
+          }
+
+          in_tables = true;
+        }
+        GLCTokens::Prec => {
+          if current_lhs.is_none() || current_prec.is_some() {
+            return Err("%prec can only be used once in a production");
+          }
+
+          expects_prec = true;
+        }
         _ => {
           return Err("Unexpected token");
         }
       }
     }
 
+    if expects_prec {
+      return Err("%prec must be followed by a terminal symbol");
+    }
+
     if let Some(current_non_terminal) = current_lhs {
-      productions.push((current_non_terminal, current_rhs));
+      let production = (current_non_terminal, current_rhs);
+      if let Some(terminal) = current_prec {
+        prec_overrides.insert(production.clone(), terminal);
+      }
+
+      productions.push(production);
     }
 
     Ok(Self {
       productions,
       first_set: HashMap::new(),
       follow_set: HashMap::new(),
+      precedence,
+      prec_overrides,
+      tables,
     })
   }
 }
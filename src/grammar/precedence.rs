@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+use super::{production::Production, Grammar};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Associativity {
+  Left,
+  Right,
+  NonAssoc,
+}
+
+/// Declared with `%left`, `%right` or `%nonassoc`, the later declarations bind tighter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Precedence {
+  pub level: usize,
+  pub associativity: Associativity,
+}
+
+impl Grammar {
+  /// The precedence of the `%prec` terminal of a production, else of its last terminal
+  pub fn production_precedence(&self, production: &Production) -> Option<Precedence> {
+    let key = (production.lhs.clone(), production.rhs.clone());
+
+    let terminal = match self.prec_overrides.get(&key) {
+      Some(terminal) => terminal,
+      None => production
+        .rhs
+        .iter()
+        .rev()
+        .find(|symbol| symbol.is_terminal())?,
+    };
+
+    self.precedence.get(terminal).copied()
+  }
+}
+
+#[cfg(test)]
+mod precedence_tests {
+  use crate::{
+    grammar::Symbol,
+    parser::{Action, SLR::TableKind, embedded::from_grammar},
+  };
+
+  #[test]
+  fn test_precedence_settles_conflicts() {
+    let expressions = "<E> -> <E> plus <E> | <E> times <E> | minus <E> %prec negate | id;";
+    assert_eq!(
+      from_grammar(expressions, Some(TableKind::Slr))
+        .unwrap()
+        .conflicts
+        .len(),
+      6
+    );
+
+    let glc = format!("%left plus; %left times; %right negate; {expressions}");
+    let slr = from_grammar(&glc, Some(TableKind::Slr)).unwrap();
+    assert!(slr.conflicts.is_empty());
+
+    // After `<E> plus <E>`, `plus` reduces (left associative) and `times` shifts (tighter)
+    let state = slr
+      .states
+      .iter()
+      .find(|state| {
+        state.productions.iter().any(|item| {
+          item.rhs.len() == 4 && item.rhs[1].get_name() == "plus" && item.rhs[3].is_dot()
+        })
+      })
+      .unwrap();
+    let action = |terminal: &str| &slr.action_table[&(state.id, Symbol::Terminal(terminal.into()))];
+
+    assert_eq!(action("plus"), &Action::Reduce(1));
+    assert!(matches!(action("times"), Action::Shift(_)));
+
+    let comparisons = from_grammar(
+      "%nonassoc lt; <E> -> <E> lt <E> | id;",
+      Some(TableKind::Lalr),
+    )
+    .unwrap();
+    assert!(comparisons.conflicts.is_empty());
+    assert!(
+      comparisons
+        .action_table
+        .iter()
+        .any(|((_, symbol), action)| symbol.get_name() == "lt" && action == &Action::Error)
+    );
+  }
+}
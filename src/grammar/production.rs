@@ -1,3 +1,5 @@
+use std::fmt::Display;
+
 use super::Symbol;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -77,6 +79,18 @@ impl Production {
   }
 }
 
+impl Display for Production {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} ->", self.lhs)?;
+
+    for symbol in &self.rhs {
+      write!(f, " {}", symbol)?;
+    }
+
+    Ok(())
+  }
+}
+
 impl From<Production> for (Symbol, Vec<Symbol>) {
   fn from(val: Production) -> Self {
     (val.lhs, val.rhs)
@@ -28,9 +28,29 @@ pub enum GLCTokens {
   #[token("|")]
   Pipe,
 
-  /// The end of a production
+  /// The end of a production or of a declaration
   #[token(";")]
   EndOfProduction,
+
+  /// Chooses how the parsing tables of the grammar are built: slr (the default), lalr or lr1
+  #[token("%tables")]
+  Tables,
+
+  /// Declares left associative terminals, with a higher precedence than the ones before
+  #[token("%left")]
+  Left,
+
+  /// Declares right associative terminals, with a higher precedence than the ones before
+  #[token("%right")]
+  Right,
+
+  /// Declares non associative terminals, with a higher precedence than the ones before
+  #[token("%nonassoc")]
+  NonAssoc,
+
+  /// Gives a production the precedence of the terminal after it
+  #[token("%prec")]
+  Prec,
 }
 
 impl Display for GLCTokens {
@@ -0,0 +1,425 @@
+use std::collections::BTreeSet;
+
+use crate::{
+  codegen::vm::{debug_info::DebugInfo, ByteCode},
+  lexer::{self, tokens::Token},
+};
+
+use super::vm::{errors::into_report, value::Value, VM};
+
+const PROMPT: &str = "(debug) ";
+
+/// How many instructions `list` shows before and after the current one
+const LIST_CONTEXT: usize = 4;
+
+const HELP: &str = "\
+step, s [n]          Execute the next n instructions (1 by default)
+next, n              Execute the next instruction, stepping over procedure calls
+continue, c          Run until a breakpoint or the end of the program
+break, b <index>     Break before an instruction
+break, b line <n>    Break before the first instruction of a source line
+delete, d <index>    Remove the breakpoint of an instruction
+info                 List the breakpoints and the watches
+list, l              Show the instructions around the current one
+stack                Show the stack (the bottom value is 0)
+set <i> <value>      Replace the i-th value of the stack
+push <value>         Push a value
+pop                  Pop the top of the stack
+watch <expr>         Show an expression at every stop:
+                       top, depth, stack[i], var[slot] or counter[depth]
+unwatch <n>          Remove the n-th watch
+restart              Run the program from the start again
+help                 Show this message
+quit, q              Leave the debugger";
+
+/// What the debugger loop should do after a command was handled
+pub enum DebuggerAction {
+  Continue,
+  Quit,
+}
+
+/// Something about the state of the VM that is shown at every stop
+#[derive(Debug, Clone, Copy)]
+enum Watch {
+  Top,
+  Depth,
+  Stack(usize),
+  Variable(usize),
+  Counter(usize),
+}
+
+impl Watch {
+  fn parse(expression: &str) -> Option<Watch> {
+    match expression {
+      "top" => return Some(Watch::Top),
+      "depth" => return Some(Watch::Depth),
+      "counter" => return Some(Watch::Counter(0)),
+      _ => {}
+    }
+
+    let (name, index) = expression.strip_suffix(']')?.split_once('[')?;
+    let index = index.trim().parse().ok()?;
+
+    match name.trim() {
+      "stack" => Some(Watch::Stack(index)),
+      "var" => Some(Watch::Variable(index)),
+      "counter" => Some(Watch::Counter(index)),
+      _ => None,
+    }
+  }
+
+  fn eval(&self, vm: &VM) -> String {
+    let value = match self {
+      Watch::Top => vm.stack().last().map(Value::to_literal),
+      Watch::Depth => Some(vm.stack().len().to_string()),
+      Watch::Stack(index) => vm.stack().get(*index).map(Value::to_literal),
+      Watch::Variable(slot) => vm.variable(*slot).map(Value::to_literal),
+      Watch::Counter(depth) => vm.loop_counter(*depth).map(|counter| counter.to_string()),
+    };
+
+    value.unwrap_or("<unavailable>".to_string())
+  }
+}
+
+/// A debugging session over a compiled program. The source lines are only known when the program
+/// comes with its debug info.
+pub struct Debugger {
+  bytecode: Vec<ByteCode>,
+  debug_info: Option<DebugInfo>,
+  vm: VM,
+  breakpoints: BTreeSet<usize>,
+  watches: Vec<(String, Watch)>,
+}
+
+impl Debugger {
+  pub fn new(bytecode: Vec<ByteCode>, debug_info: Option<DebugInfo>, vm: VM) -> Self {
+    Self {
+      bytecode,
+      debug_info,
+      vm,
+      breakpoints: BTreeSet::new(),
+      watches: vec![],
+    }
+  }
+
+  pub fn prompt(&self) -> &'static str {
+    PROMPT
+  }
+
+  pub fn handle_command(&mut self, line: &str) -> DebuggerAction {
+    let mut words = line.split_whitespace();
+    let Some(command) = words.next() else {
+      return DebuggerAction::Continue;
+    };
+    let arguments = words.collect::<Vec<_>>();
+
+    match (command, arguments.as_slice()) {
+      ("step" | "s", []) => self.step(1),
+      ("step" | "s", [count]) => match count.parse() {
+        Ok(count) => self.step(count),
+        Err(_) => eprintln!("Invalid count: {count}"),
+      },
+      ("next" | "n", []) => self.next(),
+      ("continue" | "c", []) => self.resume(),
+      ("break" | "b", ["line", line]) => self.break_at_line(line),
+      ("break" | "b", [index]) => match index.parse() {
+        Ok(index) if index < self.bytecode.len() => {
+          self.breakpoints.insert(index);
+        }
+        _ => eprintln!("Invalid instruction: {index}"),
+      },
+      ("delete" | "d", [index]) => match index.parse() {
+        Ok(index) if self.breakpoints.remove(&index) => {}
+        _ => eprintln!("No breakpoint at {index}"),
+      },
+      ("info", []) => self.info(),
+      ("list" | "l", []) => self.list(),
+      ("stack", []) => self.show_stack(),
+      ("set", [index, value @ ..]) => self.set(index, &value.join(" ")),
+      ("push", value @ [_, ..]) => match parse_value(&value.join(" ")) {
+        Some(value) => self.vm.stack_mut().push(value),
+        None => eprintln!("Invalid value: {}", value.join(" ")),
+      },
+      ("pop", []) => match self.vm.stack_mut().pop() {
+        Some(value) => println!("{}", value.to_literal()),
+        None => eprintln!("The stack is empty"),
+      },
+      ("watch", expression @ [_, ..]) => {
+        let expression = expression.join(" ");
+        match Watch::parse(&expression) {
+          Some(watch) => self.watches.push((expression, watch)),
+          None => eprintln!("Invalid watch expression: {expression}"),
+        }
+      }
+      ("unwatch", [index]) => match index.parse::<usize>() {
+        Ok(index) if index < self.watches.len() => {
+          self.watches.remove(index);
+        }
+        _ => eprintln!("No watch {index}"),
+      },
+      ("restart", []) => {
+        self.vm = self.vm.restarted();
+        self.show_location();
+      }
+      ("help", []) => println!("{HELP}"),
+      ("quit" | "q", []) => return DebuggerAction::Quit,
+      _ => eprintln!("Unknown command: {line}, try help"),
+    }
+
+    DebuggerAction::Continue
+  }
+
+  /// Execute one instruction, reporting the runtime errors. Returns whether the program can go on.
+  fn execute_one(&mut self) -> bool {
+    if self.vm.is_finished(&self.bytecode) {
+      println!("The program finished");
+      return false;
+    }
+
+    match self.vm.step(&self.bytecode) {
+      Ok(()) => true,
+      Err(e) => {
+        eprintln!("{:?}", into_report(e));
+        false
+      }
+    }
+  }
+
+  fn at_breakpoint(&self) -> bool {
+    self.breakpoints.contains(&self.vm.instruction_counter())
+  }
+
+  fn step(&mut self, count: usize) {
+    for _ in 0..count {
+      if !self.execute_one() {
+        return;
+      }
+    }
+
+    self.show_location();
+  }
+
+  fn next(&mut self) {
+    let is_call = matches!(
+      self.bytecode.get(self.vm.instruction_counter()),
+      Some(ByteCode::Call(_))
+    );
+    let depth = self.vm.call_depth();
+
+    if !self.execute_one() {
+      return;
+    }
+
+    // Run the whole procedure, unless it hits a breakpoint
+    while is_call && self.vm.call_depth() > depth && !self.at_breakpoint() {
+      if !self.execute_one() {
+        return;
+      }
+    }
+
+    self.show_location();
+  }
+
+  fn resume(&mut self) {
+    // The current instruction may be the breakpoint that stopped the program
+    if !self.execute_one() {
+      return;
+    }
+
+    while !self.at_breakpoint() {
+      if self.vm.is_finished(&self.bytecode) {
+        println!("The program finished");
+        return;
+      }
+
+      if !self.execute_one() {
+        return;
+      }
+    }
+
+    self.show_location();
+  }
+
+  fn break_at_line(&mut self, line: &str) {
+    let Some(debug_info) = &self.debug_info else {
+      eprintln!("The program has no debug info, break on an instruction instead");
+      return;
+    };
+
+    match line
+      .parse()
+      .ok()
+      .and_then(|line| debug_info.first_instruction(line))
+    {
+      Some(instruction) => {
+        self.breakpoints.insert(instruction);
+        println!("Breakpoint at [{instruction}]");
+      }
+      None => eprintln!("No instruction on line {line}"),
+    }
+  }
+
+  fn set(&mut self, index: &str, value: &str) {
+    let Some(value) = parse_value(value) else {
+      eprintln!("Invalid value: {value}");
+      return;
+    };
+
+    match index
+      .parse::<usize>()
+      .ok()
+      .and_then(|index| self.vm.stack_mut().get_mut(index))
+    {
+      Some(slot) => *slot = value,
+      None => eprintln!("Invalid stack index: {index}"),
+    }
+  }
+
+  fn info(&self) {
+    println!("Breakpoints:");
+    for breakpoint in self.breakpoints.iter() {
+      println!("  {}", self.describe_instruction(*breakpoint));
+    }
+
+    println!("Watches:");
+    for (index, (expression, _)) in self.watches.iter().enumerate() {
+      println!("  {index}: {expression}");
+    }
+  }
+
+  fn list(&self) {
+    let current = self.vm.instruction_counter();
+    let start = current.saturating_sub(LIST_CONTEXT);
+    let end = (current + LIST_CONTEXT + 1).min(self.bytecode.len());
+
+    for instruction in start..end {
+      let marker = if instruction == current { "=>" } else { "  " };
+      println!("{marker} {}", self.describe_instruction(instruction));
+    }
+  }
+
+  fn show_stack(&self) {
+    for (index, value) in self.vm.stack().iter().enumerate() {
+      println!("{index: >4} {}", value.to_literal());
+    }
+  }
+
+  /// Show where the program stopped and the watches
+  fn show_location(&self) {
+    let current = self.vm.instruction_counter();
+
+    if self.vm.is_finished(&self.bytecode) {
+      println!("The program finished");
+    } else {
+      println!("{}", self.describe_instruction(current));
+    }
+
+    for (expression, watch) in self.watches.iter() {
+      println!("  {expression} = {}", watch.eval(&self.vm));
+    }
+  }
+
+  fn describe_instruction(&self, instruction: usize) -> String {
+    let breakpoint = if self.breakpoints.contains(&instruction) {
+      "*"
+    } else {
+      " "
+    };
+    let mut description = format!(
+      "{breakpoint}[{instruction}] {:?}",
+      self.bytecode[instruction]
+    );
+
+    if let Some(debug_info) = &self.debug_info {
+      if let Some(line) = debug_info.line(instruction) {
+        let text = debug_info.line_text(line).unwrap_or_default().trim();
+        description = format!("{description: <32} line {line}: {text}");
+      }
+    }
+
+    description
+  }
+}
+
+/// Parse a literal the same way the lexer does
+fn parse_value(text: &str) -> Option<Value> {
+  let tokens = lexer::generate::compute_tokens(text).ok()?;
+
+  match tokens.as_slice() {
+    [literal, end] if end.token == Token::EndOfInput => match &literal.token {
+      Token::Integer(value) => Some(Value::Int(*value)),
+      Token::Float(value) => Some(Value::Float32(*value)),
+      Token::Boolean(value) => Some(Value::Bool(*value)),
+      Token::String(value) => Some(Value::Str(value.clone())),
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod debugger_tests {
+  use super::*;
+  use crate::{codegen::vm::VMCodeGenerator, frontend::Program};
+
+  const DOUBLES: &str = "proc double i32 -- i32 do 2 * end\n1 double\n3 double\n+ dump\n";
+
+  fn debugger(source: &str) -> Debugger {
+    let program = Program::from_source(source.to_string(), None).unwrap();
+
+    let mut generator = VMCodeGenerator::new();
+    generator.generate_byte_code(&program.ast).unwrap();
+    let debug_info = Some(generator.debug_info(&program.source));
+
+    let vm = VM::new().with_debug_info(debug_info.clone());
+    Debugger::new(generator.bytecode().to_vec(), debug_info, vm)
+  }
+
+  fn stack(debugger: &Debugger) -> Vec<String> {
+    debugger.vm.stack().iter().map(Value::to_literal).collect()
+  }
+
+  #[test]
+  fn test_breakpoints_and_stepping() {
+    let mut debugger = debugger(DOUBLES);
+
+    debugger.handle_command("break line 3");
+    debugger.handle_command("continue");
+    assert_eq!(debugger.vm.instruction_counter(), 6);
+    assert_eq!(stack(&debugger), ["2"]);
+
+    // `next` runs the whole procedure
+    debugger.handle_command("next");
+    debugger.handle_command("next");
+    assert_eq!(debugger.vm.call_depth(), 0);
+    assert_eq!(stack(&debugger), ["2", "6"]);
+
+    // `step` goes into it
+    debugger.handle_command("restart");
+    debugger.handle_command("step 3");
+    assert_eq!(debugger.vm.call_depth(), 1);
+    assert_eq!(stack(&debugger), ["1"]);
+
+    debugger.handle_command("continue");
+    assert_eq!(debugger.vm.instruction_counter(), 6);
+    assert!(matches!(
+      debugger.handle_command("quit"),
+      DebuggerAction::Quit
+    ));
+  }
+
+  #[test]
+  fn test_editing_the_stack() {
+    let mut debugger = debugger(DOUBLES);
+
+    debugger.handle_command("push 5");
+    debugger.handle_command("push 2.5");
+    debugger.handle_command("set 0 7");
+    assert_eq!(stack(&debugger), ["7", "2.5"]);
+
+    debugger.handle_command("pop");
+    debugger.handle_command("set 3 1");
+    debugger.handle_command("push");
+    assert_eq!(stack(&debugger), ["7"]);
+  }
+}
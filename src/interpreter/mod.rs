@@ -1 +1,3 @@
+pub mod debugger;
+pub mod repl;
 pub mod vm;
@@ -0,0 +1,278 @@
+use miette::Result as MietteResult;
+
+use crate::{
+  codegen::vm::VMCodeGenerator,
+  frontend,
+  lexer::{self, tokens::Token},
+  parser::SLR::SLR,
+  semantic::SemanticAnalyzer,
+};
+
+use super::vm::{arithmetic::overflow::OverflowMode, errors::into_report, value::Value, VM};
+
+const PROMPT: &str = "pile> ";
+const CONTINUATION_PROMPT: &str = "...   ";
+
+const HELP: &str = "\
+:stack        Show the stack
+:clear        Empty the stack
+:bytecode     Show the bytecode of the session
+:load <file>  Run a file in the session
+:help         Show this message
+:quit         Leave the REPL";
+
+/// What the REPL loop should do after a line was handled
+pub enum ReplAction {
+  Continue,
+  Quit,
+}
+
+/// An interactive session: every accepted line extends the same program, so the stack, the
+/// variables and the procedures live until the session ends
+pub struct Repl {
+  parser: SLR,
+  analyzer: SemanticAnalyzer,
+  generator: VMCodeGenerator,
+  vm: VM,
+  /// The source of every accepted line, the spans of a new line start right after it
+  source: String,
+  /// The lines of a block that was not closed yet
+  pending: String,
+}
+
+impl Repl {
+  pub fn new(parser: SLR, overflow: OverflowMode) -> Self {
+    Self {
+      parser,
+      analyzer: SemanticAnalyzer::new(String::new()),
+      generator: VMCodeGenerator::new(),
+      vm: VM::new().with_overflow(overflow),
+      source: String::new(),
+      pending: String::new(),
+    }
+  }
+
+  pub fn prompt(&self) -> &'static str {
+    if self.pending.is_empty() {
+      PROMPT
+    } else {
+      CONTINUATION_PROMPT
+    }
+  }
+
+  pub fn handle_line(&mut self, line: &str) -> ReplAction {
+    if self.pending.is_empty() {
+      if let Some(command) = line.trim().strip_prefix(':') {
+        return self.command(command);
+      }
+    }
+
+    self.pending.push_str(line);
+    self.pending.push('\n');
+
+    // Wait for the `end` of the blocks that were opened
+    if Self::has_open_blocks(&self.pending) {
+      return ReplAction::Continue;
+    }
+
+    let input = std::mem::take(&mut self.pending);
+    self.eval_and_report(&input);
+
+    ReplAction::Continue
+  }
+
+  fn command(&mut self, command: &str) -> ReplAction {
+    let (name, argument) = command
+      .split_once(char::is_whitespace)
+      .map(|(name, argument)| (name, argument.trim()))
+      .unwrap_or((command, ""));
+
+    match name {
+      "stack" => println!("{}", self.describe_stack()),
+      "clear" => {
+        self.vm.clear_stack();
+        self.analyzer.clear_stack();
+        println!("{}", self.describe_stack());
+      }
+      "bytecode" => {
+        for (location, opcode) in self.generator.bytecode().iter().enumerate() {
+          println!("{location: >4} {opcode:?}");
+        }
+      }
+      "load" if !argument.is_empty() => match std::fs::read_to_string(argument) {
+        Ok(contents) => self.eval_and_report(&contents),
+        Err(e) => eprintln!("Error reading {argument}: {e}"),
+      },
+      "load" => eprintln!("Usage: :load <file>"),
+      "help" => println!("{HELP}"),
+      "quit" | "q" => return ReplAction::Quit,
+      _ => eprintln!("Unknown command :{name}, try :help"),
+    }
+
+    ReplAction::Continue
+  }
+
+  fn eval_and_report(&mut self, input: &str) {
+    if input.trim().is_empty() {
+      return;
+    }
+
+    match self.eval(input) {
+      Ok(()) => println!("{}", self.describe_stack()),
+      Err(report) => eprintln!("{report:?}"),
+    }
+  }
+
+  /// Run the input on top of the session. Every stage works on a copy of its state, so an input
+  /// that fails at any point leaves the session as it was.
+  pub fn eval(&mut self, input: &str) -> MietteResult<()> {
+    let offset = self.source.len();
+    let mut source = format!("{}{}", self.source, input);
+    if !source.ends_with('\n') {
+      source.push('\n');
+    }
+
+    // The spans point into the whole session, so the diagnostics can show the earlier lines
+    let mut analyzer = self.analyzer.clone();
+    let ast = frontend::check(&self.parser, &mut analyzer, &source, offset)?;
+
+    let mut generator = self.generator.clone();
+    generator
+      .generate_byte_code(&ast)
+      .map_err(|e| miette::miette!("{}", e))?;
+
+    let mut vm = self
+      .vm
+      .clone()
+      .with_debug_info(Some(generator.debug_info(&source)));
+    vm.execute(generator.bytecode()).map_err(into_report)?;
+
+    self.analyzer = analyzer;
+    self.generator = generator;
+    self.vm = vm;
+    self.source = source;
+
+    Ok(())
+  }
+
+  /// Whether the input opens more blocks than it closes. Inputs that can't be tokenized are
+  /// considered complete, so the lexer error is reported right away.
+  fn has_open_blocks(input: &str) -> bool {
+    let Ok(tokens) = lexer::generate::compute_tokens(input) else {
+      return false;
+    };
+
+    let depth = tokens.iter().fold(0, |depth, token| match token.token {
+      Token::If | Token::While | Token::Range | Token::Proc => depth + 1,
+      Token::End => depth - 1,
+      _ => depth,
+    });
+
+    depth > 0
+  }
+
+  pub fn describe_stack(&self) -> String {
+    let values = self
+      .vm
+      .stack()
+      .iter()
+      .map(Value::to_literal)
+      .collect::<Vec<_>>();
+
+    format!("<{}> {}", values.len(), values.join(" "))
+  }
+}
+
+#[cfg(test)]
+mod repl_tests {
+  use super::*;
+  use crate::parser::embedded;
+
+  fn repl() -> Repl {
+    Repl::new(embedded::parser(None).unwrap(), OverflowMode::Wrapping)
+  }
+
+  fn run(repl: &mut Repl, lines: &[&str]) {
+    for line in lines {
+      repl.handle_line(line);
+    }
+  }
+
+  #[test]
+  fn test_lines_share_the_session() {
+    let mut repl = repl();
+
+    run(&mut repl, &["def(i32) x", "5 @x", "1 2", "x +"]);
+    assert_eq!(repl.describe_stack(), "<2> 1 7");
+
+    run(&mut repl, &[":stack", ":clear"]);
+    assert_eq!(repl.describe_stack(), "<0> ");
+
+    // The analyzer forgot the stack too, so `+` has nothing to add
+    run(&mut repl, &["+"]);
+    run(&mut repl, &["x dup *", "2", "*"]);
+    assert_eq!(repl.describe_stack(), "<1> 50");
+
+    let length = repl.generator.bytecode().len();
+    run(&mut repl, &[":bytecode"]);
+    assert_eq!(repl.generator.bytecode().len(), length);
+  }
+
+  #[test]
+  fn test_blocks_span_lines() {
+    let mut repl = repl();
+
+    run(&mut repl, &["proc double i32 -- i32 do", "  2 *"]);
+    assert_eq!(repl.prompt(), CONTINUATION_PROMPT);
+    assert_eq!(repl.describe_stack(), "<0> ");
+
+    // Commands are code while a block is open
+    run(&mut repl, &["end", "3 double"]);
+    assert_eq!(repl.prompt(), PROMPT);
+    assert_eq!(repl.describe_stack(), "<1> 6");
+
+    run(&mut repl, &["0 3 range i do", "i double +", "end"]);
+    assert_eq!(repl.describe_stack(), "<2> 6 6");
+
+    assert!(Repl::has_open_blocks("1 if\n"));
+    assert!(Repl::has_open_blocks("1 if 2 while\n end\n"));
+    assert!(!Repl::has_open_blocks("1 if 2 end\n"));
+    assert!(!Repl::has_open_blocks("\"unterminated\n"));
+  }
+
+  #[test]
+  fn test_failed_lines_change_nothing() {
+    let mut repl = repl();
+    run(&mut repl, &["def(i32) x", "1 @x", "x"]);
+
+    let source = repl.source.clone();
+    let length = repl.generator.bytecode().len();
+
+    // Inputs rejected by the front end and at run time
+    for line in ["1 +)", "\"a\" 1 +", "0 0 /"] {
+      assert!(repl.eval(line).is_err(), "{line}");
+      assert_eq!(repl.describe_stack(), "<1> 1", "{line}");
+      assert_eq!(repl.source, source, "{line}");
+      assert_eq!(repl.generator.bytecode().len(), length, "{line}");
+    }
+
+    // The analyzer still knows what the session holds
+    assert!(repl.eval("x +").is_ok());
+    assert_eq!(repl.describe_stack(), "<1> 2");
+    assert!(matches!(repl.handle_line(":quit"), ReplAction::Quit));
+  }
+
+  #[test]
+  fn test_load() {
+    let mut repl = repl();
+
+    run(
+      &mut repl,
+      &[":load assets/lang/procedures.pile", "2 square"],
+    );
+    assert_eq!(repl.describe_stack(), "<2> 0 4");
+
+    run(&mut repl, &[":load", ":load assets/lang/missing.pile"]);
+    assert_eq!(repl.describe_stack(), "<2> 0 4");
+  }
+}
@@ -1,7 +1,16 @@
+use std::ops::{Add, Div, Mul, Rem, Sub};
+
+use crate::lexer::tokens::Type;
+
+use self::overflow::OverflowMode;
+
 use super::value::Value;
 
+pub mod overflow;
+
 pub struct ArithmeticInstruction;
 
+#[derive(Debug)]
 pub enum ArithmeticMethod {
   Add,
   Sub,
@@ -11,36 +20,163 @@ pub enum ArithmeticMethod {
 }
 
 impl ArithmeticInstruction {
-  pub fn eval(stack: &mut Vec<Value>, instruction: ArithmeticMethod) -> anyhow::Result<()> {
-    let rhs = stack.pop().ok_or(anyhow::anyhow!("Add on empty stack"))?;
-    let lhs = stack.pop().ok_or(anyhow::anyhow!("Add on empty stack"))?;
-
-    match (lhs.clone(), rhs.clone()) {
-      (Value::Int(lhs), Value::Int(rhs)) => match instruction {
-        ArithmeticMethod::Add => stack.push(Value::Int(lhs + rhs)),
-        ArithmeticMethod::Sub => stack.push(Value::Int(lhs - rhs)),
-        ArithmeticMethod::Mul => stack.push(Value::Int(lhs * rhs)),
-        ArithmeticMethod::Div => {
-          if rhs == 0 {
-            return Err(anyhow::anyhow!("Divide by zero"));
-          }
-          stack.push(Value::Int(lhs / rhs))
+  pub fn eval(
+    stack: &mut Vec<Value>,
+    instruction: ArithmeticMethod,
+    overflow: OverflowMode,
+  ) -> anyhow::Result<()> {
+    let rhs = stack
+      .pop()
+      .ok_or(anyhow::anyhow!("{:?} on empty stack", instruction))?;
+    let lhs = stack
+      .pop()
+      .ok_or(anyhow::anyhow!("{:?} on empty stack", instruction))?;
+
+    if let (Value::Str(lhs), Value::Str(rhs), ArithmeticMethod::Add) = (&lhs, &rhs, &instruction) {
+      stack.push(Value::Str(format!("{}{}", lhs, rhs)));
+      return Ok(());
+    }
+
+    // Both operands are converted to the widest of their two types (see `Type::promote`)
+    let type_ = Self::promoted_type(&lhs, &rhs).ok_or(anyhow::anyhow!(
+      "{:?} on non-numeric values: {} and {}",
+      instruction,
+      lhs,
+      rhs
+    ))?;
+
+    let result = match (lhs.cast_to(&type_)?, rhs.cast_to(&type_)?) {
+      (Value::Int(lhs), Value::Int(rhs)) => {
+        Value::Int(overflow.fit_i32(Self::integer(lhs.into(), rhs.into(), &instruction)?)?)
+      }
+      (Value::Int64(lhs), Value::Int64(rhs)) => {
+        Value::Int64(overflow.fit_i64(Self::integer(lhs.into(), rhs.into(), &instruction)?)?)
+      }
+      (Value::Float32(lhs), Value::Float32(rhs)) => {
+        Value::Float32(Self::float(lhs, rhs, &instruction))
+      }
+      (Value::Float64(lhs), Value::Float64(rhs)) => {
+        Value::Float64(Self::float(lhs, rhs, &instruction))
+      }
+      (lhs, rhs) => {
+        return Err(anyhow::anyhow!(
+          "{:?} on mismatched values: {} and {}",
+          instruction,
+          lhs,
+          rhs
+        ))
+      }
+    };
+
+    stack.push(result);
+
+    Ok(())
+  }
+
+  fn promoted_type(lhs: &Value, rhs: &Value) -> Option<Type> {
+    lhs.numeric_type()?.promote(&rhs.numeric_type()?)
+  }
+
+  /// The exact result of an integer operation, integers are at most 64 bits wide so it can't
+  /// overflow an i128. The result is then fitted into its type by the overflow mode.
+  fn integer(lhs: i128, rhs: i128, instruction: &ArithmeticMethod) -> anyhow::Result<i128> {
+    Ok(match instruction {
+      ArithmeticMethod::Add => lhs + rhs,
+      ArithmeticMethod::Sub => lhs - rhs,
+      ArithmeticMethod::Mul => lhs * rhs,
+      ArithmeticMethod::Div => {
+        if rhs == 0 {
+          return Err(anyhow::anyhow!("Divide by zero"));
         }
-        ArithmeticMethod::Mod => {
-          if rhs == 0 {
-            return Err(anyhow::anyhow!("Modulo by zero"));
-          }
-          stack.push(Value::Int(lhs % rhs))
+        lhs / rhs
+      }
+      ArithmeticMethod::Mod => {
+        if rhs == 0 {
+          return Err(anyhow::anyhow!("Modulo by zero"));
         }
-      },
-      _ => {
-        return Err(anyhow::anyhow!(format!(
-          "Add on non-integers: {} + {}",
-          lhs, rhs
-        )));
+        lhs % rhs
       }
+    })
+  }
+
+  /// Floats follow IEEE 754, dividing by zero gives an infinity or NaN instead of an error
+  fn float<T>(lhs: T, rhs: T, instruction: &ArithmeticMethod) -> T
+  where
+    T: Add<Output = T> + Sub<Output = T> + Mul<Output = T> + Div<Output = T> + Rem<Output = T>,
+  {
+    match instruction {
+      ArithmeticMethod::Add => lhs + rhs,
+      ArithmeticMethod::Sub => lhs - rhs,
+      ArithmeticMethod::Mul => lhs * rhs,
+      ArithmeticMethod::Div => lhs / rhs,
+      ArithmeticMethod::Mod => lhs % rhs,
     }
+  }
+}
 
-    Ok(())
+#[cfg(test)]
+mod arithmetic_tests {
+  use super::*;
+
+  fn eval(lhs: Value, rhs: Value, instruction: ArithmeticMethod) -> anyhow::Result<Value> {
+    let mut stack = vec![lhs, rhs];
+    ArithmeticInstruction::eval(&mut stack, instruction, OverflowMode::Trapping)?;
+
+    Ok(stack.pop().unwrap())
+  }
+
+  #[test]
+  fn test_promotion() {
+    assert_eq!(
+      eval(Value::Int(1), Value::Float32(2.5), ArithmeticMethod::Add).unwrap(),
+      Value::Float32(3.5)
+    );
+    assert_eq!(
+      eval(Value::Int64(3), Value::Int(2), ArithmeticMethod::Mul).unwrap(),
+      Value::Int64(6)
+    );
+    assert_eq!(
+      eval(Value::Int64(7), Value::Float32(2.0), ArithmeticMethod::Div).unwrap(),
+      Value::Float32(3.5)
+    );
+    assert_eq!(
+      eval(
+        Value::Float32(1.5),
+        Value::Float64(1.0),
+        ArithmeticMethod::Sub
+      )
+      .unwrap(),
+      Value::Float64(0.5)
+    );
+
+    assert!(eval(Value::Int(1), Value::Bool(true), ArithmeticMethod::Add).is_err());
+    assert!(eval(Value::Str("a".into()), Value::Int(1), ArithmeticMethod::Add).is_err());
+  }
+
+  #[test]
+  fn test_integers_and_overflow() {
+    // The exact result is fitted into the promoted type, not into the type of the operands
+    assert_eq!(
+      eval(
+        Value::Int64(i32::MAX.into()),
+        Value::Int(1),
+        ArithmeticMethod::Add
+      )
+      .unwrap(),
+      Value::Int64(2147483648)
+    );
+    assert!(eval(Value::Int(i32::MAX), Value::Int(1), ArithmeticMethod::Add).is_err());
+    assert!(eval(Value::Int(i32::MIN), Value::Int(-1), ArithmeticMethod::Div).is_err());
+
+    assert_eq!(
+      eval(Value::Int(-7), Value::Int(2), ArithmeticMethod::Mod).unwrap(),
+      Value::Int(-1)
+    );
+    assert_eq!(
+      eval(Value::Int(1), Value::Int(0), ArithmeticMethod::Div)
+        .unwrap_err()
+        .to_string(),
+      "Divide by zero"
+    );
   }
 }
@@ -0,0 +1,99 @@
+/// What happens when the result of an integer operation doesn't fit in its type
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum OverflowMode {
+  /// Two's complement wrap around, the same as the native targets
+  #[default]
+  Wrapping,
+  /// Clamp the result to the minimum or the maximum of the type
+  Saturating,
+  /// Checked arithmetic, the program stops with an error
+  Trapping,
+}
+
+impl OverflowMode {
+  /// Fit the exact result of an operation on two i32 into an i32
+  pub fn fit_i32(self, value: i128) -> anyhow::Result<i32> {
+    match i32::try_from(value) {
+      Ok(value) => Ok(value),
+      Err(_) => match self {
+        OverflowMode::Wrapping => Ok(value as i32),
+        OverflowMode::Saturating => Ok(value.clamp(i32::MIN.into(), i32::MAX.into()) as i32),
+        OverflowMode::Trapping => Err(anyhow::anyhow!(
+          "Integer overflow: {} doesn't fit in an i32",
+          value
+        )),
+      },
+    }
+  }
+
+  /// Fit the exact result of an operation on two i64 into an i64
+  pub fn fit_i64(self, value: i128) -> anyhow::Result<i64> {
+    match i64::try_from(value) {
+      Ok(value) => Ok(value),
+      Err(_) => match self {
+        OverflowMode::Wrapping => Ok(value as i64),
+        OverflowMode::Saturating => Ok(value.clamp(i64::MIN.into(), i64::MAX.into()) as i64),
+        OverflowMode::Trapping => Err(anyhow::anyhow!(
+          "Integer overflow: {} doesn't fit in an i64",
+          value
+        )),
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod overflow_tests {
+  use super::*;
+
+  #[test]
+  fn test_fit_i32() {
+    let too_large = i128::from(i32::MAX) + 1;
+    let too_small = i128::from(i32::MIN) - 1;
+
+    for mode in [
+      OverflowMode::Wrapping,
+      OverflowMode::Saturating,
+      OverflowMode::Trapping,
+    ] {
+      assert_eq!(mode.fit_i32(-7).unwrap(), -7);
+    }
+
+    assert_eq!(OverflowMode::Wrapping.fit_i32(too_large).unwrap(), i32::MIN);
+    assert_eq!(OverflowMode::Wrapping.fit_i32(too_small).unwrap(), i32::MAX);
+    assert_eq!(
+      OverflowMode::Saturating.fit_i32(too_large).unwrap(),
+      i32::MAX
+    );
+    assert_eq!(
+      OverflowMode::Saturating.fit_i32(too_small).unwrap(),
+      i32::MIN
+    );
+    assert!(OverflowMode::Trapping.fit_i32(too_large).is_err());
+    assert!(OverflowMode::Trapping.fit_i32(too_small).is_err());
+  }
+
+  #[test]
+  fn test_fit_i64() {
+    let too_large = i128::from(i64::MAX) + 1;
+
+    assert_eq!(OverflowMode::Wrapping.fit_i64(too_large).unwrap(), i64::MIN);
+    assert_eq!(
+      OverflowMode::Saturating.fit_i64(too_large).unwrap(),
+      i64::MAX
+    );
+    assert_eq!(
+      OverflowMode::Trapping
+        .fit_i64(too_large)
+        .unwrap_err()
+        .to_string(),
+      "Integer overflow: 9223372036854775808 doesn't fit in an i64"
+    );
+    assert_eq!(
+      OverflowMode::Trapping
+        .fit_i64(i128::from(i32::MAX) + 1)
+        .unwrap(),
+      2147483648
+    );
+  }
+}
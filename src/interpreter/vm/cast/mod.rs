@@ -0,0 +1,49 @@
+use crate::lexer::tokens::Type;
+
+use super::value::Value;
+
+pub struct CastInstruction;
+
+impl CastInstruction {
+  /// Convert the top of the stack, see `Value::cast_to`
+  pub fn eval(stack: &mut Vec<Value>, type_: &Type) -> anyhow::Result<()> {
+    let value = stack.pop().ok_or(anyhow::anyhow!("Cast on empty stack"))?;
+
+    stack.push(value.cast_to(type_)?);
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod cast_tests {
+  use super::*;
+
+  fn cast(value: Value, type_: Type) -> Value {
+    let mut stack = vec![value];
+    CastInstruction::eval(&mut stack, &type_).unwrap();
+    stack.pop().unwrap()
+  }
+
+  #[test]
+  fn test_numeric_casts() {
+    assert_eq!(cast(Value::Float32(2.7), Type::I32), Value::Int(2));
+    assert_eq!(cast(Value::Int(-3), Type::I64), Value::Int64(-3));
+    assert_eq!(cast(Value::Int64(5), Type::F64), Value::Float64(5.0));
+    assert_eq!(cast(Value::Float64(0.0), Type::Bool), Value::Bool(false));
+    assert_eq!(cast(Value::Bool(true), Type::F32), Value::Float32(1.0));
+    assert!(CastInstruction::eval(&mut vec![Value::Str("1".to_string())], &Type::I32).is_err());
+  }
+
+  #[test]
+  fn test_float_casts_saturate() {
+    assert_eq!(cast(Value::Float32(1e20), Type::I32), Value::Int(i32::MAX));
+    assert_eq!(cast(Value::Float32(-1e20), Type::I32), Value::Int(i32::MIN));
+    assert_eq!(cast(Value::Float32(f32::NAN), Type::I32), Value::Int(0));
+    assert_eq!(cast(Value::Float64(f64::NAN), Type::I64), Value::Int64(0));
+    assert_eq!(
+      cast(Value::Float32(f32::NAN), Type::Bool),
+      Value::Bool(true)
+    );
+  }
+}
@@ -23,6 +23,17 @@ impl ComparisonInstruction {
       .pop()
       .ok_or(anyhow::anyhow!("Comparison on empty stack"))?;
 
+    // Numbers are compared once converted to the widest of their two types, like the arithmetic
+    // does (see `Type::promote`)
+    let promoted_type = lhs
+      .numeric_type()
+      .zip(rhs.numeric_type())
+      .and_then(|(lhs_type, rhs_type)| lhs_type.promote(&rhs_type));
+    let (lhs, rhs) = match promoted_type {
+      Some(type_) => (lhs.cast_to(&type_)?, rhs.cast_to(&type_)?),
+      None => (lhs, rhs),
+    };
+
     let result = comparison(lhs, rhs);
 
     stack.push(Value::Bool(result));
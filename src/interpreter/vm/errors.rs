@@ -0,0 +1,40 @@
+use miette::Diagnostic;
+use thiserror::Error;
+
+#[derive(Error, Diagnostic, Debug)]
+pub enum RuntimeError {
+  #[error("{message}")]
+  #[diagnostic(code(runtime_error::failed))]
+  Located {
+    message: String,
+
+    #[source_code]
+    input: String,
+
+    #[label("Failed here")]
+    extension_src: (usize, usize),
+
+    #[help]
+    stack: String,
+  },
+
+  /// The program was compiled without its debug info, only the instruction is known
+  #[error("{message} (instruction {instruction})")]
+  #[diagnostic(code(runtime_error::failed))]
+  Unlocated {
+    message: String,
+
+    instruction: usize,
+
+    #[help]
+    stack: String,
+  },
+}
+
+/// Show the runtime errors with their diagnostic, the other errors as they are
+pub fn into_report(error: anyhow::Error) -> miette::Report {
+  match error.downcast::<RuntimeError>() {
+    Ok(error) => error.into(),
+    Err(error) => miette::miette!("{}", error),
+  }
+}
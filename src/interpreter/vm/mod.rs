@@ -1,45 +1,81 @@
-use std::{fs::File, io::Read};
-
-use crate::codegen::vm::ByteCode;
+use crate::codegen::vm::{container::Container, debug_info::DebugInfo, ByteCode};
 
 use self::{
-  arithmetic::{ArithmeticInstruction, ArithmeticMethod},
+  arithmetic::{overflow::OverflowMode, ArithmeticInstruction, ArithmeticMethod},
+  cast::CastInstruction,
   comparison::{ComparisonInstruction, ComparisonMethod},
+  errors::RuntimeError,
   stack::{dump::DumpInstruction, dup::DupInstruction, pop::PopInstruction, push::PushInstruction},
   value::Value,
 };
 
 pub mod arithmetic;
+pub mod cast;
 pub mod comparison;
+pub mod errors;
 pub mod stack;
 pub mod value;
 
 pub struct VMInterpreter;
 
 impl VMInterpreter {
-  pub fn open(bytecode_file: &str) -> anyhow::Result<Vec<ByteCode>> {
-    let mut file =
-      File::open(bytecode_file).map_err(|e| anyhow::anyhow!("Error opening file: {}", e))?;
-    let mut encoded = Vec::new();
-    file
-      .read_to_end(&mut encoded)
-      .map_err(|e| anyhow::anyhow!("Error reading file: {}", e))?;
-
-    bincode::deserialize(&encoded).map_err(|e| anyhow::anyhow!("Error deserializing: {}", e))
+  pub fn open(bytecode_file: &str) -> anyhow::Result<Container> {
+    Container::read(bytecode_file)
   }
 
-  pub fn run(bytecode_file: &str) -> anyhow::Result<()> {
-    let bytecode = VMInterpreter::open(bytecode_file)?;
-    // println!("{:?}", bytecode);
+  /// The stack size from the command line wins over the one the program was compiled with
+  pub fn run(
+    bytecode_file: &str,
+    overflow: OverflowMode,
+    trace: bool,
+    stack_size: Option<usize>,
+  ) -> anyhow::Result<()> {
+    let Container {
+      bytecode,
+      debug_info,
+      stack_size: compiled_stack_size,
+      ..
+    } = VMInterpreter::open(bytecode_file)?;
 
-    VM::new().execute(&bytecode)?;
+    VM::new()
+      .with_overflow(overflow)
+      .with_trace(trace)
+      .with_stack_size(
+        stack_size
+          .or(compiled_stack_size)
+          .unwrap_or(DEFAULT_STACK_SIZE),
+      )
+      .with_debug_info(debug_info)
+      .execute(&bytecode)?;
 
     Ok(())
   }
 }
 
+/// How many values the stack holds when neither the program nor the command line chose
+pub const DEFAULT_STACK_SIZE: usize = 64 * 1024;
+
+/// The state of a running range loop
+#[derive(Clone)]
+struct LoopFrame {
+  counter: i32,
+  bound: i32,
+}
+
+#[derive(Clone)]
 pub struct VM {
   stack: Vec<Value>,
+  return_stack: Vec<usize>,
+  loop_stack: Vec<LoopFrame>,
+  /// The variable slots, a slot is empty until the first assignment
+  variables: Vec<Option<Value>>,
+  overflow: OverflowMode,
+  /// Print every instruction with the stack before it is executed
+  trace: bool,
+  /// Locates the runtime errors in the source
+  debug_info: Option<DebugInfo>,
+  /// How many values the stack holds before the program stops with a stack overflow
+  stack_size: usize,
   instruction_counter: usize,
 }
 
@@ -48,76 +84,468 @@ impl VM {
   pub fn new() -> Self {
     Self {
       stack: vec![],
+      return_stack: vec![],
+      loop_stack: vec![],
+      variables: vec![],
+      overflow: OverflowMode::default(),
+      trace: false,
+      debug_info: None,
+      stack_size: DEFAULT_STACK_SIZE,
       instruction_counter: 0,
     }
   }
 
+  /// Choose what happens when an integer operation overflows
+  pub fn with_overflow(mut self, overflow: OverflowMode) -> Self {
+    self.overflow = overflow;
+    self
+  }
+
+  /// A VM with the same settings, ready to run a program from its start
+  pub fn restarted(&self) -> Self {
+    Self {
+      overflow: self.overflow,
+      trace: self.trace,
+      debug_info: self.debug_info.clone(),
+      stack_size: self.stack_size,
+      ..Self::new()
+    }
+  }
+
+  pub fn with_trace(mut self, trace: bool) -> Self {
+    self.trace = trace;
+    self
+  }
+
+  pub fn with_debug_info(mut self, debug_info: Option<DebugInfo>) -> Self {
+    self.debug_info = debug_info;
+    self
+  }
+
+  pub fn with_stack_size(mut self, stack_size: usize) -> Self {
+    self.stack_size = stack_size;
+    self
+  }
+
+  pub fn stack(&self) -> &[Value] {
+    &self.stack
+  }
+
+  pub fn stack_mut(&mut self) -> &mut Vec<Value> {
+    &mut self.stack
+  }
+
+  pub fn clear_stack(&mut self) {
+    self.stack.clear();
+  }
+
+  pub fn instruction_counter(&self) -> usize {
+    self.instruction_counter
+  }
+
+  /// How many procedure calls are waiting to return
+  pub fn call_depth(&self) -> usize {
+    self.return_stack.len()
+  }
+
+  /// The value of a variable slot, if it was assigned
+  pub fn variable(&self, slot: usize) -> Option<&Value> {
+    self.variables.get(slot).and_then(|value| value.as_ref())
+  }
+
+  /// The counter of the n-th enclosing range loop (0 is the innermost)
+  pub fn loop_counter(&self, depth: usize) -> Option<i32> {
+    self.loop_stack.iter().rev().nth(depth).map(|frame| frame.counter)
+  }
+
   pub fn execute(&mut self, bytecode: &[ByteCode]) -> anyhow::Result<()> {
-    while self.instruction_counter < bytecode.len() {
-      let instruction = &bytecode[self.instruction_counter];
-
-      println!(
-        "{}{:?}",
-        format!(
-          "{: <24} | ",
-          format!("[{}] {:?}", self.instruction_counter, instruction)
-        ),
-        self.stack
-      );
-      match instruction {
-        // Stack
-        ByteCode::PushInt(value) => PushInstruction::eval(&mut self.stack, *value)?,
-        ByteCode::PushFloat(value) => PushInstruction::eval(&mut self.stack, *value)?,
-        ByteCode::PushStr(value) => PushInstruction::eval(&mut self.stack, value.clone())?,
-        ByteCode::PushBool(value) => PushInstruction::eval(&mut self.stack, *value)?,
-        ByteCode::Dump => DumpInstruction::eval(&mut self.stack)?,
-        ByteCode::Dup => DupInstruction::eval(&mut self.stack)?,
-        ByteCode::Pop => PopInstruction::eval(&mut self.stack)?,
-
-        // Arithmetic
-        ByteCode::Add => ArithmeticInstruction::eval(&mut self.stack, ArithmeticMethod::Add)?,
-        ByteCode::Sub => ArithmeticInstruction::eval(&mut self.stack, ArithmeticMethod::Sub)?,
-        ByteCode::Mul => ArithmeticInstruction::eval(&mut self.stack, ArithmeticMethod::Mul)?,
-        ByteCode::Div => ArithmeticInstruction::eval(&mut self.stack, ArithmeticMethod::Div)?,
-        ByteCode::Mod => ArithmeticInstruction::eval(&mut self.stack, ArithmeticMethod::Mod)?,
-
-        // Comparison
-        ByteCode::Eq => ComparisonInstruction::eval(&mut self.stack, ComparisonMethod::Equal)?,
-        ByteCode::Neq => ComparisonInstruction::eval(&mut self.stack, ComparisonMethod::NotEqual)?,
-        ByteCode::Lt => ComparisonInstruction::eval(&mut self.stack, ComparisonMethod::LessThan)?,
-        ByteCode::Leq => {
-          ComparisonInstruction::eval(&mut self.stack, ComparisonMethod::LessThanEqual)?
-        }
-        ByteCode::Gt => {
-          ComparisonInstruction::eval(&mut self.stack, ComparisonMethod::GreaterThan)?
+    while !self.is_finished(bytecode) {
+      self.step(bytecode)?;
+    }
+
+    Ok(())
+  }
+
+  /// Whether the instruction counter went past the last instruction
+  pub fn is_finished(&self, bytecode: &[ByteCode]) -> bool {
+    self.instruction_counter >= bytecode.len()
+  }
+
+  /// Execute the instruction at the instruction counter
+  pub fn step(&mut self, bytecode: &[ByteCode]) -> anyhow::Result<()> {
+    let instruction = bytecode
+      .get(self.instruction_counter)
+      .ok_or(anyhow::anyhow!("The program already finished"))?;
+
+    if self.trace {
+      let location = format!("[{}] {:?}", self.instruction_counter, instruction);
+      println!("{location: <24} | {:?}", self.stack);
+    }
+
+    // The operands are put back when the instruction fails, so the error shows what it found
+    let depth = self.stack.len();
+    let consumed = instruction.consumed_values().min(depth);
+    let operands = self.stack[depth - consumed..].to_vec();
+
+    self.dispatch(instruction).map_err(|error| {
+      self.stack.truncate(depth - consumed);
+      self.stack.extend(operands);
+
+      self.runtime_error(error).into()
+    })
+  }
+
+  fn dispatch(&mut self, instruction: &ByteCode) -> anyhow::Result<()> {
+    match instruction {
+      // Stack
+      ByteCode::PushInt(value) => PushInstruction::eval(&mut self.stack, *value)?,
+      ByteCode::PushFloat(value) => PushInstruction::eval(&mut self.stack, *value)?,
+      ByteCode::PushStr(value) => PushInstruction::eval(&mut self.stack, value.clone())?,
+      ByteCode::PushBool(value) => PushInstruction::eval(&mut self.stack, *value)?,
+      ByteCode::Dump => DumpInstruction::eval(&mut self.stack)?,
+      ByteCode::Dup => DupInstruction::eval(&mut self.stack)?,
+      ByteCode::Pop => PopInstruction::eval(&mut self.stack)?,
+
+      // Arithmetic
+      ByteCode::Add => self.arithmetic(ArithmeticMethod::Add)?,
+      ByteCode::Sub => self.arithmetic(ArithmeticMethod::Sub)?,
+      ByteCode::Mul => self.arithmetic(ArithmeticMethod::Mul)?,
+      ByteCode::Div => self.arithmetic(ArithmeticMethod::Div)?,
+      ByteCode::Mod => self.arithmetic(ArithmeticMethod::Mod)?,
+
+      // Conversion
+      ByteCode::Cast(type_) => CastInstruction::eval(&mut self.stack, type_)?,
+
+      // Comparison
+      ByteCode::Eq => ComparisonInstruction::eval(&mut self.stack, ComparisonMethod::Equal)?,
+      ByteCode::Neq => ComparisonInstruction::eval(&mut self.stack, ComparisonMethod::NotEqual)?,
+      ByteCode::Lt => ComparisonInstruction::eval(&mut self.stack, ComparisonMethod::LessThan)?,
+      ByteCode::Leq => {
+        ComparisonInstruction::eval(&mut self.stack, ComparisonMethod::LessThanEqual)?
+      }
+      ByteCode::Gt => {
+        ComparisonInstruction::eval(&mut self.stack, ComparisonMethod::GreaterThan)?
+      }
+      ByteCode::Geq => {
+        ComparisonInstruction::eval(&mut self.stack, ComparisonMethod::GreaterThanEqual)?
+      }
+
+      // Control flow
+      // Hand written bytecode is not type checked, the condition may be anything
+      ByteCode::JumpIfNotTrue(new_counter) => match self.stack.pop() {
+        Some(Value::Bool(true)) => {}
+        Some(Value::Bool(false)) => {
+          self.instruction_counter = *new_counter;
+          return Ok(());
         }
-        ByteCode::Geq => {
-          ComparisonInstruction::eval(&mut self.stack, ComparisonMethod::GreaterThanEqual)?
+        Some(value) => {
+          return Err(anyhow::anyhow!(
+            "Jump if not true on {}, expected a bool",
+            value.to_literal()
+          ));
         }
+        None => return Err(anyhow::anyhow!("Jump if not true on empty stack")),
+      },
+      ByteCode::Jump(new_counter) => {
+        self.instruction_counter = *new_counter;
+        return Ok(());
+      }
+      ByteCode::Ignore => {}
+
+      // Procedures
+      ByteCode::Call(location) => {
+        self.return_stack.push(self.instruction_counter + 1);
+        self.instruction_counter = *location;
+        return Ok(());
+      }
+      ByteCode::Ret => {
+        let return_location = self
+          .return_stack
+          .pop()
+          .ok_or(anyhow::anyhow!("Return outside of a procedure"))?;
+        self.instruction_counter = return_location;
+        return Ok(());
+      }
+
+      // Counted loops
+      ByteCode::RangeInit(has_start) => {
+        let bound = self.pop_range_bound()?;
+        let counter = if *has_start {
+          self.pop_range_bound()?
+        } else {
+          0
+        };
+
+        self.loop_stack.push(LoopFrame { counter, bound });
+      }
+      ByteCode::RangeCheck(exit) => {
+        let frame = self
+          .loop_stack
+          .last()
+          .ok_or(anyhow::anyhow!("Range check outside of a loop"))?;
 
-        // Control flow
-        ByteCode::JumpIfNotTrue(new_counter) => {
-          if let Some(Value::Bool(value)) = self.stack.pop() {
-            if !value {
-              self.instruction_counter = *new_counter - 1;
-            }
-          }
+        if frame.counter >= frame.bound {
+          self.loop_stack.pop();
+          self.instruction_counter = *exit;
+          return Ok(());
         }
-        ByteCode::Jump(new_counter) => {
-          self.instruction_counter = *new_counter - 1;
+      }
+      ByteCode::RangeNext(check) => {
+        let frame = self
+          .loop_stack
+          .last_mut()
+          .ok_or(anyhow::anyhow!("Range next outside of a loop"))?;
+
+        frame.counter += 1;
+        self.instruction_counter = *check;
+        return Ok(());
+      }
+      ByteCode::RangeCounter(depth) => {
+        let frame = self
+          .loop_stack
+          .iter()
+          .rev()
+          .nth(*depth)
+          .ok_or(anyhow::anyhow!("Range counter outside of a loop"))?;
+
+        PushInstruction::eval(&mut self.stack, frame.counter)?;
+      }
+
+      // Variables
+      ByteCode::Store(slot) => {
+        let value = self
+          .stack
+          .pop()
+          .ok_or(anyhow::anyhow!("Store on empty stack"))?;
+
+        if self.variables.len() <= *slot {
+          self.variables.resize(*slot + 1, None);
         }
-        ByteCode::Ignore => {}
+        self.variables[*slot] = Some(value);
       }
+      ByteCode::Load(slot) => {
+        let value = self
+          .variables
+          .get(*slot)
+          .cloned()
+          .flatten()
+          .ok_or(anyhow::anyhow!("Variable read before being assigned"))?;
 
-      self.instruction_counter += 1; // Increment the instruction counter after each instruction
+        self.stack.push(value);
+      }
+    }
+
+    if self.stack.len() > self.stack_size {
+      anyhow::bail!(
+        "Stack overflow: the stack holds at most {} values",
+        self.stack_size
+      );
     }
 
+    self.instruction_counter += 1; // Increment the instruction counter after each instruction
+
     Ok(())
   }
 }
 
+impl VM {
+  fn runtime_error(&self, error: anyhow::Error) -> RuntimeError {
+    let message = error.to_string();
+    let stack = format!(
+      "Stack: [{}]",
+      self
+        .stack
+        .iter()
+        .map(Value::to_literal)
+        .collect::<Vec<_>>()
+        .join(" ")
+    );
+
+    match &self.debug_info {
+      Some(debug_info) if debug_info.span(self.instruction_counter).is_some() => {
+        RuntimeError::Located {
+          message,
+          input: debug_info.source.clone(),
+          extension_src: debug_info.span(self.instruction_counter).unwrap(),
+          stack,
+        }
+      }
+      _ => RuntimeError::Unlocated {
+        message,
+        instruction: self.instruction_counter,
+        stack,
+      },
+    }
+  }
+
+  fn arithmetic(&mut self, method: ArithmeticMethod) -> anyhow::Result<()> {
+    ArithmeticInstruction::eval(&mut self.stack, method, self.overflow)
+  }
+
+  fn pop_range_bound(&mut self) -> anyhow::Result<i32> {
+    match self.stack.pop() {
+      Some(Value::Int(value)) => Ok(value),
+      Some(value) => Err(anyhow::anyhow!("Range bound is not an integer: {}", value)),
+      None => Err(anyhow::anyhow!("Range on empty stack")),
+    }
+  }
+}
+
 impl Default for VM {
   fn default() -> Self {
     Self::new()
   }
 }
+
+#[cfg(test)]
+mod vm_tests {
+  use super::*;
+  use crate::{codegen::vm::VMCodeGenerator, frontend::Program};
+
+  /// Compile and run a program, the values it leaves on the stack
+  fn run(source: &str) -> anyhow::Result<Vec<Value>> {
+    let program = Program::from_source(source.to_string(), None)
+      .map_err(|report| anyhow::anyhow!("{report:?}"))?;
+
+    let mut generator = VMCodeGenerator::new();
+    generator.generate_byte_code(&program.ast)?;
+
+    let mut vm = VM::new().with_debug_info(Some(generator.debug_info(&program.source)));
+    vm.execute(generator.bytecode())?;
+
+    Ok(vm.stack().to_vec())
+  }
+
+  #[test]
+  fn test_procedures_call_and_return() {
+    let source = "\
+proc square i32 -- i32 do dup * end
+proc fourth i32 -- i32 do square square end
+proc add3 i32 i32 i32 -- i32 do + + end
+4 square 3 fourth 1 2 3 add3
+";
+
+    assert_eq!(
+      run(source).unwrap(),
+      [Value::Int(16), Value::Int(81), Value::Int(6)]
+    );
+  }
+
+  #[test]
+  fn test_jumps_land_on_their_target() {
+    assert_eq!(
+      run("5 while dup 0 > do 1 - end\n").unwrap(),
+      [Value::Int(0)]
+    );
+    assert_eq!(
+      run("0 while dup 3 < do 1 + end 7\n").unwrap(),
+      [Value::Int(3), Value::Int(7)]
+    );
+    assert_eq!(
+      run("1 2 < if 10 else 20 end 2 1 < if 30 else 40 end\n").unwrap(),
+      [Value::Int(10), Value::Int(40)]
+    );
+  }
+
+  #[test]
+  fn test_stack_operators() {
+    assert_eq!(
+      run("1 2 drop dup\n").unwrap(),
+      [Value::Int(1), Value::Int(1)]
+    );
+    assert_eq!(run("1 2 drop drop\n").unwrap(), []);
+  }
+
+  #[test]
+  fn test_mixed_comparisons() {
+    assert_eq!(
+      run("1 1.0 = 1 2.5 < 2 1.5 > 1 1.5 =\n").unwrap(),
+      [
+        Value::Bool(true),
+        Value::Bool(true),
+        Value::Bool(true),
+        Value::Bool(false)
+      ]
+    );
+    assert_eq!(
+      run("2.5 2 >= 2.0 2 <= 3 3.5 <>\n").unwrap(),
+      [Value::Bool(true), Value::Bool(true), Value::Bool(true)]
+    );
+  }
+
+  #[test]
+  fn test_range_loops() {
+    // The loops must keep the stack balanced, they add to a variable instead
+    let sum = |loops: &str| {
+      let source = format!("def(i32) total 0 @total\n{loops}\ntotal\n");
+      run(&source).unwrap()
+    };
+
+    assert_eq!(sum("3 range i do total i + @total end"), [Value::Int(3)]);
+    assert_eq!(
+      sum("5 8 range from n do total n + @total end"),
+      [Value::Int(18)]
+    );
+    assert_eq!(sum("0 range i do total 1 + @total end"), [Value::Int(0)]);
+
+    // Nested loops see their own counter: 00 + 01 + 10 + 11
+    assert_eq!(
+      sum("2 range i do 2 range j do total i 10 * j + + @total end end"),
+      [Value::Int(22)]
+    );
+  }
+
+  #[test]
+  fn test_runtime_errors_show_where_and_the_stack() {
+    let source = "7 1 2 +\n3 0 /\n";
+
+    match run(source).unwrap_err().downcast::<RuntimeError>().unwrap() {
+      RuntimeError::Located {
+        message,
+        extension_src: (offset, length),
+        stack,
+        ..
+      } => {
+        assert_eq!(message, "Divide by zero");
+        assert_eq!(&source[offset..offset + length], "/");
+        assert_eq!(stack, "Stack: [7 3 3 0]");
+      }
+      error => panic!("The error is not located: {error:?}"),
+    }
+
+    // Without the debug info only the instruction is known
+    let program = Program::from_source(source.to_string(), None).unwrap();
+    let mut generator = VMCodeGenerator::new();
+    generator.generate_byte_code(&program.ast).unwrap();
+
+    let error = VM::new().execute(generator.bytecode()).unwrap_err();
+    match error.downcast::<RuntimeError>().unwrap() {
+      RuntimeError::Unlocated { instruction, .. } => {
+        assert!(matches!(generator.bytecode()[instruction], ByteCode::Div))
+      }
+      error => panic!("The error is located without debug info: {error:?}"),
+    }
+  }
+
+  #[test]
+  fn test_conditions_must_be_bools() {
+    let mut vm = VM::new();
+    let error = vm
+      .execute(&[ByteCode::PushInt(1), ByteCode::JumpIfNotTrue(0)])
+      .unwrap_err();
+    match error.downcast::<RuntimeError>().unwrap() {
+      RuntimeError::Unlocated {
+        message,
+        instruction,
+        stack,
+      } => {
+        assert_eq!(message, "Jump if not true on 1, expected a bool");
+        assert_eq!(instruction, 1);
+        assert_eq!(stack, "Stack: [1]");
+      }
+      error => panic!("The error is located without debug info: {error:?}"),
+    }
+
+    assert!(VM::new().execute(&[ByteCode::JumpIfNotTrue(0)]).is_err());
+  }
+}
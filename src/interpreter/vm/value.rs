@@ -1,6 +1,9 @@
+use crate::lexer::tokens::Type;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
   Int(i32),
+  Int64(i64),
   Float32(f32),
   Float64(f64),
   Bool(bool),
@@ -11,6 +14,7 @@ impl PartialOrd for Value {
   fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
     match (self, other) {
       (Value::Int(lhs), Value::Int(rhs)) => lhs.partial_cmp(rhs),
+      (Value::Int64(lhs), Value::Int64(rhs)) => lhs.partial_cmp(rhs),
       (Value::Float32(lhs), Value::Float32(rhs)) => lhs.partial_cmp(rhs),
       (Value::Float64(lhs), Value::Float64(rhs)) => lhs.partial_cmp(rhs),
       (Value::Str(lhs), Value::Str(rhs)) => lhs.partial_cmp(rhs),
@@ -20,12 +24,79 @@ impl PartialOrd for Value {
   }
 }
 
+impl Value {
+  /// The value written the way it would be in a program, strings are quoted
+  pub fn to_literal(&self) -> String {
+    match self {
+      Value::Str(value) => format!("{value:?}"),
+      value => value.to_string(),
+    }
+  }
+
+  /// The numeric type of the value, strings and bools are not numbers
+  pub fn numeric_type(&self) -> Option<Type> {
+    match self {
+      Value::Int(_) => Some(Type::I32),
+      Value::Int64(_) => Some(Type::I64),
+      Value::Float32(_) => Some(Type::F32),
+      Value::Float64(_) => Some(Type::F64),
+      Value::Bool(_) | Value::Str(_) => None,
+    }
+  }
+
+  /// Numbers convert to any numeric type or to a bool (anything but zero is true), bools become
+  /// 0 or 1. These are the same rules the semantic analysis uses.
+  pub fn cast_to(&self, type_: &Type) -> anyhow::Result<Value> {
+    Ok(match (type_, self) {
+      (Type::I32, Value::Int(value)) => Value::Int(*value),
+      (Type::I32, Value::Int64(value)) => Value::Int(*value as i32),
+      (Type::I32, Value::Float32(value)) => Value::Int(*value as i32),
+      (Type::I32, Value::Float64(value)) => Value::Int(*value as i32),
+      (Type::I32, Value::Bool(value)) => Value::Int(*value as i32),
+
+      (Type::I64, Value::Int(value)) => Value::Int64(*value as i64),
+      (Type::I64, Value::Int64(value)) => Value::Int64(*value),
+      (Type::I64, Value::Float32(value)) => Value::Int64(*value as i64),
+      (Type::I64, Value::Float64(value)) => Value::Int64(*value as i64),
+      (Type::I64, Value::Bool(value)) => Value::Int64(*value as i64),
+
+      (Type::F32, Value::Int(value)) => Value::Float32(*value as f32),
+      (Type::F32, Value::Int64(value)) => Value::Float32(*value as f32),
+      (Type::F32, Value::Float32(value)) => Value::Float32(*value),
+      (Type::F32, Value::Float64(value)) => Value::Float32(*value as f32),
+      (Type::F32, Value::Bool(value)) => Value::Float32(*value as i32 as f32),
+
+      (Type::F64, Value::Int(value)) => Value::Float64(*value as f64),
+      (Type::F64, Value::Int64(value)) => Value::Float64(*value as f64),
+      (Type::F64, Value::Float32(value)) => Value::Float64(*value as f64),
+      (Type::F64, Value::Float64(value)) => Value::Float64(*value),
+      (Type::F64, Value::Bool(value)) => Value::Float64(*value as i32 as f64),
+
+      (Type::Bool, Value::Int(value)) => Value::Bool(*value != 0),
+      (Type::Bool, Value::Int64(value)) => Value::Bool(*value != 0),
+      (Type::Bool, Value::Float32(value)) => Value::Bool(*value != 0.0),
+      (Type::Bool, Value::Float64(value)) => Value::Bool(*value != 0.0),
+      (Type::Bool, Value::Bool(value)) => Value::Bool(*value),
+
+      (_, Value::Str(_)) => {
+        return Err(anyhow::anyhow!("Can't cast a string to {}: {}", type_, self));
+      }
+    })
+  }
+}
+
 impl From<i32> for Value {
   fn from(value: i32) -> Self {
     Self::Int(value)
   }
 }
 
+impl From<i64> for Value {
+  fn from(value: i64) -> Self {
+    Self::Int64(value)
+  }
+}
+
 impl From<f32> for Value {
   fn from(value: f32) -> Self {
     Self::Float32(value)
@@ -54,10 +125,69 @@ impl std::fmt::Display for Value {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match self {
       Value::Int(value) => write!(f, "{}", value),
-      Value::Float32(value) => write!(f, "{}", value),
-      Value::Float64(value) => write!(f, "{}", value),
+      Value::Int64(value) => write!(f, "{}", value),
+      Value::Float32(value) => write!(f, "{}", format_float(*value as f64)),
+      Value::Float64(value) => write!(f, "{}", format_float(*value)),
       Value::Bool(value) => write!(f, "{}", value),
       Value::Str(value) => write!(f, "{}", value),
     }
   }
 }
+
+/// Write a float like C's `%g`, the format the native executables dump floats with: 6 significant
+/// digits without the trailing zeros, in scientific notation below 1e-4 and from 1e6 on
+fn format_float(value: f64) -> String {
+  if !value.is_finite() {
+    let sign = if value.is_sign_negative() { "-" } else { "" };
+    let name = if value.is_nan() { "nan" } else { "inf" };
+    return format!("{sign}{name}");
+  }
+
+  let trim = |digits: &str| match digits.contains('.') {
+    true => digits
+      .trim_end_matches('0')
+      .trim_end_matches('.')
+      .to_string(),
+    false => digits.to_string(),
+  };
+
+  // The exponent once rounded to 6 digits picks the notation
+  let scientific = format!("{:.5e}", value);
+  let (mantissa, exponent) = scientific.split_once('e').unwrap();
+  let exponent: i32 = exponent.parse().unwrap();
+
+  if (-4..6).contains(&exponent) {
+    trim(&format!("{:.*}", (5 - exponent) as usize, value))
+  } else {
+    let sign = if exponent < 0 { '-' } else { '+' };
+    format!("{}e{}{:02}", trim(mantissa), sign, exponent.abs())
+  }
+}
+
+#[cfg(test)]
+mod value_tests {
+  use super::*;
+
+  #[test]
+  fn test_floats_are_written_like_printf() {
+    let written = [
+      (0.1, "0.1"),
+      (3.0, "3"),
+      (-0.0, "-0"),
+      (123456.5, "123456"),
+      (999999.5, "1e+06"),
+      (1234567.5, "1.23457e+06"),
+      (0.0001, "0.0001"),
+      (0.00001234565, "1.23456e-05"),
+      (1e300, "1e+300"),
+      (f64::INFINITY, "inf"),
+      (f64::NEG_INFINITY, "-inf"),
+    ];
+
+    for (value, expected) in written {
+      assert_eq!(Value::Float64(value).to_string(), expected);
+    }
+    assert_eq!(Value::Float32(0.1).to_string(), "0.1");
+    assert_eq!(Value::Float32(16777216.0).to_string(), "1.67772e+07");
+  }
+}
@@ -19,4 +19,17 @@ pub enum LexerError {
     #[label("This bit here")]
     extension_src: (usize, usize),
   },
+
+  #[error("Invalid pragma")]
+  #[diagnostic(code(lexer_error::invalid_pragma))]
+  InvalidPragma {
+    #[source_code]
+    input: String,
+
+    #[help]
+    advice: String,
+
+    #[label("This pragma")]
+    extension_src: (usize, usize),
+  },
 }
@@ -4,6 +4,7 @@ use self::tokens::Token;
 
 pub mod errors;
 pub mod generate;
+pub mod pragma;
 pub mod tokens;
 
 #[derive(Debug, Clone)]
@@ -0,0 +1,82 @@
+//! Settings written in the source, as comments so the other tools ignore them:
+//!
+//! ```text
+//! \ pragma stack-size 1024
+//! ```
+//!
+//! Only the known names are pragmas, any other comment starting with `pragma` is left alone.
+
+use miette::Result as MietteResult;
+
+use super::errors::LexerError;
+
+const PRAGMA_PREFIX: &str = "pragma";
+const STACK_SIZE: &str = "stack-size";
+
+/// The settings of a program, `None` when the source doesn't set them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pragmas {
+  /// How many values the stack holds
+  pub stack_size: Option<usize>,
+}
+
+pub fn parse(input: &str) -> MietteResult<Pragmas> {
+  let mut pragmas = Pragmas::default();
+
+  let mut offset = 0;
+  for line in input.split_inclusive('\n') {
+    let start = offset;
+    offset += line.len();
+
+    let Some(comment) = line.trim_start().strip_prefix('\\') else {
+      continue;
+    };
+    let mut words = comment.split_whitespace();
+    if words.next() != Some(PRAGMA_PREFIX) || words.next() != Some(STACK_SIZE) {
+      continue;
+    }
+
+    let span = (start, line.trim_end().len());
+    let error = |advice: String| LexerError::InvalidPragma {
+      input: input.to_string(),
+      advice,
+      extension_src: span,
+    };
+
+    match (words.next(), words.next()) {
+      (Some(value), None) => match value.parse::<usize>() {
+        Ok(size) if size > 0 => pragmas.stack_size = Some(size),
+        _ => Err(error(format!(
+          "The stack size must be a positive number, found `{}`",
+          value
+        )))?,
+      },
+      _ => Err(error(
+        "Expected `pragma stack-size <number of values>`".to_string(),
+      ))?,
+    }
+  }
+
+  Ok(pragmas)
+}
+
+#[cfg(test)]
+mod pragma_tests {
+  use super::*;
+
+  #[test]
+  fn test_stack_size() {
+    let source = "\\ a comment\n\\ pragma stack-size 128\n1 dump\n";
+    assert_eq!(parse(source).unwrap().stack_size, Some(128));
+
+    assert_eq!(parse("1 dump\n").unwrap(), Pragmas::default());
+    assert!(parse("\\ pragma stack-size none\n").is_err());
+    assert!(parse("\\ pragma stack-size\n").is_err());
+
+    // Comments that only look like pragmas
+    assert_eq!(
+      parse("\\ pragma heap-size 1\n\\ pragmatic\n").unwrap(),
+      Pragmas::default()
+    );
+  }
+}
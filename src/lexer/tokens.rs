@@ -1,24 +1,65 @@
 use logos::{Lexer, Logos};
+use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum Type {
   I32,
   I64,
   F32,
   F64,
+  Bool,
+}
+
+impl Type {
+  pub fn from_name(name: &str) -> Option<Type> {
+    match name {
+      "i32" => Some(Type::I32),
+      "i64" => Some(Type::I64),
+      "f32" => Some(Type::F32),
+      "f64" => Some(Type::F64),
+      "bool" => Some(Type::Bool),
+      _ => None,
+    }
+  }
+}
+
+/// The numeric types from the narrowest to the widest, the operands of an arithmetic operation
+/// are promoted to the widest of their two types
+const PROMOTION_ORDER: [Type; 4] = [Type::I32, Type::I64, Type::F32, Type::F64];
+
+impl Type {
+  pub fn is_numeric(&self) -> bool {
+    PROMOTION_ORDER.contains(self)
+  }
+
+  /// The type both operands of an arithmetic operation are converted to, if both are numbers
+  pub fn promote(&self, other: &Type) -> Option<Type> {
+    let rank = |type_: &Type| PROMOTION_ORDER.iter().position(|numeric| numeric == type_);
+
+    Some(PROMOTION_ORDER[rank(self)?.max(rank(other)?)].clone())
+  }
+}
+
+impl Display for Type {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Type::I32 => write!(f, "i32"),
+      Type::I64 => write!(f, "i64"),
+      Type::F32 => write!(f, "f32"),
+      Type::F64 => write!(f, "f64"),
+      Type::Bool => write!(f, "bool"),
+    }
+  }
 }
 
 fn def_type(lex: &mut Lexer<Token>) -> Option<Type> {
   let slice = lex.slice();
-  let slice = &slice[4..slice.len() - 1];
-  match slice {
-    "i32" => Some(Type::I32),
-    "i64" => Some(Type::I64),
-    "f32" => Some(Type::F32),
-    "f64" => Some(Type::F64),
-    _ => None,
-  }
+  Type::from_name(&slice[4..slice.len() - 1])
+}
+
+fn to_type(lex: &mut Lexer<Token>) -> Option<Type> {
+  Type::from_name(lex.slice())
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -165,17 +206,28 @@ pub enum Token {
   #[token("range")]
   Range,
 
+  /// The range also takes its start from the stack (range from)
+  #[token("from")]
+  From,
+
+  #[token("proc")]
+  Proc,
+
+  /// Separates the inputs from the outputs in a procedure signature (i32 i32 -- i32)
+  #[token("--")]
+  SignatureSeparator,
+
   /// Types
-  #[regex("i32|i64|f32|f64")]
-  Types,
+  #[regex("i32|i64|f32|f64|bool", to_type)]
+  Types(Type),
 
   /// Def Type (def(i32))
   #[regex("def\\((i32|i64|f32|f64)\\)", def_type)]
   DefType(Type),
 
   /// Identifiers
-  #[regex(r"[a-zA-Z_][a-zA-Z0-9_]*")]
-  Identifier,
+  #[regex(r"[a-zA-Z_][a-zA-Z0-9_]*", |lex| lex.slice().to_string())]
+  Identifier(String),
 
   /// End of input
   #[regex(r"\$")]
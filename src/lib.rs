@@ -1,6 +1,7 @@
 #![feature(const_trait_impl)]
 
 pub mod codegen;
+pub mod frontend;
 pub mod grammar;
 pub mod interpreter;
 pub mod lexer;
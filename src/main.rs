@@ -8,6 +8,11 @@ fn main() -> MietteResult<(), Box<dyn std::error::Error>> {
   match &cli.command {
     Commands::Compile(opts) => PileCompiler::compile(opts)?,
     Commands::Run(opts) => PileCompiler::run(opts)?,
+    Commands::Repl(opts) => PileCompiler::repl(opts)?,
+    Commands::Debug(opts) => PileCompiler::debug(opts)?,
+    Commands::Disasm(opts) => PileCompiler::disasm(opts)?,
+    Commands::Asm(opts) => PileCompiler::asm(opts)?,
+    Commands::Grammar(opts) => PileCompiler::grammar(opts)?,
   }
 
   Ok(())
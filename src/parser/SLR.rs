@@ -1,32 +1,100 @@
 use std::collections::HashMap;
 
+use serde::{Deserialize, Serialize};
+
 use crate::grammar::{production::Production, Grammar, Symbol};
 
-use super::{closure::ClosureItem, Action};
+use super::{closure::ClosureItem, conflicts::Conflict, lr1, Action};
+
+/// How the states of the automaton and the lookaheads of its reductions are found
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TableKind {
+  /// The LR(0) states, reducing on the FOLLOW set of the production
+  #[default]
+  Slr,
+  /// The LR(0) states, reducing on the lookaheads of the LR(1) states they merge
+  Lalr,
+  /// The LR(1) states, as many as the lookaheads tell apart
+  Lr1,
+}
+
+impl std::fmt::Display for TableKind {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      TableKind::Slr => write!(f, "SLR"),
+      TableKind::Lalr => write!(f, "LALR(1)"),
+      TableKind::Lr1 => write!(f, "LR(1)"),
+    }
+  }
+}
+
+impl std::str::FromStr for TableKind {
+  type Err = String;
+
+  fn from_str(name: &str) -> Result<Self, Self::Err> {
+    match name {
+      "slr" => Ok(TableKind::Slr),
+      "lalr" => Ok(TableKind::Lalr),
+      "lr1" => Ok(TableKind::Lr1),
+      _ => Err(format!(
+        "Unknown parsing tables {name}, expected slr, lalr or lr1"
+      )),
+    }
+  }
+}
+
+impl TableKind {
+  /// The tables a grammar declares with `%tables`, SLR ones when it doesn't
+  pub fn declared(grammar: &Grammar) -> Result<Self, String> {
+    grammar
+      .tables
+      .as_deref()
+      .map_or(Ok(TableKind::default()), str::parse)
+  }
+}
 
 #[allow(dead_code)]
 pub struct SLR {
   pub grammar: Grammar,
+  pub kind: TableKind,
   pub action_table: HashMap<(usize, Symbol), Action>,
   pub goto_table: HashMap<(usize, Symbol), usize>,
-  pub closure_set: HashMap<Vec<Production>, ClosureItem>,
+  /// The states of the automaton, by id
+  pub states: Vec<ClosureItem>,
+  /// The conflicts found while building the tables, by state
+  pub conflicts: Vec<Conflict>,
 }
 
 impl SLR {
   pub fn new(grammar: Grammar) -> SLR {
-    // Build the first closure set I0
-    let mut kernel = Vec::new();
-    let first_production: Production = (grammar.productions[0].clone()).into();
-    kernel.push(first_production.add_dot());
+    SLR::with_kind(grammar, TableKind::Slr)
+  }
+
+  pub fn with_kind(grammar: Grammar, kind: TableKind) -> SLR {
+    let states = match kind {
+      TableKind::Slr => {
+        // Build the first closure set I0
+        let mut kernel = Vec::new();
+        let first_production: Production = (grammar.productions[0].clone()).into();
+        kernel.push(first_production.add_dot());
 
-    let mut i0 = ClosureItem::new(kernel, &grammar, 0);
-    let closure_set = i0.populate(&grammar);
+        let mut i0 = ClosureItem::new(kernel, &grammar, 0);
+        let mut states = i0.populate(&grammar).into_values().collect::<Vec<_>>();
+        states.sort();
+
+        states
+      }
+      TableKind::Lalr => lr1::lalr_states(&grammar),
+      TableKind::Lr1 => lr1::lr1_states(&grammar),
+    };
 
     let mut slr = SLR {
       grammar,
-      closure_set,
+      kind,
+      states,
       action_table: HashMap::new(),
       goto_table: HashMap::new(),
+      conflicts: Vec::new(),
     };
 
     slr.build_tables();
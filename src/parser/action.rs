@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+  Shift(usize),  // shift to the state with the given index
+  Reduce(usize), // reduce using the production with the given index
+  Accept,        // accept the input
+  Error,         // error
+}
@@ -1,6 +1,6 @@
 use std::{
   cell::RefCell,
-  collections::{HashMap, VecDeque},
+  collections::{HashMap, HashSet, VecDeque},
   fmt::Display,
   hash::Hash,
   rc::Rc,
@@ -17,6 +17,8 @@ pub struct ClosureItem {
   pub kernel: Vec<Production>,
   pub productions: Vec<Production>,
   pub transitions: HashMap<Symbol, usize>,
+  /// The terminals that may follow each item, only known in the LR(1) and LALR(1) states
+  pub lookaheads: HashMap<Production, HashSet<Symbol>>,
 }
 
 impl PartialOrd for ClosureItem {
@@ -49,6 +51,12 @@ impl Display for ClosureItem {
       for symbol in rhs {
         write!(f, "{} ", symbol)?;
       }
+
+      if let Some(lookaheads) = self.lookaheads.get(production) {
+        let mut lookaheads = lookaheads.iter().map(Symbol::get_name).collect::<Vec<_>>();
+        lookaheads.sort();
+        write!(f, "[{}]", lookaheads.join(" "))?;
+      }
       writeln!(f)?;
     }
 
@@ -73,6 +81,7 @@ impl ClosureItem {
       kernel,
       productions,
       transitions: HashMap::new(),
+      lookaheads: HashMap::new(),
     };
 
     closure.expand(grammar).clone()
@@ -90,6 +99,7 @@ impl ClosureItem {
       kernel,
       productions,
       transitions: HashMap::new(),
+      lookaheads: HashMap::new(),
     }));
 
     closure.borrow_mut().expand(grammar);
@@ -0,0 +1,80 @@
+use std::fmt::Display;
+
+use crate::grammar::{production::Production, Symbol};
+
+use super::Action;
+
+/// A state of the automaton with more than one action for a lookahead. The tables keep the last
+/// action, so a shift wins over a reduction and a later production over an earlier one.
+#[derive(Debug, Clone)]
+pub struct Conflict {
+  pub state: usize,
+  pub lookahead: Symbol,
+  /// The competing actions with the production they come from: the production a reduction
+  /// reduces, or the item that shifts the lookahead
+  pub actions: Vec<(Action, Production)>,
+  /// The items of the state
+  pub items: Vec<Production>,
+}
+
+impl Conflict {
+  pub fn kind(&self) -> &'static str {
+    match self
+      .actions
+      .iter()
+      .any(|(action, _)| matches!(action, Action::Shift(_)))
+    {
+      true => "shift/reduce",
+      false => "reduce/reduce",
+    }
+  }
+}
+
+impl Display for Conflict {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    writeln!(
+      f,
+      "{} conflict in state {} on `{}`",
+      self.kind(),
+      self.state,
+      self.lookahead
+    )?;
+
+    for (action, production) in &self.actions {
+      let action = match action {
+        Action::Shift(state) => format!("shift to state {}", state),
+        Action::Reduce(_) => "reduce".to_string(),
+        Action::Accept => "accept".to_string(),
+        Action::Error => "error".to_string(),
+      };
+
+      writeln!(f, "  {:<20}{}", action, production)?;
+    }
+
+    writeln!(f, "  items of the state:")?;
+    for item in &self.items {
+      writeln!(f, "    {}", item)?;
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod conflicts_tests {
+  use crate::parser::{SLR::TableKind, embedded::from_grammar};
+
+  #[test]
+  fn test_conflicts() {
+    let ambiguous = from_grammar("<E> -> <E> plus <E> | id;", Some(TableKind::Slr)).unwrap();
+    assert!(!ambiguous.conflicts.is_empty());
+    assert!(
+      ambiguous.conflicts.iter().all(
+        |conflict| conflict.kind() == "shift/reduce" && conflict.lookahead.get_name() == "plus"
+      )
+    );
+
+    let unambiguous = from_grammar("<E> -> <E> plus id | id;", Some(TableKind::Slr)).unwrap();
+    assert!(unambiguous.conflicts.is_empty());
+  }
+}
@@ -0,0 +1,97 @@
+//! The grammar of the language, embedded in the binary with the parsing tables the build script
+//! made from it
+
+use miette::Result as MietteResult;
+
+use crate::grammar;
+
+use super::{
+  tables::ParsingTables,
+  SLR::{TableKind, SLR},
+};
+
+/// The grammar the compiler was built with
+pub const GRAMMAR: &str = include_str!("../../assets/glc/lang.glc");
+
+const TABLES: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/tables.bin"));
+
+/// The parser of the embedded grammar, or of the one in `grammar_file` (`--grammar`), whose
+/// tables are built on the spot
+pub fn parser(grammar_file: Option<&str>) -> MietteResult<SLR> {
+  let Some(grammar_file) = grammar_file else {
+    let tables =
+      ParsingTables::decode(TABLES).expect("The parsing tables of the build script are corrupted");
+    return Ok(tables.into());
+  };
+
+  let glc_contents = std::fs::read_to_string(grammar_file)
+    .map_err(|e| miette::miette!("Could not read the grammar {}: {}", grammar_file, e))?;
+
+  let slr = from_grammar(&glc_contents, None)?;
+  // Like the build script does for the embedded grammar, the parser would silently keep the
+  // last action of each conflict
+  if !slr.conflicts.is_empty() {
+    eprintln!(
+      "Warning: {} has {} parsing conflicts, `grammar check {}` shows them",
+      grammar_file,
+      slr.conflicts.len(),
+      grammar_file
+    );
+  }
+
+  Ok(slr)
+}
+
+/// Build the tables of a grammar written like `assets/glc/lang.glc`, of the `kind` the grammar
+/// declares with `%tables` unless another one is given
+pub fn from_grammar(glc_contents: &str, kind: Option<TableKind>) -> MietteResult<SLR> {
+  let mut glc = grammar::parser::parse(glc_contents).map_err(|e| miette::miette!("{}", e))?;
+  let kind = match kind {
+    Some(kind) => kind,
+    None => TableKind::declared(&glc).map_err(|e| miette::miette!("{}", e))?,
+  };
+
+  glc.compute_follow_set().expand();
+
+  Ok(SLR::with_kind(glc, kind))
+}
+
+#[cfg(test)]
+mod embedded_tests {
+  use super::*;
+  use crate::lexer::generate::compute_tokens;
+
+  #[test]
+  fn test_embedded_tables_parse_like_the_grammar() {
+    let source = "proc square i32 -- i32 do dup * end\n3 range i do i square dump end\n";
+    let parse = |slr: SLR| {
+      let tokens = compute_tokens(source).unwrap();
+      slr.parse(tokens, source).unwrap().unwrap().to_string()
+    };
+
+    assert_eq!(
+      parse(parser(None).unwrap()),
+      parse(from_grammar(GRAMMAR, None).unwrap())
+    );
+  }
+
+  #[test]
+  fn test_tables_directive() {
+    // LALR(1) but not SLR: the FOLLOW set of <R> has `=`, where <S> -> <L> = <R> must shift
+    let glc = "<S> -> <L> eq <R> | <R>; <L> -> star <R> | id; <R> -> <L>;";
+    assert_eq!(from_grammar(glc, None).unwrap().kind, TableKind::Slr);
+    assert!(!from_grammar(glc, None).unwrap().conflicts.is_empty());
+
+    let lalr = from_grammar(&format!("%tables lalr;\n{glc}"), None).unwrap();
+    assert_eq!(lalr.kind, TableKind::Lalr);
+    assert!(lalr.conflicts.is_empty());
+
+    // The kind asked for wins over the declared one
+    let lr1 = from_grammar(&format!("%tables lalr;\n{glc}"), Some(TableKind::Lr1)).unwrap();
+    assert_eq!(lr1.kind, TableKind::Lr1);
+
+    assert!(from_grammar(&format!("%tables lr0;\n{glc}"), None).is_err());
+    assert!(from_grammar(&format!("%tables;\n{glc}"), None).is_err());
+    assert!(from_grammar(&format!("%tables slr lalr;\n{glc}"), None).is_err());
+  }
+}
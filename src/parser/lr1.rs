@@ -0,0 +1,221 @@
+//! The states of the canonical LR(1) automaton, whose items carry the terminals that may follow
+//! them, and of the LALR(1) automaton, which merges the LR(1) states that have the same items
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::grammar::{production::Production, Grammar, Symbol};
+
+use super::closure::ClosureItem;
+
+/// An item and the terminals that may follow it
+type Item = (Production, HashSet<Symbol>);
+
+impl ClosureItem {
+  pub fn new_lr1(kernel: Vec<Item>, grammar: &Grammar, id: usize) -> ClosureItem {
+    let productions = kernel
+      .iter()
+      .map(|(production, _)| production.clone())
+      .collect::<Vec<_>>();
+
+    let mut closure = ClosureItem {
+      id,
+      kernel: productions.clone(),
+      productions,
+      transitions: HashMap::new(),
+      lookaheads: kernel.into_iter().collect(),
+    };
+
+    closure.expand_lr1(grammar);
+
+    closure
+  }
+
+  /// Add the items of the non terminals after the dots until nothing changes. The item
+  /// `A -> α . B β` with the lookahead `a` adds the items `B -> . γ` with what starts `β a`.
+  fn expand_lr1(&mut self, grammar: &Grammar) {
+    let mut changed = true;
+
+    while changed {
+      changed = false;
+
+      for production in self.productions.clone() {
+        let Some(next_symbol @ Symbol::NonTerminal(_)) = production.next_symbol_after_dot() else {
+          continue;
+        };
+
+        let dot = production.rhs.iter().position(Symbol::is_dot).unwrap();
+        let rest = grammar.first_of_sequence(&production.rhs[dot + 2..]);
+
+        let mut lookaheads = rest
+          .iter()
+          .filter(|symbol| !symbol.is_empty())
+          .cloned()
+          .collect::<HashSet<_>>();
+        if rest.contains(&Symbol::Empty) {
+          lookaheads.extend(self.lookaheads[&production].iter().cloned());
+        }
+
+        for new_production in grammar.get_production(&next_symbol).unwrap_or_default() {
+          let item = Production::from(new_production).add_dot();
+
+          if !self.productions.contains(&item) {
+            self.productions.push(item.clone());
+            changed = true;
+          }
+
+          let item_lookaheads = self.lookaheads.entry(item).or_default();
+          let size = item_lookaheads.len();
+          item_lookaheads.extend(lookaheads.iter().cloned());
+          changed |= item_lookaheads.len() != size;
+        }
+      }
+    }
+  }
+
+  /// The kernel of the state reached with `symbol`, the items keep their lookaheads
+  fn goto_lr1(&self, symbol: &Symbol) -> Vec<Item> {
+    self
+      .find_goto_productions(symbol.clone())
+      .into_iter()
+      .map(|production| {
+        let lookaheads = self.lookaheads[&production].clone();
+        (production.forward_dot(), lookaheads)
+      })
+      .collect()
+  }
+}
+
+/// Identifies an LR(1) state by its kernel, whatever the order of the items
+fn state_key(kernel: &[Item]) -> Vec<(String, Vec<String>)> {
+  let mut key = kernel
+    .iter()
+    .map(|(production, lookaheads)| {
+      let mut lookaheads = lookaheads.iter().map(Symbol::get_name).collect::<Vec<_>>();
+      lookaheads.sort();
+      (production.to_string(), lookaheads)
+    })
+    .collect::<Vec<_>>();
+
+  key.sort();
+  key
+}
+
+/// The states of the canonical LR(1) automaton, by id
+pub fn lr1_states(grammar: &Grammar) -> Vec<ClosureItem> {
+  let start: Item = (
+    Production::from(grammar.productions[0].clone()).add_dot(),
+    HashSet::from([Symbol::End]),
+  );
+
+  let kernel = vec![start];
+
+  let mut ids = HashMap::from([(state_key(&kernel), 0)]);
+  let mut states = vec![ClosureItem::new_lr1(kernel, grammar, 0)];
+
+  let mut queue = VecDeque::from([0]);
+  while let Some(id) = queue.pop_front() {
+    // The symbols after the dots, in the order of the items
+    let mut symbols = Vec::new();
+    for production in &states[id].productions {
+      match production.next_symbol_after_dot() {
+        None | Some(Symbol::Empty) => {}
+        Some(symbol) if symbols.contains(&symbol) => {}
+        Some(symbol) => symbols.push(symbol),
+      }
+    }
+
+    for symbol in symbols {
+      let kernel = states[id].goto_lr1(&symbol);
+      let key = state_key(&kernel);
+
+      let target = match ids.get(&key) {
+        Some(target) => *target,
+        None => {
+          let target = states.len();
+          states.push(ClosureItem::new_lr1(kernel, grammar, target));
+          ids.insert(key, target);
+          queue.push_back(target);
+
+          target
+        }
+      };
+
+      states[id].transitions.insert(symbol, target);
+    }
+  }
+
+  states
+}
+
+/// The states of the LALR(1) automaton: the LR(1) states with the same items are merged and
+/// their lookaheads are put together
+pub fn lalr_states(grammar: &Grammar) -> Vec<ClosureItem> {
+  let lr1 = lr1_states(grammar);
+
+  // The merged state of every LR(1) state, numbered in the order they first appear
+  let mut cores: HashMap<Vec<String>, usize> = HashMap::new();
+  let mut merged = Vec::with_capacity(lr1.len());
+  let mut states: Vec<ClosureItem> = Vec::new();
+
+  for state in &lr1 {
+    let mut core = state
+      .kernel
+      .iter()
+      .map(Production::to_string)
+      .collect::<Vec<_>>();
+    core.sort();
+
+    let id = *cores.entry(core).or_insert_with(|| {
+      let id = states.len();
+      states.push(ClosureItem {
+        id,
+        transitions: HashMap::new(),
+        lookaheads: HashMap::new(),
+        ..state.clone()
+      });
+
+      id
+    });
+    merged.push(id);
+
+    for (production, lookaheads) in &state.lookaheads {
+      states[id]
+        .lookaheads
+        .entry(production.clone())
+        .or_default()
+        .extend(lookaheads.iter().cloned());
+    }
+  }
+
+  for state in &lr1 {
+    for (symbol, target) in &state.transitions {
+      states[merged[state.id]]
+        .transitions
+        .insert(symbol.clone(), merged[*target]);
+    }
+  }
+
+  states
+}
+
+#[cfg(test)]
+mod lr1_tests {
+  use crate::parser::{embedded::from_grammar, SLR::TableKind};
+
+  #[test]
+  fn test_lalr_and_lr1_states() {
+    // SLR reduces `<R> -> <L>` on `eq` after `<L>`, since `eq` follows the `<L>` in `<L> eq <R>`
+    let glc = "<S> -> <L> eq <R> | <R>; <L> -> star <R> | id; <R> -> <L>;";
+
+    let slr = from_grammar(glc, Some(TableKind::Slr)).unwrap();
+    let lalr = from_grammar(glc, Some(TableKind::Lalr)).unwrap();
+    let lr1 = from_grammar(glc, Some(TableKind::Lr1)).unwrap();
+
+    assert!(!slr.conflicts.is_empty());
+    assert!(lalr.conflicts.is_empty());
+    assert!(lr1.conflicts.is_empty());
+
+    assert_eq!(lalr.states.len(), slr.states.len());
+    assert!(lr1.states.len() > lalr.states.len());
+  }
+}
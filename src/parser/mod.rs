@@ -1,14 +1,13 @@
 #[allow(non_snake_case)]
 pub mod SLR;
+pub mod action;
 pub mod closure;
+pub mod conflicts;
+pub mod embedded;
+pub mod lr1;
 pub mod parsing_table;
 pub mod parse;
 pub mod errors;
+pub mod tables;
 
-#[derive(Debug, Clone)]
-pub enum Action {
-  Shift(usize),  // shift to the state with the given index
-  Reduce(usize), // reduce using the production with the given index
-  Accept,        // accept the input
-  Error,         // error
-}
+pub use action::Action;
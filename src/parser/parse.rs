@@ -5,7 +5,7 @@ use std::{collections::VecDeque, fmt::Display};
 use crate::{
   grammar::Symbol,
   lexer::{
-    tokens::{span_to_tuple, Token},
+    tokens::{span_to_tuple, Token, Type},
     PileToken,
   },
   parser::{errors::ParseError, Action},
@@ -98,10 +98,11 @@ impl SLR {
           Symbol::Terminal(current_token.get_token_type_only())
         };
 
+        // A missing entry is a syntax error like an explicit one
         let action = self
           .action_table
-          .get(&(*state, symbol.clone()))
-          .unwrap_or_else(|| panic!("No action for state {} and symbol {:?}", state, symbol));
+          .get(&(*state, symbol))
+          .unwrap_or(&Action::Error);
 
         match action {
           Action::Shift(shift_state) => {
@@ -118,8 +119,11 @@ impl SLR {
           Action::Reduce(reduce_state) => {
             let (lhs, rhs) = &self.grammar.productions[*reduce_state];
 
+            // ε doesn't take any place in the stacks
+            let rhs_len = rhs.iter().filter(|symbol| !symbol.is_empty()).count();
+
             // Normal stack
-            let mut to_pop = rhs.len() * 2;
+            let mut to_pop = rhs_len * 2;
             while to_pop > 0 {
               stack.pop_back();
               to_pop -= 1;
@@ -127,7 +131,7 @@ impl SLR {
 
             // Parse stack
             let mut children = Vec::new();
-            for _ in 0..rhs.len() {
+            for _ in 0..rhs_len {
               let node = parse_stack.pop().unwrap();
               children.push(node);
             }
@@ -222,6 +226,74 @@ impl<'a> AstNode {
     AstNodeIter { nodes: vec![self] }
   }
 
+  /// The name of a procedure definition, which is its first child
+  pub fn proc_name(&self) -> Option<&str> {
+    match (&self.token, self.children.first()) {
+      (Token::Proc, Some(AstNode {
+        token: Token::Identifier(name),
+        ..
+      })) => Some(name),
+      _ => None,
+    }
+  }
+
+  /// The name given to the counter of a range loop, if any
+  pub fn range_counter(&self) -> Option<&str> {
+    match &self.token {
+      Token::Range => self.children.iter().find_map(|child| match &child.token {
+        Token::Identifier(name) => Some(name.as_str()),
+        _ => None,
+      }),
+      _ => None,
+    }
+  }
+
+  /// The name of the variable a declaration (`def(type) name`) or an assignment (`@name`) refers
+  /// to
+  pub fn variable_name(&self) -> Option<&str> {
+    match (&self.token, self.children.first()) {
+      (Token::DefType(_) | Token::AtSign, Some(AstNode {
+        token: Token::Identifier(name),
+        ..
+      })) => Some(name),
+      _ => None,
+    }
+  }
+
+  /// The type the top of the stack is converted to by a cast (`:: type`)
+  pub fn cast_type(&self) -> Option<&Type> {
+    match (&self.token, self.children.first()) {
+      (Token::CastOp, Some(AstNode {
+        token: Token::Types(type_),
+        ..
+      })) => Some(type_),
+      _ => None,
+    }
+  }
+
+  /// Whether the range loop takes its start from the stack (`range from`) or starts at zero
+  pub fn range_has_start(&self) -> bool {
+    self.token == Token::Range && self.children.iter().any(|child| child.token == Token::From)
+  }
+
+  /// The declared stack effect of a procedure definition, as (inputs, outputs)
+  pub fn proc_signature(&self) -> (Vec<Type>, Vec<Type>) {
+    let mut inputs = Vec::new();
+    let mut outputs = Vec::new();
+    let mut after_separator = false;
+
+    for child in self.children.iter().skip(1) {
+      match &child.token {
+        Token::SignatureSeparator => after_separator = true,
+        Token::Types(type_) if after_separator => outputs.push(type_.clone()),
+        Token::Types(type_) => inputs.push(type_.clone()),
+        _ => {}
+      }
+    }
+
+    (inputs, outputs)
+  }
+
   pub fn detailed_iter(&'a self) -> AstNodeDetailedIter<'a> {
     AstNodeDetailedIter {
       stack: vec![(self, 0, true)],
@@ -277,6 +349,84 @@ impl Display for AstNode {
   }
 }
 
+/// Collect the terminals below a node, from left to right
+fn terminals(node: &ParseTreeNode) -> Vec<(&Token, (usize, usize))> {
+  match node {
+    ParseTreeNode::Terminal(token, span) => vec![(token, *span)],
+    ParseTreeNode::NonTerminal(_, children, _) => children.iter().flat_map(terminals).collect(),
+  }
+}
+
+fn leaf(token: &Token, span: (usize, usize)) -> AstNode {
+  AstNode {
+    symbol: Symbol::Terminal(token.to_string()),
+    children: Vec::new(),
+    token: token.clone(),
+    span,
+  }
+}
+
+/// Build a node from the first terminal of a header, the terminals after it become its children
+/// (e.g. `DefType Identifier` or `AtSign Identifier`)
+fn header_node(header: &[ParseTreeNode]) -> AstNode {
+  let mut terminals = header.iter().flat_map(terminals);
+  let (token, span) = terminals
+    .next()
+    .expect("A header starts with a terminal");
+
+  AstNode {
+    symbol: Symbol::Terminal(token.to_string()),
+    children: terminals.map(|(token, span)| leaf(token, span)).collect(),
+    token: token.clone(),
+    span,
+  }
+}
+
+/// Build the node of `Range [From] <optional-id>`, the modifiers become the children of the node.
+/// The body is parsed as any other sequence of statements.
+fn range_statement(children: &[ParseTreeNode]) -> (AstNode, usize) {
+  let header_len = children
+    .iter()
+    .position(|child| matches!(child, ParseTreeNode::Terminal(Token::Do, _)))
+    .unwrap_or(children.len());
+
+  (header_node(&children[..header_len]), header_len)
+}
+
+/// Build the node of `Proc Identifier <proc-signature>`, the name and the signature become the
+/// children of the node. The body is parsed as any other sequence of statements.
+fn proc_definition(children: &[ParseTreeNode]) -> MietteResult<AstNode> {
+  let header = children
+    .iter()
+    .take(3)
+    .flat_map(terminals)
+    .collect::<Vec<_>>();
+
+  match header.as_slice() {
+    [(Token::Proc, span), name @ (Token::Identifier(proc_name), _), signature @ ..] => Ok(AstNode {
+      symbol: Symbol::Terminal(proc_name.to_string()),
+      children: std::iter::once(name)
+        .chain(signature.iter())
+        .map(|(token, span)| leaf(token, *span))
+        .collect(),
+      token: Token::Proc,
+      span: *span,
+    }),
+    _ => Err(ParseError::UnexpectedToken {
+      input: Token::Proc.to_string(),
+      extension_src: (0, 0),
+      advice: "Malformed procedure definition".to_string(),
+    })?,
+  }
+}
+
+/// The operands of an operator are the nodes right before it. There are fewer of them when the
+/// operator uses values of an earlier input, like the previous lines of the REPL: those are only
+/// on the stack when the program runs.
+fn operands(stack: &mut Vec<AstNode>, count: usize) -> Vec<AstNode> {
+  stack.split_off(stack.len().saturating_sub(count))
+}
+
 fn parse_ast(node: &ParseTreeNode) -> MietteResult<Vec<AstNode>> {
   // Iterate for each through the leaves of the tree, if the leave is a Integer push it to the
   // stack, if it is a operator pop the last two elements of the stack and create a new node
@@ -288,9 +438,31 @@ fn parse_ast(node: &ParseTreeNode) -> MietteResult<Vec<AstNode>> {
   let mut traverse_stack: Vec<&ParseTreeNode> = Vec::new();
   let mut current_node = node;
   loop {
-    if let ParseTreeNode::NonTerminal(_, children, _) = current_node {
-      for child in children.iter().rev() {
-        traverse_stack.push(child);
+    if let ParseTreeNode::NonTerminal(symbol, children, _) = current_node {
+      match symbol.get_name().as_str() {
+        "var-declaration" | "assign-to-identifier" | "cast" => stack.push(header_node(children)),
+        "range-statement" => {
+          let (range, header_len) = range_statement(children);
+          stack.push(range);
+
+          // Only the body (Do <statement> End) is left to traverse
+          for child in children.iter().skip(header_len).rev() {
+            traverse_stack.push(child);
+          }
+        }
+        "proc-definition" => {
+          stack.push(proc_definition(children)?);
+
+          // Only the body (Do <statement> End) is left to traverse
+          for child in children.iter().skip(3).rev() {
+            traverse_stack.push(child);
+          }
+        }
+        _ => {
+          for child in children.iter().rev() {
+            traverse_stack.push(child);
+          }
+        }
       }
     } else if let ParseTreeNode::Terminal(token, span) = current_node {
       match token {
@@ -318,12 +490,19 @@ fn parse_ast(node: &ParseTreeNode) -> MietteResult<Vec<AstNode>> {
             span: *span,
           });
         }
+        Token::Boolean(boolean) => {
+          stack.push(AstNode {
+            symbol: Symbol::Terminal(boolean.to_string()),
+            children: Vec::new(),
+            token: token.clone(),
+            span: *span,
+          });
+        }
         Token::ArithmeticOp { .. } => {
-          let right = stack.pop().unwrap();
-          let left = stack.pop().unwrap();
+          let operands = operands(&mut stack, 2);
           stack.push(AstNode {
             symbol: Symbol::Terminal(token.to_string()),
-            children: vec![left, right],
+            children: operands,
             token: token.clone(),
             span: *span,
           });
@@ -337,21 +516,20 @@ fn parse_ast(node: &ParseTreeNode) -> MietteResult<Vec<AstNode>> {
           });
         }
         Token::ComparisonOp { .. } => {
-          let right = stack.pop().unwrap();
-          let left = stack.pop().unwrap();
+          let operands = operands(&mut stack, 2);
 
           stack.push(AstNode {
             symbol: Symbol::Terminal(token.to_string()),
-            children: vec![left, right],
+            children: operands,
             token: token.clone(),
             span: *span,
           });
         }
         Token::If => {
-          let condition = stack.pop().unwrap();
+          let condition = operands(&mut stack, 1);
           stack.push(AstNode {
             symbol: Symbol::Terminal(token.to_string()),
-            children: vec![condition],
+            children: condition,
             token: token.clone(),
             span: *span,
           });
@@ -364,6 +542,30 @@ fn parse_ast(node: &ParseTreeNode) -> MietteResult<Vec<AstNode>> {
             span: *span,
           });
         }
+        Token::Identifier(name) => {
+          stack.push(AstNode {
+            symbol: Symbol::Terminal(name.to_string()),
+            children: Vec::new(),
+            token: token.clone(),
+            span: *span,
+          });
+        }
+        Token::While => {
+          stack.push(AstNode {
+            symbol: Symbol::Terminal(token.to_string()),
+            children: Vec::new(),
+            token: token.clone(),
+            span: *span,
+          });
+        }
+        Token::Do => {
+          stack.push(AstNode {
+            symbol: Symbol::Terminal(token.to_string()),
+            children: Vec::new(),
+            token: token.clone(),
+            span: *span,
+          });
+        }
         Token::End => {
           stack.push(AstNode {
             symbol: Symbol::Terminal(token.to_string()),
@@ -401,3 +603,40 @@ fn parse_ast(node: &ParseTreeNode) -> MietteResult<Vec<AstNode>> {
 
   Ok(stack)
 }
+
+#[cfg(test)]
+mod parse_tests {
+  use super::*;
+  use crate::{grammar, lexer::generate::compute_tokens};
+
+  fn parser(glc_contents: &str) -> SLR {
+    let mut glc = grammar::parser::parse(glc_contents).unwrap();
+    glc.compute_follow_set().expand();
+    SLR::new(glc)
+  }
+
+  fn parse(slr: &SLR, source: &str) -> MietteResult<Option<AstNode>> {
+    slr.parse(compute_tokens(source)?, source)
+  }
+
+  #[test]
+  fn test_booleans() {
+    let slr = parser(&std::fs::read_to_string("assets/glc/lang.glc").unwrap());
+
+    assert!(parse(&slr, "true if 1 dump end\n").is_ok());
+    assert!(parse(&slr, "true false < dump\n").is_ok());
+    assert!(parse(&slr, "0 while dup 3 < true = do 1 + end\n").is_ok());
+  }
+
+  #[test]
+  fn test_missing_action_is_an_error() {
+    // Booleans aren't part of this grammar, its tables have no action at all for them
+    let slr = parser("<program> -> Integer;");
+    let error = parse(&slr, "true\n").unwrap_err();
+
+    assert!(matches!(
+      error.downcast_ref::<ParseError>(),
+      Some(ParseError::UnexpectedToken { .. })
+    ));
+  }
+}
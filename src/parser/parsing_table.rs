@@ -1,68 +1,93 @@
-use crate::grammar::Symbol;
+use std::{cmp::Ordering, collections::HashMap};
+
+use crate::grammar::{precedence::Associativity, production::Production, Symbol};
 
 use super::{
   closure::{ClosureItem, Lhs, Rhs},
+  conflicts::Conflict,
   Action,
-  SLR::SLR,
+  SLR::{TableKind, SLR},
 };
 
 impl SLR {
   pub fn build_tables(&mut self) -> &mut Self {
     let grammar_productions = self.grammar.productions.clone();
 
-    self.closure_set.iter().for_each(
-      |(
-        _,
-        ClosureItem {
-          id,
-          transitions,
-          productions,
-          ..
-        },
-      )| {
-        for production in productions {
-          let (_, rhs): (Lhs, Rhs) = production.clone().into();
-          if rhs.last().unwrap() == &Symbol::Dot {
-            let production_without_dot = production.copy_without_dot();
-
-            for (idx, _prod) in grammar_productions.iter().enumerate() {
-              let (lhs, rhs): (Lhs, Rhs) = _prod.clone();
-              if lhs == production_without_dot.lhs && rhs == production_without_dot.rhs {
-                if idx == 0 {
-                  // apply R3
-                  self.action_table.insert((*id, Symbol::End), Action::Accept);
-                  continue;
-                }
-
-                // apply R2
-                let follow_set = self.grammar.follow_set.get(&lhs).unwrap();
-
-                for follow in follow_set {
-                  self
-                    .action_table
-                    .insert((*id, follow.clone()), Action::Reduce(idx));
-                }
+    // The states are visited in order, so the conflicts are always reported the same way
+    let states = self.states.clone();
+
+    // Every action some item of a state asks for, with the production it comes from
+    let mut proposals: Vec<(usize, Symbol, Action, Production)> = Vec::new();
+
+    for ClosureItem {
+      id,
+      transitions,
+      productions,
+      lookaheads,
+      ..
+    } in &states
+    {
+      for production in productions {
+        // The item is complete when the dot reached the end, or when only ε is left after it
+        if matches!(
+          production.next_symbol_after_dot(),
+          None | Some(Symbol::Empty)
+        ) {
+          let production_without_dot = production.copy_without_dot();
+
+          for (idx, _prod) in grammar_productions.iter().enumerate() {
+            let (lhs, rhs): (Lhs, Rhs) = _prod.clone();
+            if lhs == production_without_dot.lhs && rhs == production_without_dot.rhs {
+              if idx == 0 {
+                // apply R3
+                proposals.push((
+                  *id,
+                  Symbol::End,
+                  Action::Accept,
+                  production_without_dot.clone(),
+                ));
+                continue;
+              }
+
+              // apply R2, on the FOLLOW set of the lhs or on the lookaheads of the item
+              let follow_set = match self.kind {
+                TableKind::Slr => self.grammar.follow_set.get(&lhs).unwrap(),
+                TableKind::Lalr | TableKind::Lr1 => &lookaheads[production],
+              };
+
+              for follow in follow_set {
+                proposals.push((
+                  *id,
+                  follow.clone(),
+                  Action::Reduce(idx),
+                  production_without_dot.clone(),
+                ));
               }
             }
           }
         }
+      }
 
-        for (symbol, goto) in transitions {
-          // apply R1 and R4
-          match symbol {
-            Symbol::Terminal(_) => {
-              self
-                .action_table
-                .insert((*id, symbol.clone()), Action::Shift(*goto));
+      for (symbol, goto) in transitions {
+        // apply R1 and R4
+        match symbol {
+          Symbol::Terminal(_) => {
+            for item in productions
+              .iter()
+              .filter(|item| item.next_symbol_after_dot().as_ref() == Some(symbol))
+            {
+              proposals.push((*id, symbol.clone(), Action::Shift(*goto), item.clone()));
             }
-            Symbol::NonTerminal(_) => {
-              self.goto_table.insert((*id, symbol.clone()), *goto);
-            }
-            _ => {}
           }
+          Symbol::NonTerminal(_) => {
+            self.goto_table.insert((*id, symbol.clone()), *goto);
+          }
+          _ => {}
         }
-      },
-    );
+      }
+    }
+
+    self.add_proposals(proposals, &states);
 
     // define all the reduce actions
     for key in self.action_table.clone().keys() {
@@ -83,6 +108,88 @@ impl SLR {
     self
   }
 
+  /// Fill the action table with the proposals, in order. When a state gets more than one action
+  /// for a lookahead the precedences pick one, else the last one is kept and the conflict is
+  /// recorded.
+  fn add_proposals(
+    &mut self,
+    proposals: Vec<(usize, Symbol, Action, Production)>,
+    states: &[ClosureItem],
+  ) {
+    let mut competing: HashMap<(usize, Symbol), Vec<(Action, Production)>> = HashMap::new();
+
+    for (state, lookahead, action, production) in proposals {
+      self
+        .action_table
+        .insert((state, lookahead.clone()), action.clone());
+
+      let actions = competing.entry((state, lookahead)).or_default();
+      if !actions.contains(&(action.clone(), production.clone())) {
+        actions.push((action, production));
+      }
+    }
+
+    for ((state, lookahead), actions) in competing {
+      if actions.iter().all(|(action, _)| action == &actions[0].0) {
+        continue;
+      }
+
+      if let Some(action) = self.resolve_with_precedence(&lookahead, &actions) {
+        self.action_table.insert((state, lookahead), action);
+        continue;
+      }
+
+      self.conflicts.push(Conflict {
+        state,
+        lookahead,
+        actions,
+        items: states
+          .iter()
+          .find(|closure| closure.id == state)
+          .map(|closure| closure.productions.clone())
+          .unwrap_or_default(),
+      });
+    }
+
+    self
+      .conflicts
+      .sort_by_key(|conflict| (conflict.state, conflict.lookahead.get_name()));
+  }
+
+  /// Settle a shift/reduce conflict like yacc: the production reduces when its precedence is
+  /// higher than the one of the lookahead, or equal and left associative. Nothing is settled when
+  /// one of them has no precedence.
+  fn resolve_with_precedence(
+    &self,
+    lookahead: &Symbol,
+    actions: &[(Action, Production)],
+  ) -> Option<Action> {
+    let (shift, _) = actions
+      .iter()
+      .find(|(action, _)| matches!(action, Action::Shift(_)))?;
+
+    let reduces = actions
+      .iter()
+      .filter(|(action, _)| !matches!(action, Action::Shift(_)))
+      .collect::<Vec<_>>();
+    let [(reduce @ Action::Reduce(_), production)] = reduces[..] else {
+      return None;
+    };
+
+    let token = self.grammar.precedence.get(lookahead)?;
+    let rule = self.grammar.production_precedence(production)?;
+
+    Some(match rule.level.cmp(&token.level) {
+      Ordering::Greater => reduce.clone(),
+      Ordering::Less => shift.clone(),
+      Ordering::Equal => match token.associativity {
+        Associativity::Left => reduce.clone(),
+        Associativity::Right => shift.clone(),
+        Associativity::NonAssoc => Action::Error,
+      },
+    })
+  }
+
   fn add_action(&mut self, row: usize, action: &Action) {
     let terminals = self.grammar.terminals();
 
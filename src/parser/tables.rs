@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::grammar::{Grammar, Symbol};
+
+use super::{
+  Action,
+  SLR::{TableKind, SLR},
+};
+
+/// What the parser needs from an [`SLR`] automaton. The build script serializes the tables of
+/// the language grammar, so the compiler doesn't build the automaton every time it runs.
+#[derive(Serialize, Deserialize)]
+pub struct ParsingTables {
+  pub grammar: Grammar,
+  pub kind: TableKind,
+  pub action_table: HashMap<(usize, Symbol), Action>,
+  pub goto_table: HashMap<(usize, Symbol), usize>,
+}
+
+impl ParsingTables {
+  pub fn encode(&self) -> Vec<u8> {
+    bincode::serialize(self).expect("The parsing tables can always be serialized")
+  }
+
+  pub fn decode(bytes: &[u8]) -> bincode::Result<Self> {
+    bincode::deserialize(bytes)
+  }
+}
+
+impl From<SLR> for ParsingTables {
+  fn from(slr: SLR) -> Self {
+    Self {
+      grammar: slr.grammar,
+      kind: slr.kind,
+      action_table: slr.action_table,
+      goto_table: slr.goto_table,
+    }
+  }
+}
+
+/// The states and the conflicts only matter while building the tables, the parser is left
+/// without them
+impl From<ParsingTables> for SLR {
+  fn from(tables: ParsingTables) -> Self {
+    SLR {
+      grammar: tables.grammar,
+      kind: tables.kind,
+      action_table: tables.action_table,
+      goto_table: tables.goto_table,
+      states: Vec::new(),
+      conflicts: Vec::new(),
+    }
+  }
+}
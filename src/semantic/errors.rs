@@ -29,6 +29,25 @@ pub enum SemanticError {
 
     #[help]
     advice: String,
+
+    #[label = "Here"]
+    extension_src: (usize, usize),
+  },
+
+  #[error("Type mismatch")]
+  #[diagnostic(code(semantic_error::type_mismatch))]
+  TypeMismatch {
+    #[source_code]
+    input: String,
+
+    #[help]
+    advice: String,
+
+    #[label = "Pushed here"]
+    value_extension_src: (usize, usize),
+
+    #[label = "Used here"]
+    extension_src: (usize, usize),
   },
 
   #[error("Invalid Cast")]
@@ -88,6 +107,19 @@ pub enum SemanticError {
     extension_src: (usize, usize),
   },
 
+  #[error("Invalid Assignment")]
+  #[diagnostic(code(semantic_error::assign_to_counter))]
+  AssignToCounter {
+    #[source_code]
+    input: String,
+
+    #[help]
+    advice: String,
+
+    #[label = "Here"]
+    extension_src: (usize, usize),
+  },
+
   #[error("Operators type differ")]
   #[diagnostic(code(semantic_error::operators_type_differ))]
   OperatorsTypeDiffer {
@@ -113,4 +145,105 @@ pub enum SemanticError {
     #[label = "Here"]
     extension_src: (usize, usize),
   },
+
+  /// Procedure errors
+
+  #[error("Procedure signature mismatch")]
+  #[diagnostic(code(semantic_error::procedure_signature_mismatch))]
+  ProcedureSignatureMismatch {
+    #[source_code]
+    input: String,
+
+    #[help]
+    advice: String,
+
+    #[label = "Here"]
+    extension_src: (usize, usize),
+  },
+
+  /// Control flow errors
+
+  #[error("Invalid condition")]
+  #[diagnostic(code(semantic_error::invalid_condition))]
+  InvalidCondition {
+    #[source_code]
+    input: String,
+
+    #[help]
+    advice: String,
+
+    #[label = "Here"]
+    extension_src: (usize, usize),
+  },
+
+  #[error("Invalid range")]
+  #[diagnostic(code(semantic_error::invalid_range))]
+  InvalidRange {
+    #[source_code]
+    input: String,
+
+    #[help]
+    advice: String,
+
+    #[label = "Here"]
+    extension_src: (usize, usize),
+  },
+
+  #[error("Unbalanced loop")]
+  #[diagnostic(code(semantic_error::unbalanced_loop))]
+  UnbalancedLoop {
+    #[source_code]
+    input: String,
+
+    #[help]
+    advice: String,
+
+    #[label = "Loop starts here"]
+    start_extension_src: (usize, usize),
+
+    #[label = "Loop ends here"]
+    extension_src: (usize, usize),
+  },
+
+  #[error("Unbalanced branches")]
+  #[diagnostic(code(semantic_error::unbalanced_branches))]
+  UnbalancedBranches {
+    #[source_code]
+    input: String,
+
+    #[help]
+    advice: String,
+
+    #[label = "Branch starts here"]
+    start_extension_src: (usize, usize),
+
+    #[label = "Branch ends here"]
+    extension_src: (usize, usize),
+  },
+
+  #[error("Else without if")]
+  #[diagnostic(code(semantic_error::else_without_if))]
+  ElseWithoutIf {
+    #[source_code]
+    input: String,
+
+    #[help]
+    advice: String,
+
+    #[label = "Here"]
+    extension_src: (usize, usize),
+  },
+
+  #[error("Unmatched end")]
+  #[diagnostic(code(semantic_error::unmatched_end))]
+  UnmatchedEnd {
+    #[source_code]
+    input: String,
+
+    #[help]
+    advice: String,
+
+    #[label = "Here"]
+    extension_src: (usize, usize),
+  },
 }
@@ -1,13 +1,13 @@
 use crate::{
-  lexer::tokens::{ArithmeticOperators, StackOperators, Token},
+  lexer::tokens::{ArithmeticOperators, StackOperators, Token, Type},
   parser::parse::AstNode,
 };
 use miette::Result as MietteResult;
 
 use self::{
   errors::SemanticError,
-  stack_frame::StackFrame,
-  symbol_table::{SymbolTable, Value},
+  stack_frame::{StackEntry, StackFrame, StackType},
+  symbol_table::{Symbol, SymbolKind, SymbolTable, Value},
 };
 
 pub mod ast;
@@ -15,9 +15,45 @@ pub mod errors;
 pub mod stack_frame;
 pub mod symbol_table;
 
+/// The blocks that are still waiting for their `end`
+#[derive(Clone)]
+enum Block {
+  /// An `if` without `else` must leave the stack as it was after the condition was popped
+  If {
+    entry_stack: StackFrame,
+    span: (usize, usize),
+  },
+  /// The `else` branch must leave the same stack as the `if` branch
+  Else {
+    then_stack: StackFrame,
+    span: (usize, usize),
+  },
+  /// The stack outside of the procedure is put aside while the body is analyzed
+  Proc {
+    name: String,
+    outputs: Vec<Type>,
+    outer_stack: StackFrame,
+  },
+  /// Every iteration must start with the same stack, so it is kept to be compared against the
+  /// stack after the condition and after the body
+  While {
+    entry_stack: StackFrame,
+    span: (usize, usize),
+  },
+  /// The counter lives in its own scope, the body must leave the stack as it found it
+  Range {
+    entry_stack: StackFrame,
+    span: (usize, usize),
+  },
+}
+
+/// Walks the program keeping track of the types on the stack (not their values), so every
+/// operation can be checked against what it will find at runtime
+#[derive(Clone)]
 pub struct SemanticAnalyzer {
   pub symbol_table: SymbolTable,
   stack: StackFrame,
+  blocks: Vec<Block>,
   source_code: String,
 }
 
@@ -26,10 +62,21 @@ impl SemanticAnalyzer {
     Self {
       symbol_table: SymbolTable::new(source_code.to_string()),
       stack: Default::default(),
+      blocks: vec![],
       source_code,
     }
   }
 
+  /// Replace the source the errors point into, when the program grows (e.g. in the REPL)
+  pub fn set_source(&mut self, source_code: String) {
+    self.symbol_table.source = source_code.clone();
+    self.source_code = source_code;
+  }
+
+  pub fn clear_stack(&mut self) {
+    self.stack = StackFrame::default();
+  }
+
   pub fn analyze(&mut self, ast: &AstNode) -> MietteResult<()> {
     match ast.token.clone() {
       Token::Program => {
@@ -37,52 +84,312 @@ impl SemanticAnalyzer {
           self.analyze(child)?;
         }
       }
-      Token::Integer(value) => {
-        self.stack.values.push(Value::I32(value));
-      }
-      Token::Float(value) => self.stack.values.push(Value::F32(value)),
-      Token::String(value) => self.stack.values.push(Value::String(value)),
-      Token::Boolean(value) => self.stack.values.push(Value::Bool(value)),
+      Token::Integer(_) => self.stack.push(StackType::I32, ast.span),
+      Token::Float(_) => self.stack.push(StackType::F32, ast.span),
+      Token::String(_) => self.stack.push(StackType::String, ast.span),
+      Token::Boolean(_) => self.stack.push(StackType::Bool, ast.span),
       Token::StackOps(operator) => {
         match operator {
-          StackOperators::Dump => self.stack_pop()?,
-          StackOperators::Dup => self.stack_dup()?,
-          StackOperators::Drop => self.stack_pop()?,
+          StackOperators::Dump => self.stack_pop(ast.span)?,
+          StackOperators::Dup => self.stack_dup(ast.span)?,
+          StackOperators::Drop => self.stack_pop(ast.span)?,
         };
       }
       Token::ArithmeticOp(operator) => {
-        self.analyze(ast.children.get(0).unwrap()).unwrap(); // left side
-        self.analyze(ast.children.get(1).unwrap()).unwrap(); // right side
-
-        let left = self.stack_pop()?;
-        let right = self.stack_pop()?;
-
-        match (left, right) {
-          (Value::I32(left), Value::I32(right)) => {
-            self.stack.values.push(Value::I32(match operator {
-              ArithmeticOperators::Plus => left + right,
-              ArithmeticOperators::Minus => left - right,
-              ArithmeticOperators::Times => left * right,
-              ArithmeticOperators::Divide => left / right,
-              ArithmeticOperators::Modulo => left % right,
-            }))
+        // The operands that are not children were pushed by an earlier input
+        for operand in ast.children.iter() {
+          self.analyze(operand)?;
+        }
+
+        let right = self.stack_pop(ast.span)?;
+        let left = self.stack_pop(ast.span)?;
+
+        let result = match (&left.type_, &right.type_, operator) {
+          // Strings can only be concatenated
+          (StackType::String, StackType::String, ArithmeticOperators::Plus) => StackType::String,
+          _ => self.promote(&left, &right, ast.span)?,
+        };
+
+        self.stack.push(result, ast.span);
+      }
+      Token::ComparisonOp(_) => {
+        // The operands that are not children were pushed by an earlier input
+        for operand in ast.children.iter() {
+          self.analyze(operand)?;
+        }
+
+        let right = self.stack_pop(ast.span)?;
+        let left = self.stack_pop(ast.span)?;
+
+        // Numbers are promoted, anything else can only be compared with its own type
+        if left.type_ != right.type_ {
+          self.promote(&left, &right, ast.span)?;
+        }
+
+        self.stack.push(StackType::Bool, ast.span);
+      }
+      Token::CastOp => {
+        let type_ = ast.cast_type().ok_or(SemanticError::InvalidCast {
+          input: self.source_code.clone(),
+          advice: "Expected the type to cast to".to_string(),
+          extension_src: ast.span,
+        })?;
+
+        let value = self.stack_pop(ast.span)?;
+        if value.type_ == StackType::String {
+          Err(SemanticError::InvalidCast {
+            input: self.source_code.clone(),
+            advice: format!("A string can't be cast to {}", type_),
+            extension_src: ast.span,
+          })?
+        }
+
+        self.stack.push(StackType::from(type_), ast.span);
+      }
+      Token::If => {
+        if let Some(condition) = ast.children.first() {
+          self.analyze(condition)?;
+        }
+
+        self.expect_condition(ast.span, "an if")?;
+
+        self.blocks.push(Block::If {
+          entry_stack: self.stack.clone(),
+          span: ast.span,
+        });
+      }
+      Token::Else => match self.blocks.pop() {
+        Some(Block::If { entry_stack, span }) => {
+          let then_stack = std::mem::replace(&mut self.stack, entry_stack);
+          self.blocks.push(Block::Else { then_stack, span });
+        }
+        _ => Err(SemanticError::ElseWithoutIf {
+          input: self.source_code.clone(),
+          advice: "An `else` must follow an `if`".to_string(),
+          extension_src: ast.span,
+        })?,
+      },
+      Token::While => self.blocks.push(Block::While {
+        entry_stack: self.stack.clone(),
+        span: ast.span,
+      }),
+      Token::Range => {
+        // The upper bound is on top of the optional start
+        let mut bounds = vec![self.stack_pop(ast.span)?];
+        if ast.range_has_start() {
+          bounds.push(self.stack_pop(ast.span)?);
+        }
+
+        if let Some(bound) = bounds.iter().find(|bound| bound.type_ != StackType::I32) {
+          Err(SemanticError::InvalidRange {
+            input: self.source_code.clone(),
+            advice: format!("The bounds of a range must be i32, found {}", bound.type_),
+            extension_src: ast.span,
+          })?
+        }
+
+        self.symbol_table.enter_scope();
+
+        if let Some((counter, (start, len))) =
+          ast.children.iter().find_map(|child| match &child.token {
+            Token::Identifier(name) => Some((name, child.span)),
+            _ => None,
+          })
+        {
+          self
+            .symbol_table
+            .define_counter(counter, (start, start + len))?;
+        }
+
+        self.blocks.push(Block::Range {
+          entry_stack: self.stack.clone(),
+          span: ast.span,
+        });
+      }
+      Token::Proc => {
+        let name = ast.proc_name().unwrap_or_default().to_string();
+        let (inputs, outputs) = ast.proc_signature();
+
+        self.symbol_table.define_procedure(
+          &name,
+          (inputs.clone(), outputs.clone()),
+          (ast.span.0, ast.span.0 + ast.span.1),
+        )?;
+        self.symbol_table.enter_scope();
+
+        // The body starts with only the declared inputs on the stack
+        let outer_stack = std::mem::replace(&mut self.stack, Self::frame_of(&inputs, ast.span));
+
+        self.blocks.push(Block::Proc {
+          name,
+          outputs,
+          outer_stack,
+        });
+      }
+      Token::Do => {
+        if let Some(Block::While { entry_stack, span }) = self.blocks.last() {
+          let (entry_stack, span) = (entry_stack.clone(), *span);
+
+          self.expect_condition(ast.span, "a loop")?;
+          self.expect_loop_balanced(&entry_stack, span, ast.span, "condition")?;
+        }
+      }
+      Token::End => match self.blocks.pop() {
+        Some(Block::If { entry_stack, span }) => {
+          if !self.stack.same_shape(&entry_stack) {
+            Err(SemanticError::UnbalancedBranches {
+              input: self.source_code.clone(),
+              advice: format!(
+                "An `if` without `else` must leave the stack as it found it {}, but it leaves {}",
+                StackFrame::describe(&entry_stack.types()),
+                StackFrame::describe(&self.stack.types()),
+              ),
+              start_extension_src: span,
+              extension_src: ast.span,
+            })?
           }
-          (Value::F32(left), Value::F32(right)) => {
-            self.stack.values.push(Value::F32(match operator {
-              ArithmeticOperators::Plus => left + right,
-              ArithmeticOperators::Minus => left - right,
-              ArithmeticOperators::Times => left * right,
-              ArithmeticOperators::Divide => left / right,
-              ArithmeticOperators::Modulo => left % right,
-            }))
+        }
+        Some(Block::Else { then_stack, span }) => {
+          if !self.stack.same_shape(&then_stack) {
+            Err(SemanticError::UnbalancedBranches {
+              input: self.source_code.clone(),
+              advice: format!(
+                "Both branches must leave the same stack, the `if` leaves {} but the `else` leaves {}",
+                StackFrame::describe(&then_stack.types()),
+                StackFrame::describe(&self.stack.types()),
+              ),
+              start_extension_src: span,
+              extension_src: ast.span,
+            })?
+          }
+        }
+        Some(Block::Proc {
+          name,
+          outputs,
+          outer_stack,
+        }) => {
+          let expected = Self::frame_of(&outputs, ast.span);
+
+          if !self.stack.same_shape(&expected) {
+            Err(SemanticError::ProcedureSignatureMismatch {
+              input: self.source_code.clone(),
+              advice: format!(
+                "The procedure `{}` should leave {} on the stack, but it leaves {}",
+                name,
+                StackFrame::describe(&expected.types()),
+                StackFrame::describe(&self.stack.types()),
+              ),
+              extension_src: ast.span,
+            })?
           }
-          _ => Err(SemanticError::OperatorsTypeDiffer {
+
+          self.stack = outer_stack;
+          self.symbol_table.exit_scope();
+        }
+        Some(Block::While { entry_stack, span }) => {
+          self.expect_loop_balanced(&entry_stack, span, ast.span, "body")?;
+        }
+        Some(Block::Range { entry_stack, span }) => {
+          self.expect_loop_balanced(&entry_stack, span, ast.span, "body")?;
+          self.symbol_table.exit_scope();
+        }
+        None => Err(SemanticError::UnmatchedEnd {
+          input: self.source_code.clone(),
+          advice: "This `end` doesn't close any block".to_string(),
+          extension_src: ast.span,
+        })?,
+      },
+      Token::DefType(type_) => {
+        let (name, (start, len)) = self.variable(ast)?;
+
+        self
+          .symbol_table
+          .define(&name, Value::from(&type_), (start, start + len))?;
+      }
+      Token::AtSign => {
+        let (name, span) = self.variable(ast)?;
+
+        let variable = match self.symbol_table.lookup(&name) {
+          Some(
+            symbol @ Symbol {
+              kind: SymbolKind::Variable,
+              ..
+            },
+          ) => symbol,
+          Some(Symbol {
+            kind: SymbolKind::Counter,
+            ..
+          }) => Err(SemanticError::AssignToCounter {
             input: self.source_code.clone(),
-            advice: "You can only add two values of the same type".to_string(),
-            extension_src: ast.span,
+            advice: format!(
+              "`{}` counts the iterations of a range, it can't be assigned",
+              name
+            ),
+            extension_src: span,
+          })?,
+          _ => Err(SemanticError::VariableNotDeclared {
+            input: self.source_code.clone(),
+            advice: format!("There is no variable named `{}` in scope", name),
+            extension_src: span,
           })?,
+        };
+
+        let value = self.stack_pop(ast.span)?;
+        let expected = StackType::from(&variable.value);
+        if value.type_ != expected {
+          Err(SemanticError::VariableTypeMismatch {
+            input: self.source_code.clone(),
+            advice: format!(
+              "The variable `{}` is {}, but the top of the stack is {}",
+              name, expected, value.type_
+            ),
+            extension_src: ast.span,
+          })?
         }
       }
+      Token::Identifier(name) => match self.symbol_table.lookup(&name) {
+        Some(Symbol {
+          kind: SymbolKind::Procedure(inputs, outputs),
+          ..
+        }) => {
+          let expected = Self::frame_of(&inputs, ast.span);
+
+          let mut arguments = StackFrame::default();
+          for _ in inputs.iter() {
+            arguments.values.insert(0, self.stack_pop(ast.span)?);
+          }
+
+          if !arguments.same_shape(&expected) {
+            Err(SemanticError::ProcedureSignatureMismatch {
+              input: self.source_code.clone(),
+              advice: format!(
+                "The procedure `{}` expects {} on the stack, but found {}",
+                name,
+                StackFrame::describe(&expected.types()),
+                StackFrame::describe(&arguments.types()),
+              ),
+              extension_src: ast.span,
+            })?
+          }
+
+          for output in outputs.iter() {
+            self.stack.push(StackType::from(output), ast.span);
+          }
+        }
+        Some(Symbol {
+          kind: SymbolKind::Variable | SymbolKind::Counter,
+          value,
+          ..
+        }) => self.stack.push(StackType::from(&value), ast.span),
+        None => Err(SemanticError::VariableNotDeclared {
+          input: self.source_code.clone(),
+          advice: format!(
+            "There is no variable or procedure named `{}` in scope",
+            name
+          ),
+          extension_src: ast.span,
+        })?,
+      },
       _ => Err(SemanticError::Unimplemented {
         input: self.source_code.clone(),
         advice: "Semantic validation not implemented".to_string(),
@@ -93,26 +400,216 @@ impl SemanticAnalyzer {
     Ok(())
   }
 
-  pub fn stack_dup(&mut self) -> MietteResult<Value> {
-    match self.stack.values.clone().last() {
-      Some(value) => {
-        self.stack.values.push(value.clone());
-        Ok(value.clone())
+  /// The stack holding the given types, as if they were all pushed at `span`
+  fn frame_of(types: &[Type], span: (usize, usize)) -> StackFrame {
+    let mut frame = StackFrame::default();
+    for type_ in types {
+      frame.push(StackType::from(type_), span);
+    }
+    frame
+  }
+
+  /// The type both operands of an arithmetic operation or a comparison are converted to
+  fn promote(
+    &self,
+    left: &StackEntry,
+    right: &StackEntry,
+    span: (usize, usize),
+  ) -> MietteResult<StackType> {
+    if let Some(type_) = left.type_.promote(&right.type_) {
+      return Ok(type_);
+    }
+
+    // Blame the operand that isn't a number, or the right one if neither is
+    let (culprit, other) = if left.type_.is_numeric() {
+      (right, left)
+    } else if right.type_.is_numeric() {
+      (left, right)
+    } else {
+      (right, left)
+    };
+
+    let expected = if other.type_.is_numeric() {
+      other.type_.to_string()
+    } else {
+      "a number".to_string()
+    };
+
+    Err(SemanticError::TypeMismatch {
+      input: self.source_code.clone(),
+      advice: format!("Expected {}, found {}", expected, culprit.type_),
+      value_extension_src: culprit.span,
+      extension_src: span,
+    })?
+  }
+
+  /// The condition of an `if` or of a loop is popped and must be a bool
+  fn expect_condition(&mut self, span: (usize, usize), construct: &str) -> MietteResult<()> {
+    match self.stack.values.pop() {
+      Some(StackEntry {
+        type_: StackType::Bool,
+        ..
+      }) => Ok(()),
+      other => Err(SemanticError::InvalidCondition {
+        input: self.source_code.clone(),
+        advice: format!(
+          "The condition of {} must leave a bool on the stack, found {}",
+          construct,
+          other.map_or("an empty stack".to_string(), |entry| entry.type_.to_string())
+        ),
+        extension_src: span,
+      })?,
+    }
+  }
+
+  /// The name and the span of the variable a declaration or an assignment refers to
+  fn variable(&self, ast: &AstNode) -> MietteResult<(String, (usize, usize))> {
+    match (ast.variable_name(), ast.children.first()) {
+      (Some(name), Some(identifier)) => Ok((name.to_string(), identifier.span)),
+      _ => Err(SemanticError::VariableNotDeclared {
+        input: self.source_code.clone(),
+        advice: "Expected the name of a variable".to_string(),
+        extension_src: ast.span,
+      })?,
+    }
+  }
+
+  /// The stack must have the same shape it had when the loop started
+  fn expect_loop_balanced(
+    &self,
+    entry_stack: &StackFrame,
+    start: (usize, usize),
+    end: (usize, usize),
+    part: &str,
+  ) -> MietteResult<()> {
+    if self.stack.same_shape(entry_stack) {
+      return Ok(());
+    }
+
+    Err(SemanticError::UnbalancedLoop {
+      input: self.source_code.clone(),
+      advice: format!(
+        "The loop {} must leave the stack as it was before the loop {}, but it leaves {}",
+        part,
+        StackFrame::describe(&entry_stack.types()),
+        StackFrame::describe(&self.stack.types()),
+      ),
+      start_extension_src: start,
+      extension_src: end,
+    })?
+  }
+
+  pub fn stack_dup(&mut self, span: (usize, usize)) -> MietteResult<StackEntry> {
+    match self.stack.values.last().cloned() {
+      Some(entry) => {
+        self.stack.push(entry.type_.clone(), span);
+        Ok(entry)
       }
       None => Err(SemanticError::EmptyStack {
         input: self.source_code.clone(),
         advice: "You can't dup an empty stack".to_string(),
+        extension_src: span,
       })?,
     }
   }
 
-  pub fn stack_pop(&mut self) -> MietteResult<Value> {
+  pub fn stack_pop(&mut self, span: (usize, usize)) -> MietteResult<StackEntry> {
     match self.stack.values.pop() {
-      Some(value) => Ok(value),
+      Some(entry) => Ok(entry),
       None => Err(SemanticError::EmptyStack {
         input: self.source_code.clone(),
         advice: "You can't pop an empty stack".to_string(),
+        extension_src: span,
       })?,
     }
   }
 }
+
+#[cfg(test)]
+mod semantic_tests {
+  use super::*;
+  use crate::{frontend::Program, grammar::Symbol};
+
+  /// The semantic error of a program, `None` when it is valid
+  fn check(source: &str) -> Option<SemanticError> {
+    Program::from_source(source.to_string(), None)
+      .err()
+      .map(|report| report.downcast::<SemanticError>().unwrap())
+  }
+
+  fn advice(error: Option<SemanticError>) -> String {
+    match error {
+      Some(
+        SemanticError::TypeMismatch { advice, .. }
+        | SemanticError::UnbalancedBranches { advice, .. },
+      ) => advice,
+      other => panic!("Unexpected result: {:?}", other),
+    }
+  }
+
+  #[test]
+  fn test_type_mismatch() {
+    // The example of `assets/lang/test.pile`, caught before running
+    assert_eq!(
+      advice(check("\"oi\" 2 + dump\n")),
+      "Expected i32, found string"
+    );
+    assert_eq!(advice(check("1 \"a\" -\n")), "Expected i32, found string");
+    assert_eq!(
+      advice(check("\"a\" \"b\" *\n")),
+      "Expected a number, found string"
+    );
+
+    assert!(check("1 2.5 + dump\n").is_none());
+    assert!(check("\"a\" \"b\" + dump\n").is_none());
+  }
+
+  #[test]
+  fn test_if_else_balance() {
+    assert!(check("1 2 < if 1 else 2 end dump\n").is_none());
+    assert!(check("5 1 2 < if 1 + end dump\n").is_none());
+
+    assert_eq!(
+      advice(check("1 2 < if 1 end\n")),
+      "An `if` without `else` must leave the stack as it found it [], but it leaves [i32]"
+    );
+    assert_eq!(
+      advice(check("1 2 < if 1 else \"a\" end\n")),
+      "Both branches must leave the same stack, the `if` leaves [i32] but the `else` leaves [string]"
+    );
+  }
+
+  #[test]
+  fn test_stray_else_and_end() {
+    // The parser never produces them, but the analyzer doesn't rely on it
+    let node = |token, length| AstNode {
+      symbol: Symbol::Empty,
+      children: vec![],
+      token,
+      span: (0, length),
+    };
+
+    let mut analyzer = SemanticAnalyzer::new("else".to_string());
+    let error = analyzer.analyze(&node(Token::Else, 4)).unwrap_err();
+    assert!(matches!(
+      error.downcast::<SemanticError>().unwrap(),
+      SemanticError::ElseWithoutIf { .. }
+    ));
+
+    let mut analyzer = SemanticAnalyzer::new("end".to_string());
+    let error = analyzer.analyze(&node(Token::End, 3)).unwrap_err();
+    assert!(matches!(
+      error.downcast::<SemanticError>().unwrap(),
+      SemanticError::UnmatchedEnd { .. }
+    ));
+  }
+
+  #[test]
+  fn test_range_counter_is_read_only() {
+    assert!(check("3 range i do i dump end\n").is_none());
+    assert!(matches!(
+      check("3 range i do 1 @i end\n"),
+      Some(SemanticError::AssignToCounter { .. })
+    ));
+  }
+}
@@ -1,8 +1,114 @@
+use std::fmt::Display;
+
+use crate::lexer::tokens::Type;
+
 use super::symbol_table::Value;
 
-// The stack frame will actually keep track of the values in the stack
+/// The type of a value on the stack, the values themselves are only known at runtime
+#[derive(Debug, Clone, PartialEq)]
+pub enum StackType {
+  I32,
+  I64,
+  F32,
+  F64,
+  Bool,
+  String,
+}
+
+impl StackType {
+  /// The language type, strings don't have one
+  pub fn as_type(&self) -> Option<Type> {
+    match self {
+      StackType::I32 => Some(Type::I32),
+      StackType::I64 => Some(Type::I64),
+      StackType::F32 => Some(Type::F32),
+      StackType::F64 => Some(Type::F64),
+      StackType::Bool => Some(Type::Bool),
+      StackType::String => None,
+    }
+  }
+
+  pub fn is_numeric(&self) -> bool {
+    self.as_type().is_some_and(|type_| type_.is_numeric())
+  }
+
+  /// The type of the result of an arithmetic operation on two numbers
+  pub fn promote(&self, other: &StackType) -> Option<StackType> {
+    Some(StackType::from(&self.as_type()?.promote(&other.as_type()?)?))
+  }
+}
+
+impl From<&Type> for StackType {
+  fn from(type_: &Type) -> Self {
+    match type_ {
+      Type::I32 => StackType::I32,
+      Type::I64 => StackType::I64,
+      Type::F32 => StackType::F32,
+      Type::F64 => StackType::F64,
+      Type::Bool => StackType::Bool,
+    }
+  }
+}
+
+impl From<&Value> for StackType {
+  fn from(value: &Value) -> Self {
+    match value {
+      Value::I32(_) => StackType::I32,
+      Value::I64(_) => StackType::I64,
+      Value::F32(_) => StackType::F32,
+      Value::F64(_) => StackType::F64,
+      Value::Bool(_) => StackType::Bool,
+      Value::String(_) => StackType::String,
+    }
+  }
+}
+
+impl Display for StackType {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.as_type() {
+      Some(type_) => write!(f, "{}", type_),
+      None => write!(f, "string"),
+    }
+  }
+}
+
+/// A value on the stack, with the span of the code that pushed it
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackEntry {
+  pub type_: StackType,
+  pub span: (usize, usize),
+}
+
+// The stack frame will actually keep track of the types in the stack
 // as the pile is a stack-based language
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct StackFrame {
-  pub values: Vec<Value>,
+  pub values: Vec<StackEntry>,
+}
+
+impl StackFrame {
+  pub fn push(&mut self, type_: StackType, span: (usize, usize)) {
+    self.values.push(StackEntry { type_, span });
+  }
+
+  pub fn types(&self) -> Vec<StackType> {
+    self.values.iter().map(|entry| entry.type_.clone()).collect()
+  }
+
+  /// Two stacks have the same shape when they hold the same types, wherever they were pushed
+  pub fn same_shape(&self, other: &StackFrame) -> bool {
+    self.types() == other.types()
+  }
+
+  /// The types of the stack, from the bottom to the top (e.g. `[i32 string]`)
+  pub fn describe(types: &[StackType]) -> String {
+    format!(
+      "[{}]",
+      types
+        .iter()
+        .map(|type_| type_.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+    )
+  }
 }
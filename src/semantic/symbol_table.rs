@@ -1,6 +1,6 @@
 use std::{collections::HashMap, fmt::Display};
 
-use crate::lexer::tokens::span_to_tuple;
+use crate::lexer::tokens::{span_to_tuple, Type};
 
 use super::errors::SemanticError;
 
@@ -52,6 +52,12 @@ impl Default for Value {
   }
 }
 
+impl From<&Type> for Value {
+  fn from(type_: &Type) -> Self {
+    Value::from(type_.to_string().as_str())
+  }
+}
+
 impl Value {
   pub fn compare_type_to(&self, other: &Value) -> bool {
     matches!(
@@ -77,17 +83,6 @@ impl Value {
     .to_string()
   }
 
-  pub fn cast_to(&self, type_name: &str) -> Result<Value, String> {
-    match (type_name, self) {
-      ("i32", Value::I32(value)) => Ok(Value::I32(*value)),
-      ("i32", Value::F32(value)) => Ok(Value::I32(*value as i32)),
-      _ => Err(format!(
-        "Cannot cast value {:?} to type {}",
-        self, type_name
-      )),
-    }
-  }
-
   pub fn times(&self, other: &Value) -> Result<Value, ()> {
     match (self, other) {
       (Value::I32(value), Value::I32(other)) => Ok(Value::I32(value * other)),
@@ -143,12 +138,24 @@ pub enum Scope {
   Local(usize),
 }
 
+/// What a name refers to
+/// Procedures keep their declared stack effect as (inputs, outputs)
+/// Counters are the read only variables of the range loops
+#[derive(Default, Debug, Clone, PartialEq)]
+pub enum SymbolKind {
+  #[default]
+  Variable,
+  Counter,
+  Procedure(Vec<Type>, Vec<Type>),
+}
+
 /// Symbol information
 /// The symbol information is stored in a struct
-/// The symbol name, the type, the scope, the value and the position
+/// The symbol name, the kind, the type, the scope, the value and the position
 #[derive(Default, Debug, Clone, PartialEq)]
 pub struct Symbol {
   pub name: String,
+  pub kind: SymbolKind,
   pub scope: Scope,
   pub value: Value,
   pub position: (u32, u32),
@@ -157,7 +164,7 @@ pub struct Symbol {
 /// Symbol table
 /// Use a hash map to store the symbols and their informations
 /// The key is the symbol name and the value is the symbol information
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct SymbolTable {
   pub symbols: HashMap<(String, usize), Symbol>,
   pub current_scope: usize,
@@ -200,7 +207,10 @@ impl SymbolTable {
     self.current_scope += 1;
   }
 
+  /// Leaving a scope forgets every symbol that was defined in it
   pub fn exit_scope(&mut self) {
+    let current_scope = self.current_scope;
+    self.symbols.retain(|(_, scope), _| *scope != current_scope);
     self.current_scope -= 1;
   }
 
@@ -208,6 +218,38 @@ impl SymbolTable {
     &mut self,
     name: &str,
     value: Value,
+    position: (usize, usize),
+  ) -> Result<Option<Symbol>, SemanticError> {
+    self.define_symbol(name, SymbolKind::Variable, value, position)
+  }
+
+  pub fn define_counter(
+    &mut self,
+    name: &str,
+    position: (usize, usize),
+  ) -> Result<Option<Symbol>, SemanticError> {
+    self.define_symbol(name, SymbolKind::Counter, Value::I32(0), position)
+  }
+
+  pub fn define_procedure(
+    &mut self,
+    name: &str,
+    (inputs, outputs): (Vec<Type>, Vec<Type>),
+    position: (usize, usize),
+  ) -> Result<Option<Symbol>, SemanticError> {
+    self.define_symbol(
+      name,
+      SymbolKind::Procedure(inputs, outputs),
+      Value::default(),
+      position,
+    )
+  }
+
+  fn define_symbol(
+    &mut self,
+    name: &str,
+    kind: SymbolKind,
+    value: Value,
     (row, col): (usize, usize),
   ) -> Result<Option<Symbol>, SemanticError> {
     if let Some(symbol) = self.symbols.get(&(name.to_string(), self.current_scope)) {
@@ -223,6 +265,7 @@ impl SymbolTable {
       (name.to_string(), self.current_scope),
       Symbol {
         name: name.to_string(),
+        kind,
         scope: self.current_scope.into(),
         value,
         position: (row as u32, col as u32),
@@ -244,10 +287,16 @@ impl SymbolTable {
     None
   }
 
+  /// Update the innermost variable with this name, which may live in an enclosing scope
   pub fn update_variable(&mut self, name: &str, value: Value) -> Result<(), String> {
+    let scope = (0..=self.current_scope)
+      .rev()
+      .find(|scope| self.symbols.contains_key(&(name.to_string(), *scope)))
+      .ok_or(format!("Variable {} is not yet declared", name))?;
+
     self
       .symbols
-      .get_mut(&(name.to_string(), self.current_scope))
+      .get_mut(&(name.to_string(), scope))
       .map(|symbol| {
         symbol.value = value;
         Ok(())
@@ -308,6 +357,23 @@ mod semantic_tests {
     Ok(())
   }
 
+  #[test]
+  fn test_update_enclosing_scope() -> Result<(), Box<dyn std::error::Error>> {
+    let mut symbol_table = SymbolTable::new(String::from("a"));
+
+    symbol_table.define("a", Value::I32(1), (0, 0))?;
+    symbol_table.enter_scope();
+    symbol_table.update_variable("a", Value::I32(2))?;
+    symbol_table.exit_scope();
+
+    let var_a = symbol_table.lookup("a").expect("Variable a not found");
+
+    assert_eq!(var_a.scope, Scope::Global);
+    assert_eq!(var_a.value, Value::I32(2));
+
+    Ok(())
+  }
+
   #[test]
   fn test_local_preference() -> Result<(), Box<dyn std::error::Error>> {
     let mut symbol_table = SymbolTable::new(String::from("a a"));